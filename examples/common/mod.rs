@@ -0,0 +1,98 @@
+//! Workloads shared by the comparison examples
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use internship::IStr;
+use internship::bench::{measure, Measurement};
+
+/// Operations on the string type under comparison
+pub trait Subject: Clone + Eq + Hash {
+    const NAME: &'static str;
+    fn make(src: &str) -> Self;
+}
+
+impl Subject for IStr {
+    const NAME: &'static str = "IStr";
+
+    fn make(src: &str) -> Self {
+        IStr::new(src)
+    }
+}
+
+impl Subject for Rc<str> {
+    const NAME: &'static str = "Rc<str>";
+
+    fn make(src: &str) -> Self {
+        Rc::from(src)
+    }
+}
+
+impl Subject for Arc<str> {
+    const NAME: &'static str = "Arc<str>";
+
+    fn make(src: &str) -> Self {
+        Arc::from(src)
+    }
+}
+
+fn run<T: Subject>(workload: &str, stream: &[&str], out: &mut Vec<Row>) {
+    let ops = stream.len() as u64;
+    let mut push = |op: &'static str, m: Measurement| out.push(Row {
+        subject: T::NAME,
+        workload: workload.to_string(),
+        op,
+        measurement: m,
+    });
+
+    let mut made = Vec::with_capacity(stream.len());
+    push("make", measure(ops, || made.extend(stream.iter().map(|src| T::make(src)))));
+
+    let mut cloned = Vec::with_capacity(made.len());
+    push("clone", measure(ops, || cloned.extend(made.iter().cloned())));
+
+    let mut equal = 0usize;
+    push("eq", measure(ops, || {
+        equal = made.windows(2).filter(|pair| pair[0] == pair[1]).count();
+    }));
+
+    let mut counts: HashMap<T, usize> = HashMap::new();
+    push("count", measure(ops, || {
+        for value in &made {
+            *counts.entry(value.clone()).or_insert(0) += 1;
+        }
+    }));
+
+    // Keep the results alive until every operation is measured.
+    assert!(equal < stream.len() && counts.len() <= made.len() && cloned.len() == made.len());
+}
+
+/// Result of an operation on a string type
+pub struct Row {
+    subject: &'static str,
+    workload: String,
+    op: &'static str,
+    measurement: Measurement,
+}
+
+/// Measure every string type over the access stream.
+pub fn compare(workload: &str, stream: &[&str]) -> Vec<Row> {
+    let mut rows = Vec::new();
+    run::<IStr>(workload, stream, &mut rows);
+    run::<Rc<str>>(workload, stream, &mut rows);
+    run::<Arc<str>>(workload, stream, &mut rows);
+    rows
+}
+
+pub fn print_csv(rows: &[Row]) {
+    println!("workload,type,op,iters,total_ns,ns_per_iter");
+
+    for row in rows {
+        let total = row.measurement.elapsed.as_nanos();
+        let iters = row.measurement.iters;
+        println!("{},{},{},{},{},{:.2}", row.workload, row.subject, row.op, iters, total,
+            total as f64 / iters.max(1) as f64);
+    }
+}
@@ -0,0 +1,38 @@
+//! Compare `IStr` with `Rc<str>` and `Arc<str>` over the lines of a file, printing CSV.
+//!
+//! Each line is a string of the access stream, so a dump of real keys reproduces
+//! its own distribution of lengths and repetitions.
+//!
+//! ```text
+//! cargo run --release --features bench --example compare_file -- FILE [REPEAT]
+//! ```
+
+extern crate internship;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::process;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let path = match args.first() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: compare_file FILE [REPEAT]");
+            process::exit(2);
+        }
+    };
+    let repeat: usize = args.get(1).map_or(1, |arg| arg.parse().expect("invalid REPEAT"));
+
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("failed to read {}: {}", path, err);
+        process::exit(1);
+    });
+
+    let lines: Vec<&str> = text.lines().collect();
+    let stream: Vec<&str> = (0..repeat).flat_map(|_| lines.iter().cloned()).collect();
+
+    common::print_csv(&common::compare(path, &stream));
+}
@@ -0,0 +1,111 @@
+//! Compare `EpochPool` with the locking `SharedPool` over generated keys, printing CSV.
+//!
+//! ```text
+//! cargo run --release --features bench,epoch --example compare_shared -- [THREADS] [DISTINCT] [OPS] [ZIPF]
+//! ```
+
+extern crate internship;
+
+use std::env;
+use std::sync::Arc;
+use std::thread;
+
+use internship::bench::{measure, Workload, Measurement};
+use internship::shared::{SharedPool, EpochPool, SharedStr};
+
+/// Pool under comparison
+trait Subject: Send + Sync + 'static {
+    const NAME: &'static str;
+    fn make() -> Self;
+    fn intern(&self, src: &str) -> SharedStr;
+}
+
+impl Subject for SharedPool {
+    const NAME: &'static str = "SharedPool";
+
+    fn make() -> Self {
+        SharedPool::new()
+    }
+
+    fn intern(&self, src: &str) -> SharedStr {
+        SharedPool::intern(self, src)
+    }
+}
+
+impl Subject for EpochPool {
+    const NAME: &'static str = "EpochPool";
+
+    fn make() -> Self {
+        EpochPool::new()
+    }
+
+    fn intern(&self, src: &str) -> SharedStr {
+        EpochPool::intern(self, src)
+    }
+}
+
+fn arg<T: std::str::FromStr>(args: &[String], idx: usize, default: T) -> T {
+    match args.get(idx) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| panic!("invalid argument {:?}", arg)),
+        None => default,
+    }
+}
+
+/// Intern the streams from a thread each.
+fn parallel<T: Subject>(pool: &Arc<T>, streams: &Arc<Vec<Vec<String>>>) {
+    let handles: Vec<_> = (0..streams.len()).map(|idx| {
+        let pool = Arc::clone(pool);
+        let streams = Arc::clone(streams);
+
+        thread::spawn(move || {
+            for src in &streams[idx] {
+                pool.intern(src);
+            }
+        })
+    }).collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+fn run<T: Subject>(streams: &Arc<Vec<Vec<String>>>, out: &mut Vec<(&'static str, &'static str, Measurement)>) {
+    let ops = streams.iter().map(Vec::len).sum::<usize>() as u64;
+    let pool = Arc::new(T::make());
+
+    // Every key is new to the pool at first, then it's found.
+    out.push((T::NAME, "cold", measure(ops, || parallel(&pool, streams))));
+    out.push((T::NAME, "warm", measure(ops, || parallel(&pool, streams))));
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let threads = arg(&args, 0, 4);
+    let distinct = arg(&args, 1, 10_000);
+    let ops = arg(&args, 2, 1_000_000);
+    let zipf = arg(&args, 3, 1.0);
+
+    let workload = Workload::new(distinct).zipf(zipf);
+    let keys = workload.keys();
+    let streams: Vec<Vec<String>> = (0..threads)
+        .map(|idx| {
+            workload.clone().seed(idx as u64).stream(&keys, ops / threads.max(1))
+                .into_iter()
+                .map(String::from)
+                .collect()
+        })
+        .collect();
+    let streams = Arc::new(streams);
+
+    let mut rows = Vec::new();
+    run::<SharedPool>(&streams, &mut rows);
+    run::<EpochPool>(&streams, &mut rows);
+
+    println!("pool,op,threads,iters,total_ns,ns_per_iter");
+
+    for (pool, op, measurement) in rows {
+        let total = measurement.elapsed.as_nanos();
+        println!("{},{},{},{},{},{:.2}", pool, op, threads, measurement.iters, total,
+            total as f64 / measurement.iters.max(1) as f64);
+    }
+}
@@ -0,0 +1,39 @@
+//! Compare `IStr` with `Rc<str>` and `Arc<str>` over generated keys, printing CSV.
+//!
+//! ```text
+//! cargo run --release --features bench --example compare_synthetic -- [DISTINCT] [OPS] [ZIPF] [MIN_LEN] [MAX_LEN]
+//! ```
+
+extern crate internship;
+
+mod common;
+
+use std::env;
+
+use internship::bench::{Workload, LengthDist};
+
+fn arg<T: std::str::FromStr>(args: &[String], idx: usize, default: T) -> T {
+    match args.get(idx) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| panic!("invalid argument {:?}", arg)),
+        None => default,
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let distinct = arg(&args, 0, 10_000);
+    let ops = arg(&args, 1, 1_000_000);
+    let zipf = arg(&args, 2, 1.0);
+    let min_len = arg(&args, 3, 4);
+    let max_len = arg(&args, 4, 48);
+
+    let workload = Workload::new(distinct)
+        .lengths(LengthDist::Uniform(min_len, max_len))
+        .zipf(zipf)
+        .seed(42);
+    let keys = workload.keys();
+    let stream = workload.stream(&keys, ops);
+
+    let name = format!("zipf{}-{}x{}-{}..{}", zipf, distinct, ops, min_len, max_len);
+    common::print_csv(&common::compare(&name, &stream));
+}
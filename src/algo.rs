@@ -0,0 +1,212 @@
+//! Algorithms exploiting the uniqueness of interned values
+//!
+//! Equal `IStr`s of a thread share the same handle, so they can be hashed and compared
+//! by their handle words instead of their contents.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use istr::IStr;
+
+/// Hasher for handle ids, which are already well distributed pointers or inline contents
+#[derive(Default)]
+pub(crate) struct IdHasher(u64);
+
+impl Hasher for IdHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.write_u64(b as u64);
+        }
+    }
+
+    #[inline]
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x51_7C_C1_B7_27_22_0A_95);
+    }
+}
+
+pub(crate) type IdMap<V> = HashMap<(usize, usize), V, BuildHasherDefault<IdHasher>>;
+
+/// Sort the strings, comparing each distinct value only once.
+///
+/// Values are first counted by their handles, then only the distinct values are sorted.
+/// It's much faster than `sort` when there are far less distinct values than the length.
+///
+/// ```
+/// # use internship::{IStr, sort_interned};
+/// let mut values: Vec<_> = ["b", "a", "c", "a", "b", "a"].iter().map(|s| IStr::new(s)).collect();
+/// sort_interned(&mut values);
+///
+/// assert_eq!(values, ["a", "a", "a", "b", "b", "c"]);
+/// ```
+pub fn sort_interned(values: &mut [IStr]) {
+    let mut counts: IdMap<(IStr, usize)> = IdMap::default();
+
+    for value in values.iter() {
+        let pooled = value.0.to_pooled().map(IStr);
+        let value = pooled.as_ref().unwrap_or(value);
+        counts.entry(value.0.id()).or_insert_with(|| (value.clone(), 0)).1 += 1;
+    }
+
+    let mut distinct: Vec<_> = counts.into_values().collect();
+    distinct.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let mut slots = values.iter_mut();
+    for (value, count) in distinct {
+        for slot in slots.by_ref().take(count) {
+            *slot = value.clone();
+        }
+    }
+}
+
+/// Group the values by their interned keys.
+///
+/// ```
+/// # use internship::{IStr, group_by_interned};
+/// let events = vec![(IStr::new("error"), 1), (IStr::new("info"), 2), (IStr::new("error"), 3)];
+/// let groups = group_by_interned(events);
+///
+/// assert_eq!(groups["error"], [1, 3]);
+/// assert_eq!(groups["info"], [2]);
+/// ```
+pub fn group_by_interned<T, I>(iter: I) -> HashMap<IStr, Vec<T>>
+where
+    I: IntoIterator<Item = (IStr, T)>,
+{
+    let mut groups = GroupBy::new();
+
+    for (key, value) in iter {
+        groups.push(key, value);
+    }
+
+    groups.finish()
+}
+
+/// Streaming version of `group_by_interned`
+///
+/// Keys are hashed by their handles while grouping, and by their contents
+/// only once per distinct key when `finish` builds the resulting map.
+pub struct GroupBy<T> {
+    groups: IdMap<(IStr, Vec<T>)>,
+}
+
+impl<T> GroupBy<T> {
+    pub fn new() -> Self {
+        GroupBy {
+            groups: IdMap::default(),
+        }
+    }
+
+    #[inline]
+    pub fn push(&mut self, key: IStr, value: T) {
+        let key = key.0.to_pooled().map_or(key, IStr);
+        self.groups.entry(key.0.id()).or_insert_with(|| (key, Vec::new())).1.push(value);
+    }
+
+    /// Values of the group pushed so far.
+    #[inline]
+    pub fn get(&self, key: &IStr) -> Option<&[T]> {
+        let pooled = key.0.to_pooled();
+        self.groups.get(&pooled.as_ref().unwrap_or(&key.0).id()).map(|group| &*group.1)
+    }
+
+    /// Number of distinct keys.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Drain the groups without building the map.
+    pub fn drain(&mut self) -> impl Iterator<Item = (IStr, Vec<T>)> + '_ {
+        self.groups.drain().map(|(_, group)| group)
+    }
+
+    pub fn finish(self) -> HashMap<IStr, Vec<T>> {
+        self.groups.into_values().collect()
+    }
+}
+
+impl<T> Default for GroupBy<T> {
+    fn default() -> Self {
+        GroupBy::new()
+    }
+}
+
+impl<T> Extend<(IStr, T)> for GroupBy<T> {
+    fn extend<I: IntoIterator<Item = (IStr, T)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.push(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn istrs(values: &[&str]) -> Vec<IStr> {
+        values.iter().map(|s| IStr::new(s)).collect()
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut empty: Vec<IStr> = Vec::new();
+        sort_interned(&mut empty);
+        assert!(empty.is_empty());
+
+        let long = "a string long enough to be pooled";
+        let mut values = istrs(&["c", long, "a", "c", long, "c"]);
+        sort_interned(&mut values);
+        assert_eq!(values, ["a", long, long, "c", "c", "c"]);
+
+        let mut same = istrs(&["x"; 4]);
+        sort_interned(&mut same);
+        assert_eq!(same, ["x"; 4]);
+    }
+
+    #[test]
+    fn test_group_by() {
+        let groups = group_by_interned(Vec::<(IStr, u32)>::new());
+        assert!(groups.is_empty());
+
+        let mut groups = GroupBy::new();
+        groups.extend(istrs(&["a", "b", "a", "a"]).into_iter().zip(0..));
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups.get(&IStr::new("a")), Some(&[0, 2, 3][..]));
+        assert_eq!(groups.get(&IStr::new("c")), None);
+
+        let finished = groups.finish();
+        assert_eq!(finished["b"], [1]);
+    }
+
+    #[test]
+    fn test_mixed_keys() {
+        let src = "a key long enough to be pooled";
+        let pooled = IStr::new(src);
+        let unpooled = IStr::new_unpooled(src);
+
+        let groups = group_by_interned(vec![(pooled.clone(), 1), (unpooled.clone(), 2)]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[src], [1, 2]);
+
+        let mut values = vec![unpooled, IStr::new("b"), pooled];
+        sort_interned(&mut values);
+        assert_eq!(values, [src, src, "b"]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Arrow dictionary encoding bridge
+//!
+//! Converts between Arrow `DictionaryArray` of strings and `IStr` values.
+//! Each dictionary entry is interned only once, and dictionaries are built
+//! by handle identity without hashing the string contents.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow_array::{Array, ArrayRef, DictionaryArray, PrimitiveArray, StringArray};
+use arrow_array::cast::AsArray;
+use arrow_array::types::ArrowDictionaryKeyType;
+use arrow_buffer::ArrowNativeType;
+use arrow_schema::ArrowError;
+
+use istr::IStr;
+
+/// Convert dictionary array of strings into interned values.
+///
+/// Null slots, including the ones referencing null dictionary values, become `None`.
+pub fn from_dictionary<K>(array: &DictionaryArray<K>) -> Result<Vec<Option<IStr>>, ArrowError>
+    where K: ArrowDictionaryKeyType
+{
+    let values = array.values().as_string_opt::<i32>().ok_or_else(|| {
+        ArrowError::CastError(format!("expected Utf8 dictionary values, found {}",
+            array.values().data_type()))
+    })?;
+
+    let dict: Vec<Option<IStr>> = (0..values.len())
+        .map(|idx| if values.is_null(idx) {
+            None
+        } else {
+            Some(IStr::new(values.value(idx)))
+        })
+        .collect();
+
+    array.keys().iter()
+        .map(|key| match key {
+            None => Ok(None),
+            Some(key) => {
+                let idx = key.as_usize();
+                dict.get(idx).cloned().ok_or_else(|| {
+                    ArrowError::InvalidArgumentError(format!(
+                        "dictionary key {} is out of bounds of {} values", idx, dict.len()))
+                })
+            }
+        })
+        .collect()
+}
+
+/// Convert interned values into dictionary array.
+pub fn to_dictionary<K>(values: &[IStr]) -> Result<DictionaryArray<K>, ArrowError>
+    where K: ArrowDictionaryKeyType
+{
+    build_dictionary(values.iter().map(Some))
+}
+
+/// Convert optional interned values into dictionary array, with `None` as null.
+pub fn to_dictionary_opt<K>(values: &[Option<IStr>]) -> Result<DictionaryArray<K>, ArrowError>
+    where K: ArrowDictionaryKeyType
+{
+    build_dictionary(values.iter().map(Option::as_ref))
+}
+
+fn build_dictionary<'a, K, I>(values: I) -> Result<DictionaryArray<K>, ArrowError>
+    where K: ArrowDictionaryKeyType, I: Iterator<Item = Option<&'a IStr>>
+{
+    let mut index = HashMap::new();
+    let mut dict: Vec<&str> = Vec::new();
+    let mut keys = Vec::with_capacity(values.size_hint().0);
+
+    for value in values {
+        let value = match value {
+            Some(value) => value,
+            None => {
+                keys.push(None);
+                continue;
+            }
+        };
+
+        let next = dict.len();
+        let idx = *index.entry(value.0.id()).or_insert(next);
+
+        if idx == next {
+            dict.push(value.as_str());
+        }
+
+        let key = <K::Native as ArrowNativeType>::from_usize(idx)
+            .ok_or(ArrowError::DictionaryKeyOverflowError)?;
+        keys.push(Some(key));
+    }
+
+    let keys: PrimitiveArray<K> = keys.into_iter().collect();
+    let dict: ArrayRef = Arc::new(StringArray::from(dict));
+
+    DictionaryArray::try_new(keys, dict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::types::Int32Type;
+
+    #[test]
+    fn test_roundtrip() {
+        let long = IStr::new("Lorem ipsum dolor sit amet, consectetur adipiscing elit");
+        let values = vec![
+            Some(IStr::new("foo")),
+            None,
+            Some(long.clone()),
+            Some(IStr::new("foo")),
+            Some(long),
+        ];
+
+        let array = to_dictionary_opt::<Int32Type>(&values).unwrap();
+        assert_eq!(array.values().len(), 2);
+        assert_eq!(from_dictionary(&array).unwrap(), values);
+    }
+}
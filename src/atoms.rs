@@ -0,0 +1,75 @@
+//! Common atoms
+//!
+//! Constants of very common short strings.
+//! They're all short enough to be inlined, so using one never touches the pool.
+//!
+//! ```
+//! # use internship::{IStr, atoms};
+//! assert_eq!(atoms::TRUE, "true");
+//! assert_eq!(atoms::digit(7).unwrap(), "7");
+//!
+//! const SEPARATOR: IStr = atoms::COMMA;
+//! assert_eq!(SEPARATOR, ",");
+//! ```
+
+use istr::IStr;
+
+macro_rules! atoms {
+    ($($name:ident => $value:expr,)*) => {
+        $(
+            #[doc = concat!("`", stringify!($value), "`")]
+            pub const $name: IStr = IStr::const_inline($value);
+        )*
+    };
+}
+
+atoms! {
+    EMPTY => "",
+    TRUE => "true",
+    FALSE => "false",
+    NULL => "null",
+    ZERO => "0",
+    ONE => "1",
+    TWO => "2",
+    THREE => "3",
+    FOUR => "4",
+    FIVE => "5",
+    SIX => "6",
+    SEVEN => "7",
+    EIGHT => "8",
+    NINE => "9",
+    SPACE => " ",
+    NEWLINE => "\n",
+    TAB => "\t",
+    COMMA => ",",
+    DOT => ".",
+    COLON => ":",
+    SEMICOLON => ";",
+    SLASH => "/",
+    BACKSLASH => "\\",
+    DASH => "-",
+    UNDERSCORE => "_",
+    EQUALS => "=",
+    QUOTE => "\"",
+    APOSTROPHE => "'",
+    OPEN_PAREN => "(",
+    CLOSE_PAREN => ")",
+    OPEN_BRACKET => "[",
+    CLOSE_BRACKET => "]",
+    OPEN_BRACE => "{",
+    CLOSE_BRACE => "}",
+}
+
+const DIGITS: [IStr; 10] = [ZERO, ONE, TWO, THREE, FOUR, FIVE, SIX, SEVEN, EIGHT, NINE];
+
+/// Decimal digit atom, or `None` if `n` is not a single digit.
+#[inline]
+pub fn digit(n: u32) -> Option<IStr> {
+    DIGITS.get(n as usize).cloned()
+}
+
+/// `"true"` or `"false"`.
+#[inline]
+pub fn bool(b: bool) -> IStr {
+    if b { TRUE } else { FALSE }
+}
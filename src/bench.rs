@@ -0,0 +1,272 @@
+//! Benchmark helpers
+//!
+//! Reproducible workload generators and simple measurement helpers,
+//! to compare pool configurations on data shaped like your own.
+//!
+//! ```
+//! # use internship::IStr;
+//! # use internship::bench::{Workload, LengthDist, measure};
+//! let workload = Workload::new(1000)
+//!     .lengths(LengthDist::Uniform(4, 32))
+//!     .zipf(1.1)
+//!     .seed(42);
+//!
+//! let keys = workload.keys();
+//! let stream = workload.stream(&keys, 10_000);
+//!
+//! let result = measure(stream.len() as u64, || {
+//!     for key in &stream {
+//!         IStr::new(key);
+//!     }
+//! });
+//! assert_eq!(result.iters, 10_000);
+//! ```
+
+use std::time::{Duration, Instant};
+use std::fmt;
+
+/// Small deterministic pseudo random number generator (SplitMix64)
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in `[min, max]`.
+    pub fn range(&mut self, min: usize, max: usize) -> usize {
+        assert!(min <= max, "empty range");
+        min + (self.next_u64() % (max - min + 1) as u64) as usize
+    }
+}
+
+/// Sampler of ranks `0..n` following Zipf's law with exponent `s`
+#[derive(Debug, Clone)]
+pub struct Zipf {
+    cdf: Box<[f64]>,
+}
+
+impl Zipf {
+    pub fn new(n: usize, s: f64) -> Self {
+        assert!(n > 0, "Zipf distribution needs at least one rank");
+
+        let mut sum = 0.0;
+        let mut cdf: Vec<f64> = (1..=n)
+            .map(|rank| {
+                sum += 1.0 / (rank as f64).powf(s);
+                sum
+            })
+            .collect();
+
+        for v in cdf.iter_mut() {
+            *v /= sum;
+        }
+
+        Zipf { cdf: cdf.into() }
+    }
+
+    /// Sample a rank, where `0` is the most frequent one.
+    pub fn sample(&self, rng: &mut Rng) -> usize {
+        let p = rng.next_f64();
+        self.cdf.partition_point(|&v| v < p).min(self.cdf.len() - 1)
+    }
+}
+
+/// Distribution of the key lengths in bytes
+#[derive(Debug, Clone, PartialEq)]
+pub enum LengthDist {
+    Fixed(usize),
+    /// Uniform between both inclusive bounds.
+    Uniform(usize, usize),
+    /// Weighted choice of lengths.
+    Weighted(Vec<(usize, u32)>),
+}
+
+impl LengthDist {
+    pub fn sample(&self, rng: &mut Rng) -> usize {
+        match *self {
+            LengthDist::Fixed(len) => len,
+            LengthDist::Uniform(min, max) => rng.range(min, max),
+            LengthDist::Weighted(ref choices) => {
+                let total: u64 = choices.iter().map(|&(_, w)| w as u64).sum();
+                assert!(total > 0, "weights should not be all zero");
+
+                let mut pick = rng.next_u64() % total;
+                for &(len, weight) in choices {
+                    if pick < weight as u64 {
+                        return len;
+                    }
+                    pick -= weight as u64;
+                }
+                unreachable!()
+            }
+        }
+    }
+}
+
+/// Generator of distinct keys and access streams over them
+#[derive(Debug, Clone)]
+pub struct Workload {
+    distinct: usize,
+    lengths: LengthDist,
+    zipf: Option<f64>,
+    seed: u64,
+}
+
+impl Workload {
+    /// Workload of `distinct` keys, 16 bytes each and uniformly accessed by default.
+    pub fn new(distinct: usize) -> Self {
+        Workload {
+            distinct,
+            lengths: LengthDist::Fixed(16),
+            zipf: None,
+            seed: 0,
+        }
+    }
+
+    pub fn lengths(mut self, lengths: LengthDist) -> Self {
+        self.lengths = lengths;
+        self
+    }
+
+    /// Access the keys following Zipf's law with exponent `s`.
+    pub fn zipf(mut self, s: f64) -> Self {
+        self.zipf = Some(s);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Generate alphanumeric keys of the sampled lengths. Keys are prefixed by their index,
+    /// so they're distinct unless they're too short to keep the whole prefix.
+    pub fn keys(&self) -> Vec<String> {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        let mut rng = Rng::new(self.seed);
+
+        (0..self.distinct)
+            .map(|idx| {
+                let mut key = format!("{:x}_", idx);
+                let len = self.lengths.sample(&mut rng);
+
+                key.truncate(len);
+                while key.len() < len {
+                    key.push(CHARS[rng.range(0, CHARS.len() - 1)] as char);
+                }
+                key
+            })
+            .collect()
+    }
+
+    /// Generate `count` accesses to the keys, or nothing if there's no key.
+    pub fn stream<'a>(&self, keys: &'a [String], count: usize) -> Vec<&'a str> {
+        if keys.is_empty() {
+            return Vec::new();
+        }
+
+        let mut rng = Rng::new(self.seed ^ 0x5EED);
+        let zipf = self.zipf.map(|s| Zipf::new(keys.len(), s));
+
+        (0..count)
+            .map(|_| {
+                let idx = match zipf {
+                    Some(ref zipf) => zipf.sample(&mut rng),
+                    None => rng.range(0, keys.len() - 1),
+                };
+                &*keys[idx]
+            })
+            .collect()
+    }
+}
+
+/// Result of `measure`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub elapsed: Duration,
+    pub iters: u64,
+}
+
+impl Measurement {
+    pub fn per_iter(&self) -> Duration {
+        if self.iters == 0 {
+            return Duration::from_secs(0);
+        }
+
+        self.elapsed / self.iters.min(u32::MAX as u64) as u32
+    }
+
+    /// Iterations per second.
+    pub fn throughput(&self) -> f64 {
+        self.iters as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} iters in {:?} ({:?}/iter, {:.0}/s)",
+            self.iters, self.elapsed, self.per_iter(), self.throughput())
+    }
+}
+
+/// Run `f` once and measure it, as doing `iters` iterations.
+pub fn measure<F: FnOnce()>(iters: u64, f: F) -> Measurement {
+    let start = Instant::now();
+    f();
+
+    Measurement {
+        elapsed: start.elapsed(),
+        iters,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workload() {
+        let workload = Workload::new(100).lengths(LengthDist::Uniform(8, 12)).zipf(1.2).seed(7);
+        let keys = workload.keys();
+
+        assert_eq!(keys, workload.keys());
+        assert!(keys.iter().all(|key| key.len() >= 8 && key.len() <= 12));
+
+        let stream = workload.stream(&keys, 10_000);
+        let hot = stream.iter().filter(|&&key| key == keys[0]).count();
+        let cold = stream.iter().filter(|&&key| key == keys[99]).count();
+        assert!(hot > cold * 10);
+    }
+
+    #[test]
+    fn test_short_keys() {
+        let workload = Workload::new(300).lengths(LengthDist::Uniform(1, 3));
+        let keys = workload.keys();
+
+        assert!(keys.iter().all(|key| !key.is_empty() && key.len() <= 3));
+        assert_eq!(Workload::new(1).lengths(LengthDist::Fixed(0)).keys(), vec![""]);
+    }
+
+    #[test]
+    fn test_empty_stream() {
+        let workload = Workload::new(0).zipf(1.0);
+
+        assert!(workload.keys().is_empty());
+        assert!(workload.stream(&[], 100).is_empty());
+    }
+}
@@ -0,0 +1,373 @@
+//! Content-hash addressed blob
+//!
+//! Large blobs are deduplicated by their BLAKE3 hash instead of comparing the whole contents.
+//! Smaller ones are interned as `IBytes` as usual.
+//!
+//! Large blobs not accessed for a while can be LZ4-compressed in place by `pool::compress_cold()`.
+//! `IBlob::get()` decompresses them if needed and keeps them uncompressed while the returned
+//! guard lives. `as_bytes()`, `Deref` and `AsRef` borrow the contents without a guard,
+//! so the blobs accessed through them are never compressed again.
+
+use std::collections::HashMap;
+use std::cell::{Cell, Ref, RefCell};
+use std::rc::Rc;
+use std::ops::Deref;
+use std::hash::{Hash, Hasher};
+use std::fmt;
+
+use ibytes::IBytes;
+
+/// 256-bit BLAKE3 hash of the contents
+pub type ContentHash = [u8; 32];
+
+struct Entry {
+    hash: ContentHash,
+    len: usize,
+    data: RefCell<Data>,
+    last_access: Cell<u64>,
+    // Borrowed without a guard by `as_bytes()`.
+    pinned: Cell<bool>,
+}
+
+enum Data {
+    Raw(Box<[u8]>),
+    Compressed(Box<[u8]>),
+}
+
+thread_local! {
+    static POOL: RefCell<HashMap<ContentHash, Rc<Entry>>> = Default::default();
+    static EPOCH: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Interned blob addressed by its content hash
+///
+/// Blobs not shorter than `IBlob::HASH_THRESHOLD` are pooled by their BLAKE3 hash,
+/// so interning them costs a single hash pass instead of a hash pass plus a full comparison.
+/// Two different contents with the same 256-bit hash are considered same.
+#[derive(Clone)]
+pub struct IBlob(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Small(IBytes),
+    Large(Rc<Entry>),
+}
+
+/// Borrowed contents of `IBlob`
+///
+/// Large blob is kept uncompressed while this guard lives.
+pub enum BlobRef<'a> {
+    Small(&'a [u8]),
+    Large(Ref<'a, [u8]>),
+}
+
+impl IBlob {
+    /// Blobs of this length or longer are addressed by their content hash.
+    pub const HASH_THRESHOLD: usize = 1024;
+
+    pub fn new(src: &[u8]) -> Self {
+        if src.len() < IBlob::HASH_THRESHOLD {
+            return IBlob(Repr::Small(IBytes::new(src)));
+        }
+
+        let hash = *blake3::hash(src).as_bytes();
+
+        let entry = POOL.with(|pool| {
+            let cached = pool.borrow().get(&hash).cloned();
+
+            cached.unwrap_or_else(|| {
+                let entry = Rc::new(Entry {
+                    hash,
+                    len: src.len(),
+                    data: RefCell::new(Data::Raw(src.into())),
+                    last_access: Cell::new(EPOCH.with(Cell::get)),
+                    pinned: Cell::new(false),
+                });
+                pool.borrow_mut().insert(hash, Rc::clone(&entry));
+                entry
+            })
+        });
+
+        IBlob(Repr::Large(entry))
+    }
+
+    /// Borrow the contents, decompressing them first if they're compressed.
+    pub fn get(&self) -> BlobRef<'_> {
+        let entry = match self.0 {
+            Repr::Small(ref v) => return BlobRef::Small(v.as_bytes()),
+            Repr::Large(ref v) => v,
+        };
+
+        entry.last_access.set(EPOCH.with(Cell::get));
+
+        let raw = match *entry.data.borrow() {
+            Data::Raw(_) => None,
+            Data::Compressed(ref buf) => Some(lz4_flex::decompress_size_prepended(buf)
+                .expect("compressed blob is corrupted")),
+        };
+
+        if let Some(raw) = raw {
+            // Compressed blob never has outstanding guards, so this borrow never fails.
+            *entry.data.borrow_mut() = Data::Raw(raw.into());
+        }
+
+        BlobRef::Large(Ref::map(entry.data.borrow(), |data| match *data {
+            Data::Raw(ref raw) => &**raw,
+            Data::Compressed(_) => unreachable!(),
+        }))
+    }
+
+    /// Borrow the contents, decompressing them first if they're compressed.
+    /// Unlike `get()`, it keeps the blob uncompressed from now on.
+    pub fn as_bytes(&self) -> &[u8] {
+        let entry = match self.0 {
+            Repr::Small(ref v) => return v.as_bytes(),
+            Repr::Large(ref v) => v,
+        };
+
+        entry.pinned.set(true);
+        drop(self.get());
+
+        // Pinned data is never compressed again, so it's never replaced
+        // nor borrowed mutably while this borrow lives.
+        match unsafe { &*entry.data.as_ptr() } {
+            Data::Raw(ref raw) => raw,
+            Data::Compressed(_) => unreachable!(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self.0 {
+            Repr::Small(ref v) => v.len(),
+            Repr::Large(ref v) => v.len,
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// BLAKE3 hash of the contents. It's cached for the blobs addressed by hash.
+    pub fn content_hash(&self) -> ContentHash {
+        match self.0 {
+            Repr::Small(ref v) => *blake3::hash(v).as_bytes(),
+            Repr::Large(ref v) => v.hash,
+        }
+    }
+
+    #[inline]
+    pub fn is_hashed(&self) -> bool {
+        match self.0 {
+            Repr::Small(_) => false,
+            Repr::Large(_) => true,
+        }
+    }
+
+    /// Whether the contents are currently stored compressed.
+    pub fn is_compressed(&self) -> bool {
+        match self.0 {
+            Repr::Small(_) => false,
+            Repr::Large(ref v) => match v.data.try_borrow() {
+                Ok(data) => matches!(*data, Data::Compressed(_)),
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+/// Compress large blobs of this thread which are not accessed since the last call.
+/// Blobs currently borrowed are skipped. Returns the number of blobs compressed.
+pub(crate) fn compress_cold() -> usize {
+    let epoch = EPOCH.with(|epoch| {
+        let current = epoch.get();
+        epoch.set(current + 1);
+        current
+    });
+
+    POOL.with(|pool| {
+        let mut count = 0;
+
+        for entry in pool.borrow().values() {
+            if entry.last_access.get() >= epoch || entry.pinned.get() {
+                continue;
+            }
+
+            let mut data = match entry.data.try_borrow_mut() {
+                Ok(data) => data,
+                Err(_) => continue,
+            };
+
+            let compressed = match *data {
+                Data::Raw(ref raw) => lz4_flex::compress_prepend_size(raw),
+                Data::Compressed(_) => continue,
+            };
+
+            // Don't bother to keep incompressible data.
+            if compressed.len() < entry.len {
+                *data = Data::Compressed(compressed.into());
+                count += 1;
+            }
+        }
+
+        count
+    })
+}
+
+impl Drop for Repr {
+    fn drop(&mut self) {
+        if let Repr::Large(ref entry) = *self {
+            if Rc::strong_count(entry) == 2 {
+                let _ = POOL.try_with(|pool| {
+                    pool.borrow_mut().remove(&entry.hash);
+                });
+            }
+        }
+    }
+}
+
+impl PartialEq for IBlob {
+    fn eq(&self, other: &IBlob) -> bool {
+        match (&self.0, &other.0) {
+            (Repr::Small(a), Repr::Small(b)) => a == b,
+            (Repr::Large(a), Repr::Large(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for IBlob {}
+
+impl Hash for IBlob {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        match self.0 {
+            Repr::Small(ref v) => v.hash(hasher),
+            Repr::Large(ref v) => v.hash.hash(hasher),
+        }
+    }
+}
+
+impl Deref for BlobRef<'_> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        match *self {
+            BlobRef::Small(v) => v,
+            BlobRef::Large(ref v) => v,
+        }
+    }
+}
+
+impl Deref for IBlob {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for IBlob {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for BlobRef<'_> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl From<&[u8]> for IBlob {
+    fn from(v: &[u8]) -> Self {
+        IBlob::new(v)
+    }
+}
+
+impl From<Vec<u8>> for IBlob {
+    fn from(v: Vec<u8>) -> Self {
+        IBlob::new(&v)
+    }
+}
+
+impl fmt::Debug for IBlob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IBlob")
+            .field("len", &self.len())
+            .field("hashed", &self.is_hashed())
+            .finish()
+    }
+}
+
+impl fmt::Debug for BlobRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_by_hash() {
+        let data = vec![42u8; 4096];
+        let a = IBlob::new(&data);
+        let b = IBlob::from(data.clone());
+
+        assert!(a.is_hashed());
+        assert_eq!(a, b);
+        assert_eq!(a.content_hash(), *blake3::hash(&data).as_bytes());
+        assert_eq!(POOL.with(|pool| pool.borrow().len()), 1);
+
+        drop(a);
+        drop(b);
+        assert_eq!(POOL.with(|pool| pool.borrow().len()), 0);
+        assert!(!IBlob::new(b"foo").is_hashed());
+    }
+
+    #[test]
+    fn test_compress_cold() {
+        let data: Vec<u8> = (0..8192u32).map(|n| (n % 7) as u8).collect();
+        let blob = IBlob::new(&data);
+
+        compress_cold();
+        assert!(!blob.is_compressed());
+
+        {
+            let pinned = blob.get();
+            compress_cold();
+            assert!(!blob.is_compressed());
+            assert_eq!(&*pinned, &data[..]);
+        }
+
+        compress_cold();
+        compress_cold();
+        assert!(blob.is_compressed());
+        assert_eq!(&*blob.get(), &data[..]);
+        assert!(!blob.is_compressed());
+    }
+
+    #[test]
+    fn test_as_bytes_pins() {
+        let data: Vec<u8> = (0..8192u32).map(|n| (n % 5) as u8).collect();
+        let blob = IBlob::new(&data);
+
+        compress_cold();
+        compress_cold();
+        assert!(blob.is_compressed());
+
+        let bytes: &[u8] = &blob;
+        assert_eq!(bytes, &data[..]);
+        compress_cold();
+        compress_cold();
+        assert!(!blob.is_compressed());
+        assert_eq!(bytes, blob.as_ref());
+        assert_eq!(IBlob::new(b"small").as_bytes(), b"small");
+    }
+}
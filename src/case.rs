@@ -0,0 +1,61 @@
+//! Case-insensitive comparison of `IStr`
+//!
+//! Whether the string is ASCII is cached per pool entry,
+//! so comparing the same strings again takes the ASCII path without scanning for it.
+
+use istr::IStr;
+use side;
+
+struct IsAscii(bool);
+
+fn is_ascii(value: &IStr) -> bool {
+    // Inlined strings are short enough to scan on every call.
+    if value.0.is_inline() {
+        return value.is_ascii();
+    }
+
+    side::get_or_insert_with(&value.0, || IsAscii(value.is_ascii())).0
+}
+
+impl IStr {
+    /// Whether the strings are equal ignoring their case, independent of the locale.
+    ///
+    /// It checks the identity of the handles first, then compares ASCII strings
+    /// with `eq_ignore_ascii_case`. Others are compared by their Unicode lowercase mappings.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let keyword = IStr::new("SELECT");
+    /// assert!(keyword.eq_ignore_case_interned(&IStr::new("select")));
+    /// assert!(IStr::new("STRASSE Ä").eq_ignore_case_interned(&IStr::new("strasse ä")));
+    /// assert!(!keyword.eq_ignore_case_interned(&IStr::new("selects")));
+    /// ```
+    pub fn eq_ignore_case_interned(&self, other: &IStr) -> bool {
+        if self == other {
+            return true;
+        }
+
+        if is_ascii(self) && is_ascii(other) {
+            return self.eq_ignore_ascii_case(other);
+        }
+
+        self.chars().flat_map(char::to_lowercase).eq(other.chars().flat_map(char::to_lowercase))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pooled() {
+        let upper = IStr::new("CONTENT-TYPE: TEXT/PLAIN; CHARSET=UTF-8");
+        let lower = IStr::new("content-type: text/plain; charset=utf-8");
+        let greek = IStr::new("ΚΑΛΗΜΕΡΑ ΚΟΣΜΕ, καλημέρα");
+
+        assert!(upper.eq_ignore_case_interned(&lower));
+        assert!(side::get_or_insert_with(&upper.0, || IsAscii(false)).0);
+        assert!(greek.eq_ignore_case_interned(&IStr::new("καλημερα κοσμε, ΚΑΛΗΜΈΡΑ")));
+        assert!(!greek.eq_ignore_case_interned(&lower));
+    }
+}
@@ -0,0 +1,58 @@
+//! Checksums of `IBytes` cached per pool entry
+//!
+//! Inlined bytes are too short to be worth caching, so theirs are computed on every call.
+
+use ibytes::IBytes;
+use side;
+
+#[cfg(feature = "crc32")]
+struct Crc32(u32);
+
+#[cfg(feature = "xxh3")]
+struct Xxh3(u64);
+
+impl IBytes {
+    /// CRC-32 (IEEE) checksum of the bytes, computed once per pool entry.
+    ///
+    /// ```
+    /// # use internship::IBytes;
+    /// assert_eq!(IBytes::new(b"123456789").crc32(), 0xCBF43926);
+    /// ```
+    #[cfg(feature = "crc32")]
+    pub fn crc32(&self) -> u32 {
+        if self.0.is_inline() {
+            return crc32fast::hash(self);
+        }
+
+        side::get_or_insert_with(&self.0, || Crc32(crc32fast::hash(self))).0
+    }
+
+    /// 64-bit XXH3 hash of the bytes, computed once per pool entry.
+    ///
+    /// ```
+    /// # use internship::IBytes;
+    /// assert_eq!(IBytes::new(b"").xxh3(), 0x2D06800538D394C2);
+    /// ```
+    #[cfg(feature = "xxh3")]
+    pub fn xxh3(&self) -> u64 {
+        if self.0.is_inline() {
+            return xxhash_rust::xxh3::xxh3_64(self);
+        }
+
+        side::get_or_insert_with(&self.0, || Xxh3(xxhash_rust::xxh3::xxh3_64(self))).0
+    }
+}
+
+#[cfg(all(test, feature = "crc32"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_cached() {
+        let payload = IBytes::new(&[0xAB; 64]);
+        let crc = payload.crc32();
+
+        assert_eq!(crc, crc32fast::hash(&[0xAB; 64]));
+        assert_eq!(side::get_or_insert_with(&payload.0, || Crc32(0)).0, crc);
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+
+use istr::IStr;
+use algo::IdMap;
+
+/// Dictionary encoded column of `IStr`s
+///
+/// Distinct values are stored once in a local dictionary and each row is a `u32` index into it.
+/// With run-length encoding, consecutive equal rows are stored as a single run.
+///
+/// ```
+/// # use internship::{IStr, encode_column, encode_column_rle};
+/// let values: Vec<_> = ["a", "a", "a", "b", "a"].iter().map(|s| IStr::new(s)).collect();
+///
+/// let plain = encode_column(&values);
+/// let rle = encode_column_rle(&values);
+///
+/// assert_eq!(plain.dictionary().len(), 2);
+/// assert_eq!(rle.get(3).unwrap(), "b");
+/// assert_eq!(rle.decode(), values);
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncodedColumn {
+    dict: Vec<IStr>,
+    codes: Codes,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Codes {
+    Plain(Vec<u32>),
+    // Code and exclusive end row of each run.
+    Rle(Vec<(u32, u64)>),
+}
+
+fn build_dict(values: &[IStr]) -> (Vec<IStr>, impl Iterator<Item = u32> + '_) {
+    let mut dict = Vec::new();
+    let mut index: IdMap<u32> = IdMap::default();
+
+    let codes: Vec<u32> = values.iter()
+        .map(|value| {
+            let pooled = value.0.to_pooled().map(IStr);
+            let value = pooled.as_ref().unwrap_or(value);
+            *index.entry(value.0.id()).or_insert_with(|| {
+                assert!(dict.len() < u32::MAX as usize, "too many distinct values");
+                dict.push(value.clone());
+                dict.len() as u32 - 1
+            })
+        })
+        .collect();
+
+    (dict, codes.into_iter())
+}
+
+/// Dictionary encode the values.
+pub fn encode_column(values: &[IStr]) -> EncodedColumn {
+    let (dict, codes) = build_dict(values);
+
+    EncodedColumn {
+        dict,
+        codes: Codes::Plain(codes.collect()),
+    }
+}
+
+/// Dictionary encode the values, then run-length encode the codes.
+pub fn encode_column_rle(values: &[IStr]) -> EncodedColumn {
+    let (dict, codes) = build_dict(values);
+    let mut runs: Vec<(u32, u64)> = Vec::new();
+
+    for (row, code) in codes.enumerate() {
+        match runs.last_mut() {
+            Some(run) if run.0 == code => run.1 += 1,
+            _ => runs.push((code, row as u64 + 1)),
+        }
+    }
+
+    EncodedColumn {
+        dict,
+        codes: Codes::Rle(runs),
+    }
+}
+
+impl EncodedColumn {
+    /// Distinct values in order of their first appearance.
+    #[inline]
+    pub fn dictionary(&self) -> &[IStr] {
+        &self.dict
+    }
+
+    /// Number of rows.
+    pub fn len(&self) -> usize {
+        match self.codes {
+            Codes::Plain(ref codes) => codes.len(),
+            Codes::Rle(ref runs) => runs.last().map_or(0, |run| run.1 as usize),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn is_rle(&self) -> bool {
+        match self.codes {
+            Codes::Plain(_) => false,
+            Codes::Rle(_) => true,
+        }
+    }
+
+    /// Dictionary index of the row.
+    pub fn code(&self, row: usize) -> Option<u32> {
+        match self.codes {
+            Codes::Plain(ref codes) => codes.get(row).cloned(),
+            Codes::Rle(ref runs) => {
+                let idx = runs.partition_point(|run| run.1 <= row as u64);
+                runs.get(idx).map(|run| run.0)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, row: usize) -> Option<&IStr> {
+        self.code(row).map(|code| &self.dict[code as usize])
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = &IStr> + '_> {
+        let dict = &self.dict;
+
+        match self.codes {
+            Codes::Plain(ref codes) => Box::new(codes.iter().map(move |&code| &dict[code as usize])),
+            Codes::Rle(ref runs) => {
+                let mut start = 0;
+                Box::new(runs.iter().flat_map(move |&(code, end)| {
+                    let len = end - start;
+                    start = end;
+                    ::std::iter::repeat_n(&dict[code as usize], len as usize)
+                }))
+            }
+        }
+    }
+
+    /// Decode back into the values.
+    pub fn decode(&self) -> Vec<IStr> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl fmt::Debug for EncodedColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncodedColumn")
+            .field("len", &self.len())
+            .field("distinct", &self.dict.len())
+            .field("rle", &self.is_rle())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn istrs(values: &[&str]) -> Vec<IStr> {
+        values.iter().map(|s| IStr::new(s)).collect()
+    }
+
+    #[test]
+    fn test_empty() {
+        for column in &[encode_column(&[]), encode_column_rle(&[])] {
+            assert!(column.dictionary().is_empty());
+            assert!(column.decode().is_empty());
+            assert!(column.get(0).is_none());
+        }
+    }
+
+    #[test]
+    fn test_single_run() {
+        let values = istrs(&["a"; 5]);
+        let rle = encode_column_rle(&values);
+
+        assert!(rle.codes == Codes::Rle(vec![(0, 5)]));
+        assert_eq!(rle.decode(), values);
+        assert_eq!(rle.get(4).unwrap(), "a");
+        assert!(rle.get(5).is_none());
+    }
+
+    #[test]
+    fn test_run_boundaries() {
+        let values = istrs(&["a", "a", "b", "a", "c", "c"]);
+        let rle = encode_column_rle(&values);
+
+        assert!(rle.codes == Codes::Rle(vec![(0, 2), (1, 3), (0, 4), (2, 6)]));
+        for (row, value) in values.iter().enumerate() {
+            assert_eq!(rle.get(row), Some(value), "row {}", row);
+        }
+        assert!(rle.get(6).is_none());
+        assert_eq!(rle.decode(), values);
+    }
+
+    #[test]
+    fn test_mixed_keys() {
+        let src = "a value long enough to be pooled";
+        let values = vec![IStr::new_unpooled(src), IStr::new(src), IStr::new_unpooled(src)];
+
+        let plain = encode_column(&values);
+        assert_eq!(plain.dictionary().len(), 1);
+        assert_eq!(plain.decode(), values);
+
+        let rle = encode_column_rle(&values);
+        assert_eq!(rle.dictionary().len(), 1);
+        assert_eq!(rle.decode(), values);
+    }
+}
@@ -0,0 +1,167 @@
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::{Command, Child, ExitStatus, Output};
+use std::io;
+use std::fmt;
+
+use iosstr::IOsStr;
+use ipath::IPath;
+use env::EnvSnapshot;
+
+/// Reusable description of a process to spawn
+///
+/// The program, arguments and environment are kept interned, so cloning the builder
+/// and building a `Command` from it copies handles only. Task runners executing
+/// the same commands many times keep one builder per command and spawn it repeatedly.
+///
+/// ```
+/// # use internship::CommandBuilder;
+/// let mut echo = CommandBuilder::new("echo");
+/// echo.arg("hello").env("LC_ALL", "C");
+///
+/// for _ in 0..3 {
+///     let output = echo.output().unwrap();
+///     assert_eq!(output.stdout, b"hello\n");
+/// }
+/// ```
+#[derive(Clone)]
+pub struct CommandBuilder {
+    program: IOsStr,
+    args: Vec<IOsStr>,
+    base_env: Option<EnvSnapshot>,
+    // `None` removes the variable.
+    envs: Vec<(IOsStr, Option<IOsStr>)>,
+    current_dir: Option<IPath>,
+}
+
+impl CommandBuilder {
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Self {
+        CommandBuilder {
+            program: IOsStr::new(program.as_ref()),
+            args: Vec::new(),
+            base_env: None,
+            envs: Vec::new(),
+            current_dir: None,
+        }
+    }
+
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(IOsStr::new(arg.as_ref()));
+        self
+    }
+
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args.extend(args.into_iter().map(|arg| IOsStr::new(arg.as_ref())));
+        self
+    }
+
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, value: V) -> &mut Self {
+        self.envs.push((IOsStr::new(key.as_ref()), Some(IOsStr::new(value.as_ref()))));
+        self
+    }
+
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        self.envs.push((IOsStr::new(key.as_ref()), None));
+        self
+    }
+
+    /// Start from the snapshot instead of the environment of the current process.
+    /// Variables set by `env()` and `env_remove()` are applied over it.
+    pub fn env_snapshot(&mut self, snapshot: EnvSnapshot) -> &mut Self {
+        self.base_env = Some(snapshot);
+        self
+    }
+
+    /// Start from the empty environment.
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.env_snapshot(EnvSnapshot::default())
+    }
+
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.current_dir = Some(IPath::new(dir.as_ref()));
+        self
+    }
+
+    #[inline]
+    pub fn get_program(&self) -> &IOsStr {
+        &self.program
+    }
+
+    #[inline]
+    pub fn get_args(&self) -> &[IOsStr] {
+        &self.args
+    }
+
+    #[inline]
+    pub fn get_current_dir(&self) -> Option<&IPath> {
+        self.current_dir.as_ref()
+    }
+
+    /// Build a fresh `Command` to spawn.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+
+        if let Some(ref snapshot) = self.base_env {
+            snapshot.apply(&mut cmd);
+        }
+
+        for (key, value) in &self.envs {
+            match *value {
+                Some(ref value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+
+        if let Some(ref dir) = self.current_dir {
+            cmd.current_dir(dir);
+        }
+
+        cmd
+    }
+
+    pub fn spawn(&self) -> io::Result<Child> {
+        self.command().spawn()
+    }
+
+    pub fn status(&self) -> io::Result<ExitStatus> {
+        self.command().status()
+    }
+
+    pub fn output(&self) -> io::Result<Output> {
+        self.command().output()
+    }
+}
+
+impl fmt::Debug for CommandBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.command(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command() {
+        let mut builder = CommandBuilder::new("sh");
+        builder.args(["-c", "echo $GREETING"])
+            .env_clear()
+            .env("GREETING", "hi")
+            .env("REMOVED", "x")
+            .env_remove("REMOVED")
+            .current_dir("/");
+
+        let cmd = builder.command();
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(envs, [(OsStr::new("GREETING"), Some(OsStr::new("hi")))]);
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("/")));
+        assert_eq!(builder.clone().get_args()[1], "echo $GREETING");
+        assert_eq!(builder.output().unwrap().stdout, b"hi\n");
+    }
+}
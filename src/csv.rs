@@ -0,0 +1,267 @@
+//! CSV loader
+//!
+//! Reads CSV records with string fields interned, either into typed records
+//! through serde or into untyped rows of categorical data.
+
+use std::io;
+use std::ops::Deref;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::fmt;
+
+use csv_crate::{Reader, StringRecord, Result};
+use serde_crate::de::DeserializeOwned;
+
+use istr::IStr;
+
+/// Field of the record read by `InternReader`
+///
+/// Fields are compared by their contents, whether they're interned or not.
+#[derive(Clone)]
+pub enum Field {
+    Interned(IStr),
+    Plain(Box<str>),
+}
+
+impl Field {
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Field::Interned(v) => v.as_str(),
+            Field::Plain(v) => v,
+        }
+    }
+
+    /// Convert to `IStr`, interning it if not already interned.
+    pub fn into_istr(self) -> IStr {
+        match self {
+            Field::Interned(v) => v,
+            Field::Plain(v) => IStr::from(v),
+        }
+    }
+}
+
+impl Deref for Field {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for Field {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Field::Interned(a), Field::Interned(b)) => a == b,
+            _ => self.as_str() == other.as_str(),
+        }
+    }
+}
+
+impl Eq for Field {}
+
+impl Hash for Field {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.as_str().hash(hasher)
+    }
+}
+
+impl fmt::Debug for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// CSV reader which interns string fields
+///
+/// By default every field is interned. Use `intern_columns` to restrict it
+/// to the columns matching given predicate.
+pub struct InternReader<R> {
+    inner: Reader<R>,
+    record: StringRecord,
+    columns: Option<Vec<bool>>,
+}
+
+impl<R: io::Read> InternReader<R> {
+    pub fn new(inner: Reader<R>) -> Self {
+        InternReader {
+            inner,
+            record: StringRecord::new(),
+            columns: None,
+        }
+    }
+
+    /// Intern only the columns for which `pred` returns `true`.
+    ///
+    /// The predicate receives column index and its header, if the reader has headers.
+    /// Columns beyond the headers are not interned.
+    pub fn intern_columns<F>(mut self, mut pred: F) -> Result<Self>
+        where F: FnMut(usize, Option<&str>) -> bool
+    {
+        let columns = if self.inner.has_headers() {
+            self.inner.headers()?.iter()
+                .enumerate()
+                .map(|(idx, header)| pred(idx, Some(header)))
+                .collect()
+        } else {
+            let width = self.inner.headers()?.len();
+            (0..width).map(|idx| pred(idx, None)).collect()
+        };
+
+        self.columns = Some(columns);
+        Ok(self)
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &Reader<R> {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Reader<R> {
+        &mut self.inner
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> Reader<R> {
+        self.inner
+    }
+
+    /// Read the next record into `out`, reusing its allocation.
+    ///
+    /// Returns `false` if no more records are available.
+    pub fn read_record(&mut self, out: &mut Vec<Field>) -> Result<bool> {
+        out.clear();
+
+        if !self.inner.read_record(&mut self.record)? {
+            return Ok(false);
+        }
+
+        let columns = self.columns.as_ref();
+
+        out.extend(self.record.iter().enumerate().map(|(idx, field)| {
+            let intern = columns.is_none_or(|cols| cols.get(idx).cloned().unwrap_or(false));
+
+            if intern {
+                Field::Interned(IStr::new(field))
+            } else {
+                Field::Plain(field.into())
+            }
+        }));
+
+        Ok(true)
+    }
+
+    #[inline]
+    pub fn records(&mut self) -> Records<'_, R> {
+        Records(self)
+    }
+
+    /// Deserialize the next record into `D`, matching the fields by the headers if any.
+    ///
+    /// `IStr` fields of `D` are interned straight from the record buffer, while other
+    /// string fields like `String` are not. The columns set by `intern_columns` don't apply here,
+    /// as the field types decide it. Returns `None` if no more records are available.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// # use internship::csv::InternReader;
+    /// let data = "city,population\nSeoul,9500000\nBusan,3300000\n";
+    /// let mut reader = InternReader::new(csv::Reader::from_reader(data.as_bytes()));
+    ///
+    /// let rows: Vec<(IStr, u32)> = reader.deserialize().collect::<Result<_, _>>().unwrap();
+    /// assert_eq!(rows[1], (IStr::new("Busan"), 3300000));
+    /// ```
+    pub fn deserialize_record<D: DeserializeOwned>(&mut self) -> Option<Result<D>> {
+        match self.inner.read_record(&mut self.record) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(err) => return Some(Err(err)),
+        }
+
+        // The headers are read and cached by the reader along with the first record.
+        let headers = if self.inner.has_headers() {
+            match self.inner.headers() {
+                Ok(headers) => Some(headers),
+                Err(err) => return Some(Err(err)),
+            }
+        } else {
+            None
+        };
+
+        Some(self.record.deserialize(headers))
+    }
+
+    /// Iterator deserializing the records into `D`. See `deserialize_record`.
+    #[inline]
+    pub fn deserialize<D: DeserializeOwned>(&mut self) -> DeserializeRecords<'_, R, D> {
+        DeserializeRecords(self, PhantomData)
+    }
+}
+
+/// Iterator over the records of `InternReader` deserialized into `D`
+pub struct DeserializeRecords<'a, R: 'a, D>(&'a mut InternReader<R>, PhantomData<fn() -> D>);
+
+impl<'a, R: io::Read, D: DeserializeOwned> Iterator for DeserializeRecords<'a, R, D> {
+    type Item = Result<D>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.deserialize_record()
+    }
+}
+
+/// Iterator over the records of `InternReader`
+pub struct Records<'a, R: 'a>(&'a mut InternReader<R>);
+
+impl<'a, R: io::Read> Iterator for Records<'a, R> {
+    type Item = Result<Vec<Field>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = Vec::new();
+
+        match self.0.read_record(&mut record) {
+            Ok(true) => Some(Ok(record)),
+            Ok(false) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = "city,name\nSeoul,foo\nSeoul,bar\nBusan,foo\n";
+
+    #[test]
+    fn test_intern_columns() {
+        let reader = Reader::from_reader(DATA.as_bytes());
+        let mut reader = InternReader::new(reader)
+            .intern_columns(|_, header| header == Some("city"))
+            .unwrap();
+
+        let records: Vec<_> = reader.records().collect::<Result<_>>().unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0][0], Field::Interned(IStr::new("Seoul")));
+        assert_eq!(records[1][1], Field::Plain("bar".into()));
+        assert_eq!(&*records[2][0], "Busan");
+        assert_eq!(records[0][1], Field::Interned(IStr::new("foo")));
+        assert_eq!(records[0][1], records[2][1]);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let reader = Reader::from_reader(DATA.as_bytes());
+        let mut reader = InternReader::new(reader);
+
+        let rows: Vec<(IStr, String)> = reader.deserialize().collect::<Result<_>>().unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].0, rows[1].0);
+        assert!(rows[0].0.is(&IStr::new("Seoul")));
+        assert_eq!(rows[2], (IStr::new("Busan"), "foo".to_string()));
+    }
+}
@@ -0,0 +1,354 @@
+//! Decoding constructors
+//!
+//! Escaped sources are decoded into a reusable per-thread scratch buffer,
+//! so the only allocation is the pool entry itself, if it's not already interned.
+//! Decoded strings already in the pool are found without copying them anywhere else,
+//! and new ones are copied once from the scratch buffer into their pool entry.
+
+use std::cell::RefCell;
+use std::char;
+use std::convert::Infallible;
+use std::str;
+use std::mem;
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "encoding")]
+use encoding_rs::{Encoding, CoderResult};
+
+use handle;
+use istr::IStr;
+
+thread_local! {
+    static SCRATCH: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+const SCRATCH_KEEP_CAPACITY: usize = 1 << 16;
+
+/// Decode `src` into the scratch buffer with `f`, then intern the result.
+pub(crate) fn intern_decoded<S, E, F>(src: &S, f: F) -> Result<IStr, E>
+where
+    S: ?Sized,
+    F: FnOnce(&S, &mut String) -> Result<(), E>,
+{
+    SCRATCH.with(|scratch| {
+        // Fallback for the reentrant call, which is not expected.
+        let mut owned = String::new();
+        let mut guard = scratch.try_borrow_mut();
+        let buf = match guard {
+            Ok(ref mut buf) => &mut **buf,
+            Err(_) => &mut owned,
+        };
+
+        buf.clear();
+        // The pool entry needs its own allocation for the refcounts, so moving the buffer
+        // into it would copy the contents anyway and lose the buffer's capacity.
+        let res = f(src, buf).map(|()| IStr::new(buf));
+
+        if buf.capacity() > SCRATCH_KEEP_CAPACITY {
+            buf.clear();
+            buf.shrink_to(SCRATCH_KEEP_CAPACITY);
+        }
+
+        res
+    })
+}
+
+/// Error returned when the escape sequence is malformed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidEscape;
+
+/// Error returned when the percent-encoded string is malformed or not a valid UTF-8 once decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPercentEncoding;
+
+fn hex_value(b: u8) -> Option<u32> {
+    (b as char).to_digit(16)
+}
+
+fn parse_hex(digits: &str) -> Result<u32, InvalidEscape> {
+    if digits.is_empty() || digits.len() > 8 {
+        return Err(InvalidEscape);
+    }
+
+    digits.bytes().try_fold(0, |acc, b| hex_value(b).map(|v| acc << 4 | v).ok_or(InvalidEscape))
+}
+
+fn json_unescape(src: &str, buf: &mut String) -> Result<(), InvalidEscape> {
+    let mut rest = src;
+
+    while let Some(idx) = rest.find('\\') {
+        buf.push_str(&rest[..idx]);
+        let esc = &rest[idx + 1..];
+
+        let (c, len) = match esc.as_bytes().first() {
+            Some(b'"') => ('"', 1),
+            Some(b'\\') => ('\\', 1),
+            Some(b'/') => ('/', 1),
+            Some(b'b') => ('\u{8}', 1),
+            Some(b'f') => ('\u{c}', 1),
+            Some(b'n') => ('\n', 1),
+            Some(b'r') => ('\r', 1),
+            Some(b't') => ('\t', 1),
+            Some(b'u') => {
+                let high = parse_hex(esc.get(1..5).ok_or(InvalidEscape)?)?;
+
+                if (0xD800..0xDC00).contains(&high) {
+                    // Surrogate pair should be followed by its low half.
+                    if esc.get(5..7) != Some("\\u") {
+                        return Err(InvalidEscape);
+                    }
+                    let low = parse_hex(esc.get(7..11).ok_or(InvalidEscape)?)?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(InvalidEscape);
+                    }
+                    let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                    (char::from_u32(code).ok_or(InvalidEscape)?, 11)
+                } else {
+                    (char::from_u32(high).ok_or(InvalidEscape)?, 5)
+                }
+            }
+            _ => return Err(InvalidEscape),
+        };
+
+        buf.push(c);
+        rest = &esc[len..];
+    }
+
+    buf.push_str(rest);
+    Ok(())
+}
+
+fn backslash_unescape(src: &str, buf: &mut String) -> Result<(), InvalidEscape> {
+    let mut rest = src;
+
+    while let Some(idx) = rest.find('\\') {
+        buf.push_str(&rest[..idx]);
+        let esc = &rest[idx + 1..];
+
+        let (c, len) = match esc.as_bytes().first() {
+            Some(b'\\') => ('\\', 1),
+            Some(b'\'') => ('\'', 1),
+            Some(b'"') => ('"', 1),
+            Some(b'0') => ('\0', 1),
+            Some(b'a') => ('\u{7}', 1),
+            Some(b'b') => ('\u{8}', 1),
+            Some(b'f') => ('\u{c}', 1),
+            Some(b'n') => ('\n', 1),
+            Some(b'r') => ('\r', 1),
+            Some(b't') => ('\t', 1),
+            Some(b'v') => ('\u{b}', 1),
+            Some(b'x') => {
+                let code = parse_hex(esc.get(1..3).ok_or(InvalidEscape)?)?;
+                // Larger values are not valid UTF-8 on their own.
+                if code > 0x7F {
+                    return Err(InvalidEscape);
+                }
+                (code as u8 as char, 3)
+            }
+            Some(b'u') => {
+                if esc.as_bytes().get(1) != Some(&b'{') {
+                    return Err(InvalidEscape);
+                }
+                let end = esc.find('}').ok_or(InvalidEscape)?;
+                let code = parse_hex(&esc[2..end])?;
+                (char::from_u32(code).ok_or(InvalidEscape)?, end + 1)
+            }
+            _ => return Err(InvalidEscape),
+        };
+
+        buf.push(c);
+        rest = &esc[len..];
+    }
+
+    buf.push_str(rest);
+    Ok(())
+}
+
+fn percent_decode(src: &str, buf: &mut String) -> Result<(), InvalidPercentEncoding> {
+    let bytes = src.as_bytes();
+    // Decoded into the allocation of `buf`, which is left empty on errors.
+    let mut vec = mem::take(buf).into_bytes();
+    let mut idx = 0;
+
+    while idx < bytes.len() {
+        if bytes[idx] != b'%' {
+            vec.push(bytes[idx]);
+            idx += 1;
+            continue;
+        }
+
+        let hi = bytes.get(idx + 1).and_then(|&b| hex_value(b));
+        let lo = bytes.get(idx + 2).and_then(|&b| hex_value(b));
+
+        match (hi, lo) {
+            (Some(hi), Some(lo)) => vec.push((hi << 4 | lo) as u8),
+            _ => return Err(InvalidPercentEncoding),
+        }
+        idx += 3;
+    }
+
+    *buf = String::from_utf8(vec).map_err(|_| InvalidPercentEncoding)?;
+    Ok(())
+}
+
+impl IStr {
+    /// Intern the contents of a JSON string literal, without its surrounding quotes,
+    /// decoding its escape sequences.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let s = IStr::from_json_escaped(r#"say \"hi\"\n😀"#).unwrap();
+    /// assert_eq!(s, "say \"hi\"\n\u{1F600}");
+    /// ```
+    pub fn from_json_escaped(src: &str) -> Result<Self, InvalidEscape> {
+        if !src.contains('\\') {
+            return Ok(IStr::new(src));
+        }
+
+        intern_decoded(src, json_unescape)
+    }
+
+    /// Intern the string decoding C/Rust style backslash escapes:
+    /// `\\`, `\'`, `\"`, `\0`, `\a`, `\b`, `\f`, `\n`, `\r`, `\t`, `\v`,
+    /// `\xHH` up to `\x7F`, and `\u{H..}`.
+    pub fn from_backslash_escaped(src: &str) -> Result<Self, InvalidEscape> {
+        if !src.contains('\\') {
+            return Ok(IStr::new(src));
+        }
+
+        intern_decoded(src, backslash_unescape)
+    }
+
+    /// Intern the string decoding `%XX` sequences of the URL percent-encoding.
+    /// `+` is not decoded as it only means a space in the form encoding.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let s = IStr::from_percent_encoded("caf%C3%A9%20au%20lait").unwrap();
+    /// assert_eq!(s, "caf\u{e9} au lait");
+    /// ```
+    pub fn from_percent_encoded(src: &str) -> Result<Self, InvalidPercentEncoding> {
+        if !src.contains('%') {
+            return Ok(IStr::new(src));
+        }
+
+        intern_decoded(src, percent_decode)
+    }
+
+    /// Intern the text decoding HTML named and numeric character references.
+    /// Unknown references are kept as is.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let s = IStr::from_html_entities("Caf&eacute; &amp; Bar &#x2615;");
+    /// assert_eq!(s, "Caf\u{e9} & Bar \u{2615}");
+    /// ```
+    #[cfg(feature = "html")]
+    pub fn from_html_entities(src: &str) -> Self {
+        if !src.contains('&') {
+            return IStr::new(src);
+        }
+
+        infallible(intern_decoded(src, |src, buf| {
+            html_escape::decode_html_entities_to_string(src, buf);
+            Ok(())
+        }))
+    }
+
+    /// Intern the text encoded in ISO-8859-1, mapping each byte to the code point of same value.
+    pub fn from_latin1(src: &[u8]) -> Self {
+        if src.is_ascii() {
+            return IStr::new(handle::to_str(src));
+        }
+
+        infallible(intern_decoded(src, |src, buf| {
+            buf.extend(src.iter().map(|&b| b as char));
+            Ok(())
+        }))
+    }
+
+    /// Intern the text in given encoding. BOM is not handled,
+    /// and malformed sequences are replaced with `U+FFFD`.
+    ///
+    /// ```
+    /// # extern crate encoding_rs;
+    /// # extern crate internship;
+    /// # use internship::IStr;
+    /// let s = IStr::from_encoding(b"\x93quoted\x94", encoding_rs::WINDOWS_1252);
+    /// assert_eq!(s, "\u{201C}quoted\u{201D}");
+    /// ```
+    #[cfg(feature = "encoding")]
+    pub fn from_encoding(src: &[u8], encoding: &'static Encoding) -> Self {
+        if encoding.is_ascii_compatible() && src.is_ascii() {
+            return IStr::new(handle::to_str(src));
+        }
+
+        infallible(intern_decoded(src, |src, buf| {
+            let mut decoder = encoding.new_decoder_without_bom_handling();
+            let max_len = decoder.max_utf8_buffer_length(src.len())
+                .expect("input is too large to decode");
+            buf.reserve(max_len);
+            let (res, _, _) = decoder.decode_to_string(src, buf, true);
+            debug_assert_eq!(res, CoderResult::InputEmpty);
+            Ok(())
+        }))
+    }
+}
+
+pub(crate) fn infallible<T>(res: Result<T, Infallible>) -> T {
+    match res {
+        Ok(v) => v,
+        Err(never) => match never {},
+    }
+}
+
+impl fmt::Display for InvalidEscape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid escape sequence")
+    }
+}
+
+impl Error for InvalidEscape {}
+
+impl fmt::Display for InvalidPercentEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid percent-encoded string")
+    }
+}
+
+impl Error for InvalidPercentEncoding {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escaped() {
+        assert_eq!(IStr::from_json_escaped(r"a\/bé\t").unwrap(), "a/b\u{e9}\t");
+        assert_eq!(IStr::from_backslash_escaped(r"\x41\u{1F600}\'\0").unwrap(), "A\u{1F600}'\0");
+
+        for src in &[r"\", r"\q", r"\u12", r"\ud83d", r"\ud83dA"] {
+            assert_eq!(IStr::from_json_escaped(src), Err(InvalidEscape), "{}", src);
+        }
+        for src in &[r"\xFF", r"\u{110000}", r"\u{}", r"\u12"] {
+            assert_eq!(IStr::from_backslash_escaped(src), Err(InvalidEscape), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_percent_encoded() {
+        assert_eq!(IStr::from_percent_encoded("a%2Fb+c%25").unwrap(), "a/b+c%");
+
+        for src in &["%", "%2", "%zz", "%FF", "%C3"] {
+            assert_eq!(IStr::from_percent_encoded(src), Err(InvalidPercentEncoding), "{}", src);
+        }
+        assert_eq!(IStr::from_percent_encoded("%41").unwrap(), "A");
+    }
+
+    #[test]
+    fn test_latin1() {
+        assert_eq!(IStr::from_latin1(b"na\xefve caf\xe9"), "na\u{ef}ve caf\u{e9}");
+        assert_eq!(IStr::from_latin1(b"plain"), "plain");
+    }
+}
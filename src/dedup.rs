@@ -0,0 +1,338 @@
+//! Portable string deduplication for any serde format
+//!
+//! `Dedup<T>` serializes as a 2-tuple of the string table and the value,
+//! where every `IStr` inside of the value is replaced by its `u32` index into the table.
+//! The table is collected by a first pass over the value, so it precedes any reference.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde_crate::{Serialize, Serializer, Deserialize, Deserializer, de, ser};
+
+use istr::IStr;
+use algo::IdMap;
+
+enum Scope {
+    Collect(IdMap<u32>, Vec<IStr>),
+    Write(IdMap<u32>),
+    Read(Vec<IStr>),
+}
+
+thread_local! {
+    static SCOPE: RefCell<Option<Scope>> = const { RefCell::new(None) };
+}
+
+/// Clears the scope even if (de)serialization fails or panics.
+struct ScopeGuard;
+
+impl ScopeGuard {
+    fn enter(scope: Scope) -> Self {
+        SCOPE.with(|s| *s.borrow_mut() = Some(scope));
+        ScopeGuard
+    }
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        let _ = SCOPE.try_with(|s| s.borrow_mut().take());
+    }
+}
+
+fn is_active() -> bool {
+    SCOPE.with(|s| s.borrow().is_some())
+}
+
+/// Value whose `IStr`s are serialized once in a leading string table
+///
+/// Repeated strings are written only once no matter how many times they occur,
+/// with any serde format. Only `IStr`s are deduplicated, and nested `Dedup`s
+/// are serialized transparently within the outermost one.
+///
+/// ```
+/// # extern crate serde_json;
+/// # extern crate internship;
+/// # use internship::{IStr, Dedup};
+/// # fn main() {
+/// let tags: Vec<_> = ["red", "blue", "red", "red"].iter().map(|s| IStr::new(s)).collect();
+///
+/// let json = serde_json::to_string(&Dedup(&tags)).unwrap();
+/// assert_eq!(json, r#"[["red","blue"],[0,1,0,0]]"#);
+///
+/// let Dedup(decoded): Dedup<Vec<IStr>> = serde_json::from_str(&json).unwrap();
+/// assert_eq!(decoded, tags);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Dedup<T>(pub T);
+
+impl<T: Serialize> Serialize for Dedup<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use serde_crate::ser::SerializeTuple;
+
+        if is_active() {
+            return self.0.serialize(s);
+        }
+
+        let guard = ScopeGuard::enter(Scope::Collect(IdMap::default(), Vec::new()));
+        self.0.serialize(Collector::<S::Error>::new(s.is_human_readable()))?;
+
+        let (index, table) = match SCOPE.with(|s| s.borrow_mut().take()) {
+            Some(Scope::Collect(index, table)) => (index, table),
+            _ => unreachable!(),
+        };
+
+        let mut tuple = s.serialize_tuple(2)?;
+        tuple.serialize_element(&table)?;
+        SCOPE.with(|s| *s.borrow_mut() = Some(Scope::Write(index)));
+        tuple.serialize_element(&self.0)?;
+        drop(guard);
+        tuple.end()
+    }
+}
+
+impl<'d, T: Deserialize<'d>> Deserialize<'d> for Dedup<T> {
+    fn deserialize<D: Deserializer<'d>>(d: D) -> Result<Self, D::Error> {
+        if is_active() {
+            return T::deserialize(d).map(Dedup);
+        }
+
+        d.deserialize_tuple(2, Visitor(PhantomData))
+    }
+}
+
+struct Visitor<T>(PhantomData<T>);
+
+impl<'d, T: Deserialize<'d>> de::Visitor<'d> for Visitor<T> {
+    type Value = Dedup<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("string table followed by a value")
+    }
+
+    fn visit_seq<A: de::SeqAccess<'d>>(self, mut seq: A) -> Result<Dedup<T>, A::Error> {
+        let table: Vec<IStr> = seq.next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+
+        let _guard = ScopeGuard::enter(Scope::Read(table));
+        let value = seq.next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        Ok(Dedup(value))
+    }
+}
+
+/// Serialize the `IStr`, as a reference if it's within a `Dedup`.
+pub(crate) fn serialize<S: Serializer>(value: &IStr, s: S) -> Result<S::Ok, S::Error> {
+    enum Action {
+        Plain,
+        Skip,
+        Ref(Option<u32>),
+    }
+
+    let pooled = value.0.to_pooled().map(IStr);
+    let value = pooled.as_ref().unwrap_or(value);
+
+    let action = SCOPE.with(|scope| match *scope.borrow_mut() {
+        None | Some(Scope::Read(_)) => Action::Plain,
+        Some(Scope::Collect(ref mut index, ref mut table)) => {
+            index.entry(value.0.id()).or_insert_with(|| {
+                table.push(value.clone());
+                table.len() as u32 - 1
+            });
+            Action::Skip
+        }
+        Some(Scope::Write(ref index)) => Action::Ref(index.get(&value.0.id()).cloned()),
+    });
+
+    match action {
+        Action::Plain => s.serialize_str(value.as_str()),
+        Action::Skip => s.serialize_unit(),
+        Action::Ref(Some(idx)) => s.serialize_u32(idx),
+        Action::Ref(None) => Err(ser::Error::custom("string not visited while collecting the table")),
+    }
+}
+
+/// Deserialize the `IStr` reference within a `Dedup`.
+pub(crate) fn deserialize<'d, D: Deserializer<'d>>(d: D) -> Result<IStr, D::Error> {
+    let idx = u32::deserialize(d)?;
+
+    SCOPE.with(|scope| match *scope.borrow() {
+        Some(Scope::Read(ref table)) => table.get(idx as usize).cloned()
+            .ok_or_else(|| de::Error::custom(format_args!("invalid string reference {}", idx))),
+        _ => unreachable!(),
+    })
+}
+
+pub(crate) fn is_reading() -> bool {
+    SCOPE.with(|s| matches!(*s.borrow(), Some(Scope::Read(_))))
+}
+
+/// Serializer which only walks the value to collect its `IStr`s
+struct Collector<E> {
+    human_readable: bool,
+    _error: PhantomData<E>,
+}
+
+impl<E> Collector<E> {
+    fn new(human_readable: bool) -> Self {
+        Collector { human_readable, _error: PhantomData }
+    }
+}
+
+macro_rules! ignore {
+    ($($method:ident($($ty:ty),*);)*) => {$(
+        fn $method(self, $(_: $ty),*) -> Result<(), E> {
+            Ok(())
+        }
+    )*};
+}
+
+macro_rules! compound {
+    ($self:ident => $($method:ident($($ty:ty),*) -> $ret:ident;)*) => {$(
+        fn $method($self, $(_: $ty),*) -> Result<Self::$ret, E> {
+            Ok($self)
+        }
+    )*};
+}
+
+impl<E: ser::Error> Serializer for Collector<E> {
+    type Ok = ();
+    type Error = E;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    ignore! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_i128(i128);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_u128(u128);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    compound! { self =>
+        serialize_seq(Option<usize>) -> SerializeSeq;
+        serialize_tuple(usize) -> SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> SerializeTupleVariant;
+        serialize_map(Option<usize>) -> SerializeMap;
+        serialize_struct(&'static str, usize) -> SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> SerializeStructVariant;
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), E> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self, _: &'static str, value: &T,
+    ) -> Result<(), E> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self, _: &'static str, _: u32, _: &'static str, value: &T,
+    ) -> Result<(), E> {
+        value.serialize(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+}
+
+macro_rules! element {
+    ($($tr:ident :: $method:ident($($key:ident: $ty:ty),*);)*) => {$(
+        impl<E: ser::Error> ser::$tr for Collector<E> {
+            type Ok = ();
+            type Error = E;
+
+            fn $method<T: ?Sized + Serialize>(&mut self, $($key: $ty,)* value: &T) -> Result<(), E> {
+                $(let _ = $key;)*
+                value.serialize(Collector::new(self.human_readable))
+            }
+
+            fn end(self) -> Result<(), E> {
+                Ok(())
+            }
+        }
+    )*};
+}
+
+element! {
+    SerializeSeq::serialize_element();
+    SerializeTuple::serialize_element();
+    SerializeTupleStruct::serialize_field();
+    SerializeTupleVariant::serialize_field();
+    SerializeStruct::serialize_field(key: &'static str);
+    SerializeStructVariant::serialize_field(key: &'static str);
+}
+
+impl<E: ser::Error> ser::SerializeMap for Collector<E> {
+    type Ok = ();
+    type Error = E;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), E> {
+        key.serialize(Collector::new(self.human_readable))
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), E> {
+        value.serialize(Collector::new(self.human_readable))
+    }
+
+    fn end(self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_map_keys_and_bad_reference() {
+        let mut map = BTreeMap::new();
+        map.insert(IStr::new("a"), vec![IStr::new("b"), IStr::new("a")]);
+        map.insert(IStr::new("b"), vec![]);
+
+        let json = ::serde_json::to_string(&Dedup(&map)).unwrap();
+        assert_eq!(json, r#"[["a","b"],{"0":[1,0],"1":[]}]"#);
+
+        let Dedup(decoded): Dedup<BTreeMap<IStr, Vec<IStr>>> = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, map);
+        assert!(!is_active());
+
+        assert!(::serde_json::from_str::<Dedup<Vec<IStr>>>(r#"[["a"],[0,1]]"#).is_err());
+        assert!(!is_active());
+    }
+
+    #[test]
+    fn test_mixed_keys() {
+        let src = "a value long enough to be pooled";
+        let values = vec![IStr::new_unpooled(src), IStr::new(src), IStr::new_unpooled(src)];
+
+        let json = ::serde_json::to_string(&Dedup(&values)).unwrap();
+        assert_eq!(json, format!(r#"[["{}"],[0,0,0]]"#, src));
+    }
+}
@@ -0,0 +1,255 @@
+use std::error::Error;
+use std::fmt;
+
+use istr::IStr;
+
+/// Line-level difference between two strings
+///
+/// Lines keep their terminators and are interned, so the hunks of many similar documents
+/// share their lines, and comparing them is cheap.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Patch {
+    hunks: Vec<Hunk>,
+}
+
+/// Replacement of consecutive lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// Index of the first replaced line in the original string.
+    pub start: usize,
+    pub removed: Vec<IStr>,
+    pub inserted: Vec<IStr>,
+}
+
+/// Error of applying a `Patch` to a string it's not made from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchError {
+    /// Index of the first line not matching the patch.
+    pub line: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+fn lines(src: &str) -> Vec<IStr> {
+    src.split_inclusive('\n').map(IStr::new).collect()
+}
+
+/// Compute the line-level difference from `a` to `b`.
+///
+/// ```
+/// # use internship::{IStr, diff};
+/// let a = IStr::new("[server]\nport = 80\nhost = \"a\"\n");
+/// let b = IStr::new("[server]\nport = 8080\nhost = \"a\"\n");
+///
+/// let patch = diff(&a, &b);
+/// assert_eq!(patch.hunks().len(), 1);
+/// assert_eq!(patch.hunks()[0].inserted, ["port = 8080\n"]);
+/// assert_eq!(patch.apply(&a).unwrap(), b);
+/// ```
+pub fn diff(a: &IStr, b: &IStr) -> Patch {
+    if a == b {
+        return Patch::default();
+    }
+
+    let a = lines(a);
+    let b = lines(b);
+
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let old = &a[prefix..a.len() - suffix];
+    let new = &b[prefix..b.len() - suffix];
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    let mut last_op = Op::Equal;
+
+    for op in edit_script(old, new) {
+        if op != Op::Equal && last_op == Op::Equal {
+            hunks.push(Hunk {
+                start: prefix + x,
+                removed: Vec::new(),
+                inserted: Vec::new(),
+            });
+        }
+
+        match op {
+            Op::Equal => {
+                x += 1;
+                y += 1;
+            }
+            Op::Delete => {
+                hunks.last_mut().unwrap().removed.push(old[x].clone());
+                x += 1;
+            }
+            Op::Insert => {
+                hunks.last_mut().unwrap().inserted.push(new[y].clone());
+                y += 1;
+            }
+        }
+
+        last_op = op;
+    }
+
+    Patch { hunks }
+}
+
+/// Shortest edit script by the Myers' algorithm.
+fn edit_script(a: &[IStr], b: &[IStr]) -> Vec<Op> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let offset = max as usize;
+
+    let mut v = vec![0isize; 2 * offset + 2];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'outer: for d in 0..=max {
+        trace.push(v.clone());
+
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + max) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[idx] = x;
+
+            if x >= n && y >= m {
+                break 'outer;
+            }
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let idx = (k + max) as usize;
+
+        let prev_k = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + max) as usize];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+
+        if d > 0 {
+            ops.push(if x == prev_x { Op::Insert } else { Op::Delete });
+            x = prev_x;
+            y = prev_y;
+        }
+    }
+
+    ops.reverse();
+    ops
+}
+
+impl Patch {
+    #[inline]
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// Apply the patch to the string it's made from.
+    pub fn apply(&self, base: &str) -> Result<IStr, PatchError> {
+        if self.hunks.is_empty() {
+            return Ok(IStr::new(base));
+        }
+
+        let lines = lines(base);
+        let mut result = String::with_capacity(base.len());
+        let mut pos = 0;
+
+        for hunk in &self.hunks {
+            let end = hunk.start + hunk.removed.len();
+
+            if hunk.start < pos || end > lines.len() {
+                return Err(PatchError { line: pos.max(lines.len()) });
+            }
+
+            for line in &lines[pos..hunk.start] {
+                result.push_str(line);
+            }
+
+            if let Some(offset) = lines[hunk.start..end].iter().zip(&hunk.removed).position(|(a, b)| a != b) {
+                return Err(PatchError { line: hunk.start + offset });
+            }
+
+            for line in &hunk.inserted {
+                result.push_str(line);
+            }
+
+            pos = end;
+        }
+
+        for line in &lines[pos..] {
+            result.push_str(line);
+        }
+
+        Ok(IStr::from(result))
+    }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} doesn't match the patch", self.line)
+    }
+}
+
+impl Error for PatchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_apply() {
+        let cases = [
+            ("", "a\nb\n"),
+            ("a\nb\nc\n", ""),
+            ("a\nb\nc\nd\n", "a\nx\nc\ny\nd\nz"),
+            ("x\na\nb\n", "a\nb\nx\n"),
+            ("same\n", "same\n"),
+        ];
+
+        for &(a, b) in &cases {
+            let (a, b) = (IStr::new(a), IStr::new(b));
+            assert_eq!(diff(&a, &b).apply(&a).unwrap(), b, "{:?} -> {:?}", a, b);
+        }
+
+        let patch = diff(&IStr::new("a\nb\nc\nd\n"), &IStr::new("a\nx\nc\ny\nd\n"));
+        assert_eq!(patch.hunks().len(), 2);
+        assert_eq!(patch.hunks()[1], Hunk { start: 3, removed: vec![], inserted: vec![IStr::new("y\n")] });
+        assert_eq!(patch.apply("a\nB\nc\nd\n"), Err(PatchError { line: 1 }));
+    }
+}
@@ -0,0 +1,254 @@
+//! Differential testing against the reference implementation
+//!
+//! `Differential` runs the same sequence of operations on `IBytes` and on the safe
+//! `reference::RefIBytes` side by side, and compares their contents, comparisons
+//! and pool membership after every step. Fuzz targets can feed it arbitrary bytes
+//! with `Op::decode` to validate the unsafe fast path of the handles.
+//! The pools are checked by contents, so other values shouldn't be interned on the same thread meanwhile.
+//!
+//! ```
+//! # use internship::differential;
+//! differential::assert_equivalent(b"\x00\x01\x28a string long enough to be pooled\x01\x02\x00\x02\x01");
+//! ```
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use handle;
+use ibytes::IBytes;
+use reference::{self, RefIBytes};
+
+/// Number of the slots operations refer to.
+pub const SLOTS: usize = 16;
+
+const MAX_LEN: usize = 48;
+
+/// Operation on the slots of both implementations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// Intern the bytes into the slot, dropping its previous value.
+    Intern(usize, Vec<u8>),
+    /// Clone the value of the first slot into the second one.
+    Clone(usize, usize),
+    /// Drop the value of the slot.
+    Drop(usize),
+    /// Compare the values of the slots.
+    Compare(usize, usize),
+}
+
+/// Difference between the implementations found by `Differential`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub step: usize,
+    pub op: Op,
+    pub message: String,
+}
+
+/// Pair of the real and the reference implementation run side by side
+pub struct Differential {
+    real: Vec<Option<IBytes>>,
+    reference: Vec<Option<RefIBytes>>,
+    step: usize,
+}
+
+impl Op {
+    /// Decode the operations from arbitrary bytes, e.g. the input of a fuzz target.
+    /// Trailing bytes not enough for an operation are ignored.
+    pub fn decode(mut data: &[u8]) -> Vec<Op> {
+        let mut ops = Vec::new();
+
+        while let [code, slot, ref rest @ ..] = *data {
+            let slot = slot as usize % SLOTS;
+
+            let op = match code % 4 {
+                0 => {
+                    let (&len, rest) = match rest.split_first() {
+                        Some(split) => split,
+                        None => break,
+                    };
+                    let len = (len as usize % MAX_LEN).min(rest.len());
+                    data = &rest[len..];
+                    ops.push(Op::Intern(slot, rest[..len].to_vec()));
+                    continue;
+                }
+                1 | 3 => {
+                    let (&other, rest) = match rest.split_first() {
+                        Some(split) => split,
+                        None => break,
+                    };
+                    data = rest;
+                    let other = other as usize % SLOTS;
+
+                    if code % 4 == 1 {
+                        Op::Clone(slot, other)
+                    } else {
+                        Op::Compare(slot, other)
+                    }
+                }
+                _ => {
+                    data = rest;
+                    Op::Drop(slot)
+                }
+            };
+
+            ops.push(op);
+        }
+
+        ops
+    }
+}
+
+impl Differential {
+    pub fn new() -> Self {
+        Differential {
+            real: vec![None; SLOTS],
+            reference: vec![None; SLOTS],
+            step: 0,
+        }
+    }
+
+    /// Apply the operation to both implementations and compare them.
+    pub fn apply(&mut self, op: &Op) -> Result<(), Mismatch> {
+        let result = self.apply_inner(op).and_then(|()| self.check());
+        let step = self.step;
+        self.step += 1;
+
+        result.map_err(|message| Mismatch { step, op: op.clone(), message })
+    }
+
+    /// Apply the operations in order, stopping at the first mismatch.
+    pub fn run<'a, I: IntoIterator<Item = &'a Op>>(&mut self, ops: I) -> Result<(), Mismatch> {
+        for op in ops {
+            self.apply(op)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_inner(&mut self, op: &Op) -> Result<(), String> {
+        match *op {
+            Op::Intern(slot, ref bytes) => {
+                self.real[slot] = Some(IBytes::new(bytes));
+                self.reference[slot] = Some(RefIBytes::new(bytes));
+            }
+            Op::Clone(from, to) => {
+                self.real[to] = self.real[from].clone();
+                self.reference[to] = self.reference[from].clone();
+            }
+            Op::Drop(slot) => {
+                self.real[slot] = None;
+                self.reference[slot] = None;
+            }
+            Op::Compare(a, b) => {
+                let real = compare(&self.real[a], &self.real[b]);
+                let reference = compare(&self.reference[a], &self.reference[b]);
+
+                if real != reference {
+                    return Err(format!("slots {} and {} compare as {:?}, expected {:?}", a, b, real, reference));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compare the contents and the pool membership of every slot.
+    fn check(&self) -> Result<(), String> {
+        for (slot, (real, reference)) in self.real.iter().zip(&self.reference).enumerate() {
+            let (real, reference) = match (real, reference) {
+                (Some(real), Some(reference)) => (real, reference),
+                (None, None) => continue,
+                _ => return Err(format!("slot {} is occupied by only one of them", slot)),
+            };
+
+            if real.as_bytes() != reference.as_bytes() {
+                return Err(format!("slot {} has {:?}, expected {:?}", slot, real.as_bytes(), reference.as_bytes()));
+            }
+        }
+
+        // Values dropped from every slot must leave both pools, unless inlined by the real one.
+        for reference in self.reference.iter().flatten() {
+            let bytes = reference.as_bytes();
+            let pooled = !handle::interns_inline(bytes.len());
+
+            if handle::pool_contains(bytes) != pooled {
+                return Err(format!("pool membership of {:?} is {}, expected {}", bytes, !pooled, pooled));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drop every value and check that both pools released them.
+    pub fn finish(mut self) -> Result<(), Mismatch> {
+        let values: Vec<Vec<u8>> = self.reference.iter().flatten().map(|v| v.as_bytes().to_vec()).collect();
+        let step = self.step;
+
+        for slot in 0..SLOTS {
+            self.real[slot] = None;
+            self.reference[slot] = None;
+        }
+
+        for bytes in values {
+            if handle::pool_contains(&bytes) || reference::pool_contains(&bytes) {
+                return Err(Mismatch {
+                    step,
+                    op: Op::Drop(SLOTS),
+                    message: format!("{:?} is left in the pool after every value is dropped", bytes),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Differential {
+    fn default() -> Self {
+        Differential::new()
+    }
+}
+
+fn compare<T: Ord>(a: &Option<T>, b: &Option<T>) -> (bool, Option<Ordering>) {
+    match (a.as_ref(), b.as_ref()) {
+        (Some(a), Some(b)) => (a == b, Some(a.cmp(b))),
+        _ => (a.is_none() && b.is_none(), None),
+    }
+}
+
+/// Decode the operations from the bytes and run them, panicking on the first mismatch.
+/// It's meant to be the body of a fuzz target.
+#[track_caller]
+pub fn assert_equivalent(data: &[u8]) {
+    let ops = Op::decode(data);
+    let mut differential = Differential::new();
+
+    if let Err(mismatch) = differential.run(&ops).and_then(|()| differential.finish()) {
+        panic!("{}\nops: {:?}", mismatch, ops);
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mismatch at step {} ({:?}): {}", self.step, self.op, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_ops() {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let data: Vec<u8> = (0..20000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            // Few distinct contents so the slots share entries.
+            (state % 7) as u8 * 37
+        }).collect();
+
+        assert_equivalent(&data);
+    }
+}
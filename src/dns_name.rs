@@ -0,0 +1,232 @@
+use std::ops::Deref;
+use std::str::FromStr;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::error::Error;
+use std::vec;
+use std::io;
+use std::fmt;
+
+use istr::IStr;
+
+/// Interned DNS name
+///
+/// Names are normalized before interning. They're lowercased and the trailing dot is removed,
+/// so `Example.COM.` and `example.com` are the same name.
+/// Only ASCII names are accepted, internationalized names should be punycode encoded first.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IDnsName(IStr);
+
+/// Error returned when the DNS name is malformed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidDnsName {
+    Empty,
+    TooLong,
+    EmptyLabel,
+    LabelTooLong,
+    InvalidChar,
+    InvalidHyphen,
+}
+
+const MAX_NAME_LEN: usize = 253;
+const MAX_LABEL_LEN: usize = 63;
+
+impl IDnsName {
+    pub fn new(src: &str) -> Result<Self, InvalidDnsName> {
+        let name = src.strip_suffix('.').unwrap_or(src);
+
+        if name.is_empty() {
+            return Err(InvalidDnsName::Empty);
+        }
+
+        if name.len() > MAX_NAME_LEN {
+            return Err(InvalidDnsName::TooLong);
+        }
+
+        for label in name.split('.') {
+            validate_label(label)?;
+        }
+
+        if name.bytes().any(|b| b.is_ascii_uppercase()) {
+            Ok(IDnsName(IStr::new(&name.to_ascii_lowercase())))
+        } else {
+            Ok(IDnsName(IStr::new(name)))
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    #[inline]
+    pub fn as_istr(&self) -> &IStr {
+        &self.0
+    }
+
+    #[inline]
+    pub fn into_istr(self) -> IStr {
+        self.0
+    }
+
+    pub fn labels(&self) -> ::std::str::Split<'_, char> {
+        self.as_str().split('.')
+    }
+
+    /// Parent domain, or `None` if this is a top level name.
+    pub fn parent(&self) -> Option<IDnsName> {
+        self.as_str().find('.').map(|idx| IDnsName(IStr::new(&self.as_str()[idx + 1..])))
+    }
+
+    /// Whether this name is equal to or a subdomain of `other`.
+    pub fn is_subdomain_of(&self, other: &IDnsName) -> bool {
+        let (name, other) = (self.as_str(), other.as_str());
+
+        name == other || (name.ends_with(other)
+            && name.as_bytes()[name.len() - other.len() - 1] == b'.')
+    }
+
+    /// Pair the name with a port, to be resolved through `ToSocketAddrs`.
+    #[inline]
+    pub fn with_port(self, port: u16) -> DnsAddr {
+        DnsAddr { name: self, port }
+    }
+}
+
+fn validate_label(label: &str) -> Result<(), InvalidDnsName> {
+    if label.is_empty() {
+        return Err(InvalidDnsName::EmptyLabel);
+    }
+
+    if label.len() > MAX_LABEL_LEN {
+        return Err(InvalidDnsName::LabelTooLong);
+    }
+
+    if !label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+        return Err(InvalidDnsName::InvalidChar);
+    }
+
+    if label.starts_with('-') || label.ends_with('-') {
+        return Err(InvalidDnsName::InvalidHyphen);
+    }
+
+    Ok(())
+}
+
+/// `IDnsName` with port number
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DnsAddr {
+    pub name: IDnsName,
+    pub port: u16,
+}
+
+impl ToSocketAddrs for DnsAddr {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        (self.name.as_str(), self.port).to_socket_addrs()
+    }
+}
+
+impl FromStr for IDnsName {
+    type Err = InvalidDnsName;
+
+    fn from_str(src: &str) -> Result<Self, InvalidDnsName> {
+        IDnsName::new(src)
+    }
+}
+
+impl Deref for IDnsName {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for IDnsName {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<IDnsName> for IStr {
+    #[inline]
+    fn from(v: IDnsName) -> IStr {
+        v.0
+    }
+}
+
+impl fmt::Debug for IDnsName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for IDnsName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for InvalidDnsName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            InvalidDnsName::Empty => "empty DNS name",
+            InvalidDnsName::TooLong => "DNS name is longer than 253 bytes",
+            InvalidDnsName::EmptyLabel => "DNS name contains empty label",
+            InvalidDnsName::LabelTooLong => "DNS label is longer than 63 bytes",
+            InvalidDnsName::InvalidChar => "DNS name contains invalid character",
+            InvalidDnsName::InvalidHyphen => "DNS label starts or ends with hyphen",
+        })
+    }
+}
+
+impl Error for InvalidDnsName {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_of_len(len: usize) -> String {
+        // Labels of 63 bytes joined by dots, with the last label filling the rest.
+        let mut name = String::new();
+        while len - name.len() > MAX_LABEL_LEN + 1 {
+            name.push_str(&"a".repeat(MAX_LABEL_LEN));
+            name.push('.');
+        }
+        name.push_str(&"b".repeat(len - name.len()));
+        name
+    }
+
+    #[test]
+    fn test_label_len() {
+        assert!(IDnsName::new(&format!("{}.com", "a".repeat(63))).is_ok());
+        assert_eq!(IDnsName::new(&format!("{}.com", "a".repeat(64))), Err(InvalidDnsName::LabelTooLong));
+        assert_eq!(IDnsName::new("a..com"), Err(InvalidDnsName::EmptyLabel));
+    }
+
+    #[test]
+    fn test_name_len() {
+        let max = name_of_len(MAX_NAME_LEN);
+        assert_eq!(IDnsName::new(&max).unwrap().as_str(), max);
+        assert!(IDnsName::new(&format!("{}.", max)).is_ok());
+        assert_eq!(IDnsName::new(&name_of_len(MAX_NAME_LEN + 1)), Err(InvalidDnsName::TooLong));
+    }
+
+    #[test]
+    fn test_trailing_dot() {
+        assert_eq!(IDnsName::new("Example.COM.").unwrap(), IDnsName::new("example.com").unwrap());
+        assert_eq!(IDnsName::new("."), Err(InvalidDnsName::Empty));
+        assert_eq!(IDnsName::new("example.com.."), Err(InvalidDnsName::EmptyLabel));
+    }
+
+    #[test]
+    fn test_hyphen() {
+        assert!(IDnsName::new("my-host.example").is_ok());
+        assert_eq!(IDnsName::new("-host.example"), Err(InvalidDnsName::InvalidHyphen));
+        assert_eq!(IDnsName::new("host-.example"), Err(InvalidDnsName::InvalidHyphen));
+        assert_eq!(IDnsName::new("host.example!"), Err(InvalidDnsName::InvalidChar));
+    }
+}
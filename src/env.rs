@@ -0,0 +1,101 @@
+//! Interned environment variables
+//!
+//! Process-spawning programs copy the same environment for every child,
+//! so the names and values are interned once and shared by all copies.
+//!
+//! ```
+//! # use internship::env;
+//! # use std::process::Command;
+//! let snapshot = env::EnvSnapshot::current();
+//! assert_eq!(snapshot, env::EnvSnapshot::current());
+//!
+//! let mut cmd = Command::new("true");
+//! snapshot.apply(&mut cmd);
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsStr;
+use std::process::Command;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::fmt;
+
+use iosstr::IOsStr;
+
+/// Iterate over the environment variables of the current process, interned.
+pub fn vars_interned() -> impl Iterator<Item = (IOsStr, IOsStr)> {
+    env::vars_os().map(|(key, value)| (IOsStr::from(key), IOsStr::from(value)))
+}
+
+/// Environment variables at a point in time
+///
+/// Cloning is cheap, and snapshots of the same environment share their table.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct EnvSnapshot(Rc<BTreeMap<IOsStr, IOsStr>>);
+
+thread_local! {
+    static LAST: RefCell<EnvSnapshot> = RefCell::new(EnvSnapshot::default());
+}
+
+impl EnvSnapshot {
+    /// Capture the current environment.
+    ///
+    /// Returns the previous snapshot of this thread if the environment hasn't changed since.
+    pub fn current() -> Self {
+        let vars: BTreeMap<_, _> = vars_interned().collect();
+
+        LAST.with(|last| {
+            let mut last = last.borrow_mut();
+            if *last.0 != vars {
+                *last = EnvSnapshot(Rc::new(vars));
+            }
+            last.clone()
+        })
+    }
+
+    pub fn get<K: AsRef<OsStr>>(&self, key: K) -> Option<&IOsStr> {
+        self.0.get(key.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&IOsStr, &IOsStr)> {
+        self.0.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Replace the environment of the command with this snapshot.
+    pub fn apply<'a>(&self, cmd: &'a mut Command) -> &'a mut Command {
+        cmd.env_clear().envs(self.iter())
+    }
+}
+
+impl fmt::Debug for EnvSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_snapshot() {
+        let a = EnvSnapshot::current();
+        let b = EnvSnapshot::current();
+        assert!(Rc::ptr_eq(&a.0, &b.0));
+        assert_eq!(a.len(), env::vars_os().count());
+        if let Some((key, value)) = env::vars_os().next() {
+            assert_eq!(a.get(&key).unwrap().as_os_str(), value);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Escaping helpers
+//!
+//! The escaped form is cached in a side table of the source's pool entry,
+//! so rendering the same string again doesn't re-escape it.
+
+use std::fmt::Write;
+use std::marker::PhantomData;
+
+use decode::{intern_decoded, infallible};
+use istr::IStr;
+use side;
+
+struct Json;
+struct Html;
+struct Shell;
+
+// `None` if the escaped form is the same as the source,
+// which can't be stored in the source's own side table without keeping it alive.
+struct Escaped<K>(Option<IStr>, PhantomData<K>);
+
+fn escape<K: 'static>(src: &IStr, needs_escape: fn(&str) -> bool, f: fn(&str, &mut String)) -> IStr {
+    let escape = || if needs_escape(src) {
+        Some(infallible(intern_decoded(src.as_str(), |src, buf| {
+            f(src, buf);
+            Ok(())
+        })))
+    } else {
+        None
+    };
+
+    // Inlined strings have no pool entry to cache with.
+    let escaped = if src.0.is_inline() {
+        escape()
+    } else {
+        side::entry::<Escaped<K>>(src).or_insert_with(|| Escaped(escape(), PhantomData)).0.clone()
+    };
+
+    escaped.unwrap_or_else(|| src.clone())
+}
+
+fn json_needs_escape(src: &str) -> bool {
+    src.bytes().any(|b| b == b'"' || b == b'\\' || b < 0x20)
+}
+
+fn json_escape(src: &str, buf: &mut String) {
+    for ch in src.chars() {
+        match ch {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            '\u{8}' => buf.push_str("\\b"),
+            '\u{c}' => buf.push_str("\\f"),
+            ch if ch < ' ' => write!(buf, "\\u{:04x}", ch as u32).unwrap(),
+            ch => buf.push(ch),
+        }
+    }
+}
+
+fn html_needs_escape(src: &str) -> bool {
+    src.bytes().any(|b| matches!(b, b'&' | b'<' | b'>' | b'"' | b'\''))
+}
+
+fn html_escape(src: &str, buf: &mut String) {
+    for ch in src.chars() {
+        match ch {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            '\'' => buf.push_str("&#x27;"),
+            ch => buf.push(ch),
+        }
+    }
+}
+
+fn shell_needs_escape(src: &str) -> bool {
+    src.is_empty() || !src.bytes().all(|b| b.is_ascii_alphanumeric() || b"_@%+=:,./-".contains(&b))
+}
+
+fn shell_escape(src: &str, buf: &mut String) {
+    buf.push('\'');
+    for ch in src.chars() {
+        match ch {
+            '\'' => buf.push_str("'\\''"),
+            ch => buf.push(ch),
+        }
+    }
+    buf.push('\'');
+}
+
+impl IStr {
+    /// Escape the string as the contents of a JSON string literal, without surrounding quotes.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let s = IStr::new("say \"hi\"\n");
+    /// assert_eq!(s.escape_json(), r#"say \"hi\"\n"#);
+    /// assert_eq!(IStr::from_json_escaped(&s.escape_json()).unwrap(), s);
+    /// ```
+    pub fn escape_json(&self) -> IStr {
+        escape::<Json>(self, json_needs_escape, json_escape)
+    }
+
+    /// Escape the text for HTML contents and quoted attribute values.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// assert_eq!(IStr::new("<a href='x'>").escape_html(), "&lt;a href=&#x27;x&#x27;&gt;");
+    /// ```
+    pub fn escape_html(&self) -> IStr {
+        escape::<Html>(self, html_needs_escape, html_escape)
+    }
+
+    /// Quote the string as a single word for POSIX shells, if it's not safe as is.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// assert_eq!(IStr::new("src/lib.rs").escape_shell(), "src/lib.rs");
+    /// assert_eq!(IStr::new("it's here").escape_shell(), r"'it'\''s here'");
+    /// ```
+    pub fn escape_shell(&self) -> IStr {
+        escape::<Shell>(self, shell_needs_escape, shell_escape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached() {
+        let src = IStr::new("a string <long> enough to be pooled");
+        let escaped = src.escape_html();
+        assert_eq!(escaped, "a string &lt;long&gt; enough to be pooled");
+        assert!(side::entry::<Escaped<Html>>(&src).get().is_some());
+
+        let plain = IStr::new("nothing to escape for the JSON string");
+        assert_eq!(plain.escape_json(), plain);
+        assert_eq!(IStr::new("\u{1}").escape_json(), "\\u0001");
+        assert_eq!(IStr::new("").escape_shell(), "''");
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+use std::fmt;
+
+use istr::IStr;
+use side;
+
+/// Set of glob patterns matched against paths
+///
+/// Patterns are compiled once per pool entry, so sets built from overlapping
+/// ignore files share the compiled form of their common patterns.
+///
+/// `*` and `?` don't match `/`, `**` matches anything,
+/// and `**/` matches zero or more leading directories.
+/// `[abc]`, `[a-z]` and `[!a-z]` match a character of the class, and `\` escapes the next one.
+///
+/// ```
+/// # use internship::GlobSet;
+/// let mut ignore = GlobSet::new();
+/// ignore.add("**/*.rs.bk").unwrap();
+/// ignore.add("target/**").unwrap();
+///
+/// assert!(ignore.is_match("src/lib.rs.bk"));
+/// assert!(ignore.is_match("target/debug/build"));
+/// assert!(!ignore.is_match("src/lib.rs"));
+///
+/// ignore.remove("target/**");
+/// assert!(!ignore.is_match("target/debug/build"));
+/// ```
+#[derive(Clone, Default)]
+pub struct GlobSet {
+    globs: HashMap<IStr, Rc<Glob>>,
+}
+
+/// Error returned when the glob pattern has unclosed class or trailing backslash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidGlob;
+
+struct Glob(Vec<Token>);
+
+enum Token {
+    Char(char),
+    AnyChar,
+    Star,
+    GlobStar,
+    // `**/`
+    Dirs,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Glob {
+    fn compile(pattern: &str) -> Result<Glob, InvalidGlob> {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            let token = match ch {
+                '?' => Token::AnyChar,
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        Token::Dirs
+                    } else {
+                        Token::GlobStar
+                    }
+                }
+                '*' => Token::Star,
+                '\\' => Token::Char(chars.next().ok_or(InvalidGlob)?),
+                '[' => {
+                    let negated = chars.peek() == Some(&'!');
+                    if negated {
+                        chars.next();
+                    }
+
+                    let mut ranges = Vec::new();
+                    loop {
+                        let start = match chars.next().ok_or(InvalidGlob)? {
+                            ']' if !ranges.is_empty() => break,
+                            '\\' => chars.next().ok_or(InvalidGlob)?,
+                            ch => ch,
+                        };
+
+                        let mut lookahead = chars.clone();
+                        let end = match (lookahead.next(), lookahead.next()) {
+                            (Some('-'), Some(end)) if end != ']' => {
+                                chars.next();
+                                chars.next();
+                                end
+                            }
+                            _ => start,
+                        };
+
+                        ranges.push((start, end));
+                    }
+
+                    Token::Class { negated, ranges }
+                }
+                ch => Token::Char(ch),
+            };
+
+            tokens.push(token);
+        }
+
+        Ok(Glob(tokens))
+    }
+
+    fn is_match(&self, path: &str) -> bool {
+        fn matches(tokens: &[Token], text: &str) -> bool {
+            let (token, rest) = match tokens.split_first() {
+                Some(split) => split,
+                None => return text.is_empty(),
+            };
+
+            let single = |pred: &dyn Fn(char) -> bool| {
+                let mut chars = text.chars();
+                match chars.next() {
+                    Some(ch) if pred(ch) => matches(rest, chars.as_str()),
+                    _ => false,
+                }
+            };
+
+            match *token {
+                Token::Char(expected) => single(&|ch| ch == expected),
+                Token::AnyChar => single(&|ch| ch != '/'),
+                Token::Class { negated, ref ranges } => single(&|ch| {
+                    ch != '/' && ranges.iter().any(|&(lo, hi)| lo <= ch && ch <= hi) != negated
+                }),
+                Token::Star => {
+                    let end = text.find('/').unwrap_or(text.len());
+                    (0..=end).filter(|&idx| text.is_char_boundary(idx))
+                        .any(|idx| matches(rest, &text[idx..]))
+                }
+                Token::GlobStar => {
+                    (0..=text.len()).filter(|&idx| text.is_char_boundary(idx))
+                        .any(|idx| matches(rest, &text[idx..]))
+                }
+                Token::Dirs => {
+                    matches(rest, text) || text.match_indices('/')
+                        .any(|(idx, _)| matches(rest, &text[idx + 1..]))
+                }
+            }
+        }
+
+        matches(&self.0, path)
+    }
+}
+
+impl GlobSet {
+    pub fn new() -> Self {
+        GlobSet::default()
+    }
+
+    /// Add the pattern, returning `false` if it's already in the set.
+    pub fn add<P: Into<IStr>>(&mut self, pattern: P) -> Result<bool, InvalidGlob> {
+        let pattern = pattern.into();
+
+        if self.globs.contains_key(&pattern) {
+            return Ok(false);
+        }
+
+        // Inlined patterns have no pool entry to share the compiled form.
+        let glob = if pattern.0.is_inline() {
+            Rc::new(Glob::compile(&pattern)?)
+        } else {
+            match side::entry::<Glob>(&pattern).get() {
+                Some(glob) => glob,
+                None => {
+                    let glob = Glob::compile(&pattern)?;
+                    side::entry(&pattern).or_insert(glob)
+                }
+            }
+        };
+
+        self.globs.insert(pattern, glob);
+        Ok(true)
+    }
+
+    /// Remove the pattern, returning `false` if it's not in the set.
+    pub fn remove(&mut self, pattern: &str) -> bool {
+        self.globs.remove(pattern).is_some()
+    }
+
+    #[inline]
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.globs.contains_key(pattern)
+    }
+
+    /// Whether any pattern matches the path.
+    pub fn is_match(&self, path: &str) -> bool {
+        self.globs.values().any(|glob| glob.is_match(path))
+    }
+
+    /// Patterns matching the path.
+    pub fn matches<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a IStr> + 'a {
+        self.globs.iter()
+            .filter(move |&(_, glob)| glob.is_match(path))
+            .map(|(pattern, _)| pattern)
+    }
+
+    pub fn patterns(&self) -> impl Iterator<Item = &IStr> {
+        self.globs.keys()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.globs.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.globs.is_empty()
+    }
+}
+
+impl fmt::Debug for GlobSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut patterns: Vec<_> = self.patterns().collect();
+        patterns.sort();
+        f.debug_set().entries(patterns).finish()
+    }
+}
+
+impl fmt::Display for InvalidGlob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid glob pattern")
+    }
+}
+
+impl Error for InvalidGlob {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        let cases = [
+            ("*.rs", "lib.rs", true),
+            ("*.rs", "src/lib.rs", false),
+            ("**/*.rs", "lib.rs", true),
+            ("**/*.rs", "src/a/lib.rs", true),
+            ("src/**", "src/a/b", true),
+            ("a?c", "a/c", false),
+            ("[a-c]x", "bx", true),
+            ("[!a-c]x", "bx", false),
+            ("[]]", "]", true),
+            ("\\*", "*", true),
+            ("\\*", "a", false),
+            ("héllo*", "héllo wörld", true),
+        ];
+
+        for &(pattern, path, expected) in &cases {
+            assert_eq!(Glob::compile(pattern).unwrap().is_match(path), expected, "{} {}", pattern, path);
+        }
+
+        assert!(Glob::compile("[ab").is_err());
+        assert!(Glob::compile("a\\").is_err());
+    }
+
+    #[test]
+    fn test_shared_compilation() {
+        let pattern = "a pattern long enough to be pooled/**";
+        let mut a = GlobSet::new();
+        let mut b = GlobSet::new();
+
+        assert_eq!(a.add(pattern), Ok(true));
+        assert_eq!(a.add(pattern), Ok(false));
+        assert_eq!(b.add(pattern), Ok(true));
+        assert!(Rc::ptr_eq(&a.globs[pattern], &b.globs[pattern]));
+        assert_eq!(a.add("[x"), Err(InvalidGlob));
+    }
+}
@@ -0,0 +1,114 @@
+//! GraphQL scalar support
+//!
+//! `SendIStr` is exposed as the builtin `String` scalar, so it can be used as field types and arguments.
+//! Both libraries require resolvable types to be `Send` and `Sync` which `IStr` is not,
+//! so arguments arrive as `SendIStr` and are interned on the resolver's thread with `into_istr()`.
+
+#[cfg(feature = "async-graphql")]
+mod async_graphql_compat {
+    use std::borrow::Cow;
+    use std::future::{self, Future};
+
+    use async_graphql::{
+        ContextSelectionSet, InputType, InputValueError, InputValueResult, OutputType,
+        Positioned, ServerResult, Value, parser::types::Field, registry::Registry,
+    };
+
+    use send_istr::SendIStr;
+
+    impl InputType for SendIStr {
+        type RawValueType = str;
+
+        fn type_name() -> Cow<'static, str> {
+            Cow::Borrowed("String")
+        }
+
+        fn create_type_info(registry: &mut Registry) -> String {
+            <String as OutputType>::create_type_info(registry)
+        }
+
+        fn parse(value: Option<Value>) -> InputValueResult<Self> {
+            match value.unwrap_or_default() {
+                Value::String(s) => Ok(SendIStr::new(&s)),
+                value => Err(InputValueError::expected_type(value)),
+            }
+        }
+
+        fn to_value(&self) -> Value {
+            Value::String(self.as_str().into())
+        }
+
+        fn as_raw_value(&self) -> Option<&str> {
+            Some(self.as_str())
+        }
+    }
+
+    impl OutputType for SendIStr {
+        fn type_name() -> Cow<'static, str> {
+            Cow::Borrowed("String")
+        }
+
+        fn create_type_info(registry: &mut Registry) -> String {
+            <String as OutputType>::create_type_info(registry)
+        }
+
+        fn resolve(
+            &self,
+            _: &ContextSelectionSet<'_>,
+            _: &Positioned<Field>,
+        ) -> impl Future<Output = ServerResult<Value>> + Send {
+            future::ready(Ok(Value::String(self.as_str().into())))
+        }
+    }
+}
+
+#[cfg(feature = "juniper")]
+mod juniper_compat {
+    use juniper::{graphql_scalar, InputValue, ScalarValue, Value};
+
+    use send_istr::SendIStr;
+
+    // Only used to hang the generated impls on.
+    #[allow(dead_code)]
+    #[graphql_scalar(name = "String", with = send_istr_scalar, parse_token(String))]
+    type Scalar = SendIStr;
+
+    mod send_istr_scalar {
+        use super::*;
+
+        pub(super) fn to_output<S: ScalarValue>(v: &SendIStr) -> Value<S> {
+            Value::scalar(v.as_str().to_owned())
+        }
+
+        pub(super) fn from_input<S: ScalarValue>(v: &InputValue<S>) -> Result<SendIStr, String> {
+            v.as_string_value()
+                .map(SendIStr::new)
+                .ok_or_else(|| format!("Expected `String`, found: {}", v))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "juniper"))]
+mod tests {
+    use juniper::{graphql_object, graphql_value, EmptyMutation, EmptySubscription, RootNode, Variables};
+
+    use send_istr::SendIStr;
+
+    struct Query;
+
+    #[graphql_object]
+    impl Query {
+        fn echo(value: SendIStr) -> SendIStr {
+            SendIStr::from(value.into_istr())
+        }
+    }
+
+    #[test]
+    fn test_juniper_echo() {
+        let schema = RootNode::new(Query, EmptyMutation::<()>::new(), EmptySubscription::<()>::new());
+        let (res, errors) = juniper::execute_sync(r#"{ echo(value: "foo") }"#, None, &schema, &Variables::new(), &()).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(res, graphql_value!({ "echo": "foo" }));
+    }
+}
@@ -2,197 +2,516 @@
 //!
 //! Internal implemation of interned byte array.
 //! All public types are built on it.
-//! As a optimization, small arrays are stored inline, without heap allocation.
-//! Max length of inlined array is `size_of::<usize>() * 2 - 1`.
+//!
+//! The handles are packed into two words by the `repr` module by default.
+//! With the `forbid-unsafe` feature they're built on plain `Rc<[u8]>` by the `safe` module
+//! instead, and the pool around them is the same for both.
+//!
+//! Auxiliary indices of the pool, like its mirror and the insertion order,
+//! live in their own modules and follow the pool's entries through their `Hook`.
 
-use std::mem;
 use std::collections::HashSet;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::cell::RefCell;
-use std::u8;
-use std::slice;
-use std::ops::Drop;
-use std::cmp::{PartialOrd, Ordering};
+use std::hash::{Hasher, BuildHasher};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::sync::Mutex;
+use std::ops::{Deref, DerefMut};
+use std::cmp::{PartialOrd, Ord, Ordering};
 use std::fmt;
-use std::ptr::NonNull;
-
-#[cfg(target_endian = "little")]
-#[repr(C)]
-#[derive(PartialEq, Eq, Ord)]
-pub struct Handle {
-    ptr: NonNull<u8>,
-    len: usize,
+
+use side;
+use pool::PoolConfig;
+
+#[cfg_attr(feature = "forbid-unsafe", path = "handle/safe.rs")]
+mod repr;
+mod mirror;
+mod order;
+mod epochs;
+mod pins;
+mod classes;
+mod watch;
+
+pub use self::repr::{Handle, RawEntry, INLINE_MAX_LEN, HEAP_MAX_LEN, interns_inline};
+pub use self::repr::{to_str, to_cstr, to_os_str};
+pub use self::mirror::{Mirror, mirror};
+pub use self::order::{set_insertion_order, entries};
+pub use self::epochs::{begin_epoch, epoch_len, end_epoch};
+pub use self::pins::purge_unused;
+pub use self::classes::{ClassStats, register_class, set_class_budget, class_stats};
+pub use self::watch::{SharedCallback, Thresholds, Threshold, Saturation};
+pub use self::watch::{set_saturation_warning, clear_saturation_warning};
+
+thread_local! {
+    static POOL: RefCell<Pool> = RefCell::new(new_pool());
 }
 
-#[cfg(target_endian = "big")]
-#[repr(C)]
-#[derive(PartialEq, Eq, Ord)]
-pub struct Handle {
-    len: usize,
-    ptr: NonNull<u8>,
+/// Auxiliary index of the pool, following its insertions and removals
+///
+/// The hooks are called while the pool is borrowed, so they must not intern or drop handles.
+struct Hook {
+    insert: fn(&Pool, &Rc<[u8]>),
+    remove: fn(&Pool, &Rc<[u8]>),
+    /// Check the index against the addresses of the pool entries.
+    check: fn(&HashSet<usize>) -> Result<(), String>,
 }
 
-const INLINE_MASK: usize = 1;
-const INLINE_TRUE: usize = 1;
-const INLINE_FALSE: usize = 0;
+const HOOKS: &[Hook] = &[pins::HOOK, mirror::HOOK, order::HOOK, epochs::HOOK, classes::HOOK, watch::HOOK];
 
-const INLINE_ARRAY_SIZE: usize = mem::size_of::<Handle>();
-const INLINE_MAX_LEN: usize = INLINE_ARRAY_SIZE - 1;
+#[derive(Default)]
+struct Pool(HashSet<Rc<[u8]>, PoolHasher>);
 
-#[cfg(target_endian = "little")]
-const INLINE_META: usize = 0;
-#[cfg(target_endian = "little")]
-const INLINE_START: usize = 1;
+impl Deref for Pool {
+    type Target = HashSet<Rc<[u8]>, PoolHasher>;
 
-#[cfg(target_endian = "big")]
-const INLINE_META: usize = 7;
-#[cfg(target_endian = "big")]
-const INLINE_START: usize = 0;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
 
-thread_local! {
-    static POOL: RefCell<HashSet<Rc<[u8]>>> = Default::default();
+impl DerefMut for Pool {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        mirror::close();
+    }
+}
+
+/// Hasher of the pools, randomly keyed unless seeded by the `PoolConfig`
+#[derive(Clone, Debug)]
+pub enum PoolHasher {
+    Random(RandomState),
+    Seeded(u64),
+}
+
+impl Default for PoolHasher {
+    fn default() -> Self {
+        PoolHasher::Random(RandomState::new())
+    }
+}
+
+impl BuildHasher for PoolHasher {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        match *self {
+            PoolHasher::Random(ref state) => state.build_hasher(),
+            PoolHasher::Seeded(seed) => {
+                let mut hasher = DefaultHasher::new();
+                hasher.write_u64(seed);
+                hasher
+            }
+        }
+    }
+}
+
+/// Configuration applied to the pools of the threads at their first use
+static DEFAULT_CONFIG: Mutex<Option<PoolConfig>> = Mutex::new(None);
+
+fn default_config() -> Option<PoolConfig> {
+    DEFAULT_CONFIG.lock().unwrap_or_else(|err| err.into_inner()).clone()
+}
+
+fn new_pool() -> Pool {
+    match default_config() {
+        Some(config) => Pool(HashSet::with_capacity_and_hasher(config.capacity.unwrap_or(0), config.hasher())),
+        None => Pool::default(),
+    }
+}
+
+pub fn set_default_config(config: PoolConfig) {
+    *DEFAULT_CONFIG.lock().unwrap_or_else(|err| err.into_inner()) = Some(config);
+}
+
+/// Apply the options set in the configuration to this thread's pool,
+/// rehashing its entries if the hasher or the capacity is set.
+pub fn configure(config: &PoolConfig) {
+    if config.capacity.is_some() || config.seed.is_some() {
+        POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            let capacity = config.capacity.unwrap_or_else(|| pool.capacity()).max(pool.len());
+            let hasher = match config.seed {
+                Some(seed) => PoolHasher::Seeded(seed),
+                None => pool.hasher().clone(),
+            };
+            let mut fresh = HashSet::with_capacity_and_hasher(capacity, hasher);
+            fresh.extend(pool.drain());
+            pool.0 = fresh;
+        });
+    }
+
+    if let Some(enabled) = config.insertion_order {
+        set_insertion_order(enabled);
+    }
+    for &(name, classify) in &config.classes {
+        register_class(name, classify);
+    }
+    for &(name, budget) in &config.budgets {
+        set_class_budget(name, budget);
+    }
+    if let Some((thresholds, ref callback)) = config.saturation {
+        set_saturation_warning(thresholds, watch::local_callback(Arc::clone(callback)));
+    }
+}
+
+/// Number of the entries of this thread's pool.
+pub fn pool_len() -> usize {
+    POOL.with(|pool| pool.borrow().len())
+}
+
+/// Number of the entries this thread's pool can hold without reallocating.
+pub fn pool_capacity() -> usize {
+    POOL.with(|pool| pool.borrow().capacity())
+}
+
+/// Shrink the capacity of this thread's pool and its pins as much as possible.
+pub fn shrink_pool() {
+    POOL.with(|pool| pool.borrow_mut().shrink_to_fit());
+    pins::shrink();
+}
+
+// Events are emitted after the pool is released, as subscribers may intern strings.
+
+#[cfg(feature = "tracing")]
+#[inline]
+fn trace_intern(len: usize, hit: bool) {
+    tracing::trace!(target: "internship::pool", len, hit, "intern");
+}
+
+#[cfg(feature = "tracing")]
+#[inline]
+fn trace_extend(count: usize, misses: usize) {
+    tracing::trace!(target: "internship::pool", count, misses, "extend");
+}
+
+#[cfg(feature = "tracing")]
+#[inline]
+fn trace_remove(len: usize) {
+    tracing::trace!(target: "internship::pool", len, "remove");
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+fn trace_intern(_len: usize, _hit: bool) {}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+fn trace_extend(_count: usize, _misses: usize) {}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+fn trace_remove(_len: usize) {}
+
+/// Whether this thread's pool has an entry of the contents.
+#[cfg(feature = "differential")]
+pub fn pool_contains(slice: &[u8]) -> bool {
+    POOL.with(|pool| pool.borrow().contains(slice))
+}
+
+/// Check the consistency of this thread's pool and its auxiliary indices.
+pub fn check_pool() -> Result<(), String> {
+    POOL.with(|pool| {
+        let pool = pool.borrow();
+
+        for rc in pool.iter() {
+            let addr = rc.as_ptr().addr();
+
+            if interns_inline(rc.len()) {
+                return Err(format!("entry {:#x} of {} bytes should be inlined", addr, rc.len()));
+            }
+            // Entries are removed when their last handle is dropped.
+            if Rc::strong_count(rc) < 2 {
+                return Err(format!("entry {:#x} has no handles", addr));
+            }
+            // Also catches the duplicated contents, as only one of them can be found.
+            match pool.get(&**rc) {
+                Some(found) if Rc::ptr_eq(found, rc) => {}
+                _ => return Err(format!("entry {:#x} is not found by its contents", addr)),
+            }
+        }
+
+        let addrs: HashSet<usize> = pool.iter().map(|rc| rc.as_ptr().addr()).collect();
+        HOOKS.iter().try_for_each(|hook| (hook.check)(&addrs))
+    })
 }
 
 impl Handle {
     #[inline]
     pub fn new(slice: &[u8]) -> Self {
-        if slice.len() > INLINE_MAX_LEN {
-            Handle::new_heap(slice)
-        } else {
+        if interns_inline(slice.len()) {
             Handle::new_inline(slice)
+        } else {
+            Handle::new_heap(slice)
         }
     }
 
     #[inline]
     fn new_heap(slice: &[u8]) -> Self {
-        let rc = POOL.with(|pool| {
-            let cached = pool.borrow().get(slice).cloned();
+        let interned = POOL.try_with(|pool| {
+            let cached = pool.try_borrow().ok()?.get(slice).cloned();
 
             match cached {
-                Some(rc) => rc,
+                Some(rc) => Some((rc, true)),
                 None => {
-                    let rc = Rc::from(slice);
-                    pool.borrow_mut().insert(Rc::clone(&rc));
-                    rc
+                    let mut pool = pool.try_borrow_mut().ok()?;
+                    Some((Handle::insert_heap(&mut pool, slice), false))
                 }
             }
         });
 
-        let len = rc.len();
-        let ptr = NonNull::new(rc.as_ptr() as *mut u8).unwrap();
-
-        let rc_ptr = Rc::into_raw(rc);
-        debug_assert_eq!(rc_ptr as *const u8, ptr.as_ptr(),
-            "slice ptr does not match with ptr from Rc::into_raw");
-        // At the time this code is written, RcBox (heap-allocated part of Rc) contains
-        // 2 usize fields to store strong/weak ref counter, which makes it to aligned for them.
-        // This is not guaranteed by spec though, It's very unlikely to change to be unaligned.
-        debug_assert_eq!(ptr.as_ptr() as usize & INLINE_MASK, INLINE_FALSE,
-            "It seems like Rc ptr is not alligned with at least 2 bytes, {:p}", ptr);
+        let (rc, hit) = match interned {
+            Ok(Some(interned)) => interned,
+            _ => return Handle::new_unpooled(slice),
+        };
 
-        Handle { ptr, len }
+        trace_intern(slice.len(), hit);
+        watch::fire();
+        Handle::from_rc(rc)
     }
 
-    #[inline]
-    fn new_inline(slice: &[u8]) -> Self {
-        debug_assert!(slice.len() <= INLINE_MAX_LEN, "Size is larger then INLINE_MAX_LEN");
-        debug_assert!(slice.len() <= (u8::MAX >> 1) as usize, "What a large, really?");
-
-        let length = slice.len() as u8;
-        let meta = length << 1 | INLINE_TRUE as u8;
+    /// Heap handle not stored in the pool, for when the pool is destroyed or already borrowed,
+    /// e.g. interning from the destructor of another thread local or from the pool's callbacks.
+    #[cold]
+    fn new_unpooled(slice: &[u8]) -> Self {
+        assert!(slice.len() <= HEAP_MAX_LEN, "Size is larger then HEAP_MAX_LEN");
+        Handle::from_unpooled_rc(Rc::from(slice))
+    }
 
-        let mut array = [0 as u8; INLINE_ARRAY_SIZE];
-        array[INLINE_META] = meta;
-        array[INLINE_START..INLINE_START + slice.len()].copy_from_slice(slice);
+    #[cfg(test)]
+    pub(crate) fn unpooled(slice: &[u8]) -> Self {
+        Handle::new_unpooled(slice)
+    }
 
-        unsafe {
-            mem::transmute(array)
-        }
+    /// Whether the pool of this thread can be used, i.e. it's neither destroyed nor borrowed.
+    pub fn is_pool_available() -> bool {
+        POOL.try_with(|pool| pool.try_borrow_mut().is_ok()).unwrap_or(false)
     }
 
-    #[inline]
-    pub fn is_inline(&self) -> bool {
-        match self.ptr.as_ptr() as usize & INLINE_MASK {
-            INLINE_TRUE => true,
-            INLINE_FALSE => false,
-            _ => unreachable!(),
+    /// Like `new`, but fails instead of inserting a new entry over its class's budget.
+    pub fn try_new(slice: &[u8]) -> Result<Self, Option<&'static str>> {
+        if interns_inline(slice.len()) {
+            return Ok(Handle::new_inline(slice));
         }
+
+        let rc = POOL.try_with(|pool| -> Result<_, Option<&'static str>> {
+            let cached = match pool.try_borrow() {
+                Ok(pool) => pool.get(slice).cloned(),
+                Err(_) => return Ok(None),
+            };
+
+            match cached {
+                Some(rc) => Ok(Some((rc, true))),
+                None => {
+                    let mut pool = match pool.try_borrow_mut() {
+                        Ok(pool) => pool,
+                        Err(_) => return Ok(None),
+                    };
+                    classes::admits(slice)?;
+                    Ok(Some((Handle::insert_heap(&mut pool, slice), false)))
+                }
+            }
+        });
+
+        // Budgets are kept by the pool, so they can't be checked without it.
+        let (rc, hit) = match rc {
+            Ok(rc) => match rc? {
+                Some(rc) => rc,
+                None => return Ok(Handle::new_unpooled(slice)),
+            },
+            Err(_) => return Ok(Handle::new_unpooled(slice)),
+        };
+        trace_intern(slice.len(), hit);
+        watch::fire();
+        Ok(Handle::from_rc(rc))
     }
 
-    #[inline]
-    pub fn get(&self) -> &[u8] {
-        if self.is_inline() {
-            self.get_inline()
-        } else {
-            self.get_heap()
+    fn insert_heap(pool: &mut Pool, slice: &[u8]) -> Rc<[u8]> {
+        assert!(slice.len() <= HEAP_MAX_LEN, "Size is larger then HEAP_MAX_LEN");
+        let rc = Rc::from(slice);
+        pool.insert(Rc::clone(&rc));
+        for hook in HOOKS {
+            (hook.insert)(pool, &rc);
         }
+        rc
     }
 
-    #[inline]
-    fn get_heap<'a>(&'a self) -> &'a [u8] {
-        unsafe {
-            slice::from_raw_parts(self.ptr.as_ptr(), self.len)
-        }
+    /// Intern the slices at once, reserving the pool beforehand and borrowing it only once.
+    /// Adjacent duplicates share the handle of the first one, which makes sorted input cheaper.
+    ///
+    /// The input is collected before borrowing the pool, so the iterator may intern too.
+    pub fn extend_sorted<I, T>(iter: I) -> Vec<Handle>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let owned: Vec<T> = iter.into_iter().collect();
+        let slices: Vec<&[u8]> = owned.iter().map(AsRef::as_ref).collect();
+        let mut handles: Vec<Handle> = Vec::with_capacity(slices.len());
+        let mut misses = 0;
+
+        let _ = POOL.try_with(|pool| {
+            let mut pool = match pool.try_borrow_mut() {
+                Ok(pool) => pool,
+                Err(_) => return,
+            };
+            pool.reserve(slices.len());
+
+            for &slice in &slices {
+                if let Some(prev) = handles.last().filter(|prev| prev.get() == slice) {
+                    let prev = prev.clone();
+                    handles.push(prev);
+                    continue;
+                }
+
+                if interns_inline(slice.len()) {
+                    handles.push(Handle::new_inline(slice));
+                    continue;
+                }
+
+                let rc = match pool.get(slice) {
+                    Some(rc) => Rc::clone(rc),
+                    None => {
+                        misses += 1;
+                        Handle::insert_heap(&mut pool, slice)
+                    }
+                };
+                handles.push(Handle::from_rc(rc));
+            }
+        });
+
+        // Remaining ones are not interned as the pool is unavailable.
+        let done = handles.len();
+        handles.extend(slices[done..].iter().map(|slice| Handle::new(slice)));
+
+        trace_extend(handles.len(), misses);
+        watch::fire();
+        handles
     }
 
+    /// Raw words of the handle without its tag. Handles from same thread have same id
+    /// if and only if their contents are same, unless either is unpooled.
+    ///
+    /// Unpooled handles keep the id of their own allocation for their whole lifetime,
+    /// which differs from the pooled handles of the same contents.
+    /// So do the constants of the `forbid-unsafe` build, which don't refer to the pool.
     #[inline]
-    fn get_inline<'a>(&'a self) -> &'a [u8] {
-        let bytes: &'a [u8; INLINE_ARRAY_SIZE] = unsafe {
-            mem::transmute(self)
-        };
+    pub fn id(&self) -> (usize, usize) {
+        self.raw_id()
+    }
 
-        let len = bytes[INLINE_META] as usize >> 1;
-        &bytes[INLINE_START..INLINE_START + len]
+    /// Handle of the pool entry of the contents, or `None` if the id is already unique to them.
+    /// Keys by `id()` take it instead, so they meet the pooled handles of the same contents.
+    #[cold]
+    pub fn to_pooled(&self) -> Option<Handle> {
+        if !self.has_unique_id() {
+            Some(Handle::new(self.get()))
+        } else {
+            None
+        }
     }
 
-    #[inline]
-    fn get_rc(&self) -> mem::ManuallyDrop<Rc<[u8]>> {
-        unsafe {
-            let slice_ptr = slice::from_raw_parts(self.ptr.as_ptr(), self.len) as *const [u8];
-            mem::ManuallyDrop::new(Rc::from_raw(slice_ptr))
+    /// Number of the references to the pool entry, including the pool's and the pin's.
+    /// It's 0 if inlined.
+    pub fn ref_count(&self) -> usize {
+        if self.is_inline() {
+            0
+        } else {
+            Rc::strong_count(&self.get_rc())
         }
     }
-}
 
-impl Drop for Handle {
-    #[inline]
-    fn drop(&mut self) {
+    /// Check the representation invariants of the handle against this thread's pool.
+    #[cfg(feature = "test-utils")]
+    pub fn check(&self) -> Result<(), String> {
+        let len = self.get().len();
+
         if self.is_inline() {
-            return;
+            if len > INLINE_MAX_LEN {
+                return Err(format!("inline handle is {} bytes long", len));
+            }
+            return Ok(());
         }
 
-        let mut rc = self.get_rc();
+        if interns_inline(len) {
+            return Err(format!("heap handle of {} bytes should be inlined", len));
+        }
 
-        if Rc::strong_count(&rc) == 2 {
-            POOL.with(|pool| {
-                pool.borrow_mut().remove(&*rc);
-            });
+        if self.is_unpooled() {
+            return Ok(());
         }
 
-        unsafe {
-            mem::ManuallyDrop::drop(&mut rc);
+        let count = Rc::strong_count(&self.get_rc());
+        if count < 2 {
+            return Err(format!("heap handle has refcount {} without the pool's", count));
         }
+
+        POOL.with(|pool| match pool.borrow().get(self.get()) {
+            Some(rc) if Rc::ptr_eq(rc, &self.get_rc()) => Ok(()),
+            Some(_) => Err("pool has another entry of the same contents".into()),
+            None => Err("heap handle is not in the pool".into()),
+        })
     }
-}
 
-impl Clone for Handle {
-    #[inline]
-    fn clone(&self) -> Self {
-        if !self.is_inline() {
-            let rc = self.get_rc();
-            mem::forget(rc.clone());
-        }
 
-        unsafe {
-            mem::transmute_copy(self)
+    /// Remove the pool entry if `self` is its last handle, or drop the side data
+    /// of the last unpooled handle. Called before the handle gives back its refcount.
+    fn release(&self, rc: &Rc<[u8]>) {
+        if self.is_unpooled() {
+            if Rc::strong_count(rc) == 1 {
+                side::on_remove(self.id());
+            }
+        } else if Rc::strong_count(rc) == 2 {
+            // The pool may be destroyed or borrowed, then the entry is left to it.
+            let removed = POOL.try_with(|pool| {
+                let mut pool = match pool.try_borrow_mut() {
+                    Ok(pool) => pool,
+                    Err(_) => return false,
+                };
+                if !pool.remove(&**rc) {
+                    return false;
+                }
+                for hook in HOOKS {
+                    (hook.remove)(&pool, rc);
+                }
+                true
+            }).unwrap_or(false);
+
+            if removed {
+                side::on_remove(self.id());
+                trace_remove(rc.len());
+            }
         }
     }
 }
 
+impl PartialEq for Handle {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+            || !(self.has_unique_id() && other.has_unique_id()) && self.get() == other.get()
+    }
+}
+
+impl Eq for Handle {}
+
 impl PartialOrd for Handle {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        PartialOrd::partial_cmp(self.get(), other.get())
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Handle {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        Ord::cmp(self.get(), other.get())
     }
 }
 
@@ -211,6 +530,7 @@ impl fmt::Debug for Handle {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::repr::TAG_MAX;
 
     #[test]
     fn test_eq_short() {
@@ -228,10 +548,10 @@ mod tests {
 
     #[test]
     fn test_pool_and_rc_count() {
-        use std::mem::replace;
+        use std::mem::{replace, take};
 
         let prev_pool = POOL.with(|pool| {
-            replace(&mut *pool.borrow_mut(), Default::default())
+            take(&mut *pool.borrow_mut())
         });
 
         let pool_size = || POOL.with(|pool| {
@@ -242,7 +562,9 @@ mod tests {
         let data2 = &b"sed do eiusmod tempor incididunt ut labore et dolore magna aliqua"[..];
 
         assert_eq!(pool_size(), 0);
-        let _b0 = Handle::new(&b"foo"[..]);
+        let b0 = Handle::new(&b"foo"[..]);
+        assert_eq!(pool_size(), !interns_inline(3) as usize);
+        drop(b0);
         assert_eq!(pool_size(), 0);
         let b1 = Handle::new(data1);
         assert_eq!(pool_size(), 1);
@@ -264,7 +586,164 @@ mod tests {
         assert_eq!(pool_size(), 0);
 
         POOL.with(|pool| {
-            replace(&mut *pool.borrow_mut(), prev_pool);
+            drop(replace(&mut *pool.borrow_mut(), prev_pool));
         })
     }
+
+    #[test]
+    fn test_tag() {
+        for &data in &[&b"kw"[..], &b"a tagged string long enough to be pooled"[..]] {
+            let plain = Handle::new(data);
+            let mut tagged = Handle::new(data);
+            tagged.set_tag(TAG_MAX);
+
+            assert_eq!(tagged.tag(), TAG_MAX);
+            assert_eq!(tagged.clone().tag(), TAG_MAX);
+            assert_eq!(plain.tag(), 0);
+            assert_eq!(tagged.get(), data);
+            assert_eq!(tagged.id(), plain.id());
+            assert_eq!(tagged, plain);
+
+            drop(plain);
+            tagged.set_tag(1);
+            assert_eq!(tagged.tag(), 1);
+            assert_eq!(tagged.pool_id().is_some(), !tagged.is_inline());
+        }
+    }
+
+    #[test]
+    fn test_unpooled_fallback() {
+        let data = &b"interned while the pool is borrowed by someone else"[..];
+        let (unpooled, available) = POOL.with(|pool| {
+            let _borrowed = pool.borrow_mut();
+            (Handle::new(data), Handle::is_pool_available())
+        });
+        let pooled = Handle::new(data);
+
+        assert!(!available);
+        assert!(unpooled.is_unpooled());
+        assert_eq!(unpooled.pool_id(), None);
+        assert_eq!(unpooled, pooled);
+        assert_ne!(unpooled.id(), pooled.id());
+        assert_eq!(unpooled.get(), data);
+        assert_eq!(Rc::strong_count(&unpooled.get_rc()), 1);
+
+        drop(pooled);
+        drop(unpooled.clone());
+        assert!(POOL.with(|pool| pool.borrow().get(data).is_none()));
+    }
+
+    #[test]
+    fn test_extend_sorted_reentrant() {
+        let srcs = ["first string long enough to be pooled", "second string long enough to be pooled"];
+        let mut seen = Vec::new();
+
+        let handles = Handle::extend_sorted(srcs.iter().inspect(|src| {
+            // Interning from the iterator gets pooled handles too.
+            seen.push(Handle::new(src.as_bytes()));
+        }));
+
+        assert!(seen.iter().chain(handles.iter()).all(|handle| !handle.is_unpooled()));
+        assert_eq!(seen, handles);
+        assert_eq!(seen[1].id(), handles[1].id());
+    }
+
+    #[test]
+    fn test_unpooled_id() {
+        let data = &b"unpooled handle keeping the id of its allocation"[..];
+        let unpooled = Handle::unpooled(data);
+        let id = unpooled.id();
+
+        let pooled = Handle::new(data);
+        assert_eq!(unpooled.id(), id);
+        assert_ne!(pooled.id(), id);
+        assert_eq!(pool_len(), 1);
+        POOL.with(|pool| {
+            let _borrowed = pool.borrow_mut();
+            assert_eq!(unpooled.id(), id);
+        });
+
+        let repooled = unpooled.to_pooled().unwrap();
+        assert_eq!(repooled.id(), pooled.id());
+        assert!(pooled.to_pooled().is_none());
+
+        drop(pooled);
+        drop(repooled);
+        assert_eq!(pool_len(), 0);
+        drop(unpooled);
+        check_pool().unwrap();
+    }
+
+    #[test]
+    fn test_pin_and_leak() {
+        let data = &b"pinned and then leaked, so it can't be unpinned"[..];
+        let handle = Handle::new(data);
+
+        handle.pin();
+        handle.pin();
+        assert_eq!(Rc::strong_count(&handle.get_rc()), 3);
+        assert!(handle.as_static().is_none());
+
+        assert_eq!(handle.leak(), Some(data));
+        assert_eq!(Rc::strong_count(&handle.get_rc()), 3);
+        assert!(!handle.unpin());
+        assert_eq!(Handle::new(data).as_static(), Some(data));
+
+        assert!(Handle::new_inline(b"inline").leak().is_none());
+    }
+
+    #[test]
+    fn test_check_pool() {
+        let _pinned = Handle::new(&b"checked string long enough to be pooled"[..]);
+        _pinned.pin();
+        set_insertion_order(true);
+        assert_eq!(check_pool(), Ok(()));
+
+        let orphan: Rc<[u8]> = Rc::from(&b"entry without any handles to it"[..]);
+        POOL.with(|pool| pool.borrow_mut().insert(Rc::clone(&orphan)));
+        assert!(check_pool().is_err());
+
+        POOL.with(|pool| pool.borrow_mut().remove(&*orphan));
+        set_insertion_order(false);
+        assert!(_pinned.unpin());
+        assert_eq!(check_pool(), Ok(()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_trace_events() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tracing::{subscriber, Event, Metadata, Subscriber};
+        use tracing::span::{Attributes, Id, Record};
+
+        // Counts the events of the pool.
+        struct Counter(Arc<AtomicUsize>);
+
+        impl Subscriber for Counter {
+            fn enabled(&self, metadata: &Metadata) -> bool {
+                metadata.target() == "internship::pool"
+            }
+            fn event(&self, _: &Event) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+            fn new_span(&self, _: &Attributes) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        subscriber::with_default(Counter(Arc::clone(&count)), || {
+            let data = &b"string traced on interning and removal"[..];
+            let a = Handle::new(data);
+            let b = Handle::new(data);
+            drop((a, b));
+        });
+
+        // Miss, hit and removal.
+        assert_eq!(count.load(Ordering::Relaxed), 3);
+    }
 }
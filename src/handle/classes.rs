@@ -0,0 +1,201 @@
+//! Classes of the pool entries
+//!
+//! Auxiliary index of the pool entries by the registered classifiers,
+//! which keeps the usage of each class and limits it with the budgets.
+
+use std::collections::{HashSet, HashMap};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use super::{Hook, Pool, POOL, default_config};
+
+thread_local! {
+    static CLASSES: RefCell<Option<Classes>> = RefCell::new(new_classes());
+}
+
+pub(super) const HOOK: Hook = Hook { insert, remove, check };
+
+/// Usage of a class of the pool entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassStats {
+    /// Name of the class, or `None` for the entries no classifier matched.
+    pub name: Option<&'static str>,
+    pub entries: usize,
+    /// Total length of the entries.
+    pub bytes: usize,
+    /// Limit of `bytes` for the new entries interned with `try_new`.
+    pub budget: Option<usize>,
+}
+
+struct Class {
+    classify: fn(&[u8]) -> bool,
+    stats: ClassStats,
+}
+
+/// Auxiliary index of the pool entries by their classes
+struct Classes {
+    // Index 0 is for the unclassified entries.
+    classes: Vec<Class>,
+    of: HashMap<usize, usize>,
+}
+
+impl Classes {
+    fn new() -> Self {
+        Classes {
+            classes: vec![Class {
+                classify: |_| false,
+                stats: ClassStats { name: None, entries: 0, bytes: 0, budget: None },
+            }],
+            of: HashMap::new(),
+        }
+    }
+
+    fn register(&mut self, name: &'static str, classify: fn(&[u8]) -> bool) {
+        match self.classes.iter_mut().find(|class| class.stats.name == Some(name)) {
+            Some(class) => class.classify = classify,
+            None => self.classes.push(Class {
+                classify,
+                stats: ClassStats { name: Some(name), entries: 0, bytes: 0, budget: None },
+            }),
+        }
+    }
+
+    fn set_budget(&mut self, name: Option<&str>, budget: Option<usize>) -> bool {
+        match self.classes.iter_mut().find(|class| class.stats.name == name) {
+            Some(class) => {
+                class.stats.budget = budget;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn classify(&self, slice: &[u8]) -> usize {
+        self.classes.iter().skip(1)
+            .position(|class| (class.classify)(slice))
+            .map_or(0, |idx| idx + 1)
+    }
+
+    fn insert(&mut self, rc: &Rc<[u8]>) {
+        let idx = self.classify(rc);
+        let stats = &mut self.classes[idx].stats;
+        stats.entries += 1;
+        stats.bytes += rc.len();
+        self.of.insert(rc.as_ptr().addr(), idx);
+    }
+
+    fn remove(&mut self, rc: &Rc<[u8]>) {
+        if let Some(idx) = self.of.remove(&(rc.as_ptr().addr())) {
+            let stats = &mut self.classes[idx].stats;
+            stats.entries -= 1;
+            stats.bytes -= rc.len();
+        }
+    }
+
+    /// Whether a new entry of the contents fits in its class's budget.
+    fn admits(&self, slice: &[u8]) -> Result<(), Option<&'static str>> {
+        let stats = &self.classes[self.classify(slice)].stats;
+
+        match stats.budget {
+            Some(budget) if stats.bytes + slice.len() > budget => Err(stats.name),
+            _ => Ok(()),
+        }
+    }
+}
+
+// The classes are initialized by the first insertion at the latest,
+// so they start from the empty pool.
+fn new_classes() -> Option<Classes> {
+    let config = default_config()?;
+    if config.classes.is_empty() {
+        return None;
+    }
+
+    let mut classes = Classes::new();
+    for &(name, classify) in &config.classes {
+        classes.register(name, classify);
+    }
+    for &(name, budget) in &config.budgets {
+        classes.set_budget(name, budget);
+    }
+
+    Some(classes)
+}
+
+#[inline]
+fn update_classes<F: FnOnce(&mut Classes)>(f: F) {
+    let _ = CLASSES.try_with(|classes| {
+        if let Some(ref mut classes) = *classes.borrow_mut() {
+            f(classes);
+        }
+    });
+}
+
+/// Whether a new entry of the contents fits in its class's budget, if any classes are registered.
+pub(super) fn admits(slice: &[u8]) -> Result<(), Option<&'static str>> {
+    CLASSES.with(|classes| match *classes.borrow() {
+        Some(ref classes) => classes.admits(slice),
+        None => Ok(()),
+    })
+}
+
+/// Register the classifier of the class, replacing the previous one of the same name.
+/// Classifiers are tried in registration order, and every entry is reclassified.
+pub fn register_class(name: &'static str, classify: fn(&[u8]) -> bool) {
+    CLASSES.with(|classes| {
+        let mut classes = classes.borrow_mut();
+        let classes = classes.get_or_insert_with(Classes::new);
+        classes.register(name, classify);
+
+        classes.of.clear();
+        for class in &mut classes.classes {
+            class.stats.entries = 0;
+            class.stats.bytes = 0;
+        }
+
+        POOL.with(|pool| {
+            for rc in pool.borrow().iter() {
+                classes.insert(rc);
+            }
+        });
+    })
+}
+
+/// Set the budget of the class, or of the unclassified entries if `name` is `None`.
+/// Returns `false` if no such class is registered.
+pub fn set_class_budget(name: Option<&str>, budget: Option<usize>) -> bool {
+    CLASSES.with(|classes| {
+        classes.borrow_mut().as_mut().is_some_and(|classes| classes.set_budget(name, budget))
+    })
+}
+
+/// Usage of every registered class, with the unclassified entries first.
+pub fn class_stats() -> Vec<ClassStats> {
+    CLASSES.with(|classes| match *classes.borrow() {
+        Some(ref classes) => classes.classes.iter().map(|class| class.stats.clone()).collect(),
+        None => Vec::new(),
+    })
+}
+
+fn insert(_pool: &Pool, rc: &Rc<[u8]>) {
+    update_classes(|classes| classes.insert(rc));
+}
+
+fn remove(_pool: &Pool, rc: &Rc<[u8]>) {
+    update_classes(|classes| classes.remove(rc));
+}
+
+fn check(addrs: &HashSet<usize>) -> Result<(), String> {
+    let classified = CLASSES.with(|classes| classes.borrow().as_ref().map(|classes| {
+        let entries: usize = classes.classes.iter().map(|class| class.stats.entries).sum();
+        (entries, classes.of.keys().cloned().collect::<Vec<_>>())
+    }));
+
+    if let Some((len, classified)) = classified {
+        if len != addrs.len() || classified.len() != addrs.len() || !classified.iter().all(|addr| addrs.contains(addr)) {
+            return Err("class index doesn't match the pool".into());
+        }
+    }
+
+    Ok(())
+}
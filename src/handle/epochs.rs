@@ -0,0 +1,99 @@
+//! Epochs of the pool
+//!
+//! Each epoch tracks the entries inserted since it began, until it ends.
+//! Epochs are nested, and ending one ends its inner ones too.
+
+use std::collections::{HashSet, HashMap};
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+
+use super::{Handle, Hook, Pool, RawEntry, POOL};
+
+thread_local! {
+    // Entries tracked by each epoch, with its id.
+    static EPOCHS: RefCell<Vec<(u64, HashMap<usize, RawEntry>)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_EPOCH: Cell<u64> = const { Cell::new(0) };
+}
+
+pub(super) const HOOK: Hook = Hook { insert, remove, check };
+
+#[inline]
+fn update_epochs<F: FnMut(&mut HashMap<usize, RawEntry>)>(mut f: F) {
+    let _ = EPOCHS.try_with(|epochs| {
+        if let Ok(mut epochs) = epochs.try_borrow_mut() {
+            for &mut (_, ref mut epoch) in epochs.iter_mut() {
+                f(epoch);
+            }
+        }
+    });
+}
+
+/// Start tracking the entries inserted from now. Returns the id of the epoch,
+/// which is unique within this thread.
+pub fn begin_epoch() -> u64 {
+    let id = NEXT_EPOCH.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+
+    EPOCHS.with(|epochs| epochs.borrow_mut().push((id, HashMap::new())));
+    id
+}
+
+/// Number of the entries inserted in the epoch and still in the pool,
+/// or 0 if it's already ended.
+pub fn epoch_len(id: u64) -> usize {
+    EPOCHS.with(|epochs| {
+        epochs.borrow().iter()
+            .find(|&&(epoch_id, _)| epoch_id == id)
+            .map_or(0, |(_, epoch)| epoch.len())
+    })
+}
+
+/// Stop tracking the epoch and its inner ones, returning its entries still in the pool.
+/// Returns nothing if it's already ended, e.g. by ending its outer epoch.
+pub fn end_epoch(id: u64) -> Vec<Handle> {
+    let ended = EPOCHS.try_with(|epochs| {
+        let mut epochs = epochs.borrow_mut();
+        let start = epochs.iter().position(|&(epoch_id, _)| epoch_id == id)?;
+        let ended = epochs.drain(start..).next().map(|(_, epoch)| epoch);
+        ended
+    });
+
+    let ended = match ended {
+        Ok(Some(ended)) => ended,
+        _ => return Vec::new(),
+    };
+
+    POOL.with(|pool| {
+        let pool = pool.borrow();
+        ended.values()
+            .filter_map(|entry| pool.get(entry.get()))
+            .map(|rc| Handle::from_rc(Rc::clone(rc)))
+            .collect()
+    })
+}
+
+fn insert(_pool: &Pool, rc: &Rc<[u8]>) {
+    update_epochs(|epoch| {
+        epoch.insert(rc.as_ptr().addr(), RawEntry::new(rc));
+    });
+}
+
+fn remove(_pool: &Pool, rc: &Rc<[u8]>) {
+    update_epochs(|epoch| {
+        epoch.remove(&(rc.as_ptr().addr()));
+    });
+}
+
+fn check(addrs: &HashSet<usize>) -> Result<(), String> {
+    let tracked = EPOCHS.with(|epochs| epochs.borrow().iter()
+        .flat_map(|(_, epoch)| epoch.keys().cloned())
+        .collect::<Vec<_>>());
+
+    match tracked.into_iter().find(|addr| !addrs.contains(addr)) {
+        Some(addr) => Err(format!("entry {:#x} tracked by an epoch is not in the pool", addr)),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,163 @@
+//! Mirror of the pool
+//!
+//! Copy of the thread local pool readable from other threads.
+//! It's maintained only while any reader holds it.
+
+use std::collections::{HashSet, HashMap};
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use std::cell::RefCell;
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+
+use super::{Hook, Pool, RawEntry, POOL};
+
+thread_local! {
+    static MIRROR: RefCell<MirrorSlot> = const { RefCell::new(MirrorSlot(None)) };
+}
+
+pub(super) const HOOK: Hook = Hook { insert, remove, check };
+
+impl PartialEq for RawEntry {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl Eq for RawEntry {}
+
+impl Hash for RawEntry {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.get().hash(hasher)
+    }
+}
+
+impl Borrow<[u8]> for RawEntry {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        self.get()
+    }
+}
+
+/// Copy of the thread local pool readable from other threads
+///
+/// It's emptied and closed when the owner thread's pool is destroyed,
+/// so readers don't see the entries freed with it.
+#[derive(Default)]
+pub struct Mirror {
+    pub entries: HashSet<RawEntry>,
+    pub by_addr: HashMap<usize, RawEntry>,
+    pub closed: bool,
+}
+
+// Closes the mirror when this thread's locals are destroyed, whether before or after the pool.
+struct MirrorSlot(Option<Arc<RwLock<Mirror>>>);
+
+impl MirrorSlot {
+    fn close(&mut self) {
+        if let Some(arc) = self.0.take() {
+            let mut mirror = arc.write().unwrap_or_else(|err| err.into_inner());
+            mirror.entries.clear();
+            mirror.by_addr.clear();
+            mirror.closed = true;
+        }
+    }
+}
+
+impl Drop for MirrorSlot {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl Mirror {
+    fn insert(&mut self, rc: &Rc<[u8]>) {
+        let entry = RawEntry::new(rc);
+        self.entries.insert(entry.clone());
+        self.by_addr.insert(entry.addr(), entry);
+    }
+
+    fn remove(&mut self, rc: &Rc<[u8]>) {
+        self.entries.remove(&**rc);
+        self.by_addr.remove(&(rc.as_ptr().addr()));
+    }
+}
+
+/// Get the mirror of this thread's pool, starting to maintain it if not exist.
+pub fn mirror() -> Arc<RwLock<Mirror>> {
+    MIRROR.with(|mirror| {
+        let mut mirror = mirror.borrow_mut();
+
+        if let Some(ref mirror) = mirror.0 {
+            return Arc::clone(mirror);
+        }
+
+        let mut fresh = Mirror::default();
+        POOL.with(|pool| {
+            for rc in pool.borrow().iter() {
+                fresh.insert(rc);
+            }
+        });
+
+        let fresh = Arc::new(RwLock::new(fresh));
+        mirror.0 = Some(Arc::clone(&fresh));
+        fresh
+    })
+}
+
+/// Close the mirror as the pool is destroyed, so readers of other threads
+/// don't see the entries freed with it.
+pub(super) fn close() {
+    let _ = MIRROR.try_with(|mirror| {
+        if let Ok(mut mirror) = mirror.try_borrow_mut() {
+            mirror.close();
+        }
+    });
+}
+
+/// Apply the change to the mirror if any reader is alive, or stop maintaining it.
+#[inline]
+fn update_mirror<F: FnOnce(&mut Mirror)>(f: F) {
+    let _ = MIRROR.try_with(|mirror| {
+        let mut mirror = mirror.borrow_mut();
+
+        let alive = match mirror.0 {
+            None => return,
+            Some(ref arc) => Arc::strong_count(arc) > 1,
+        };
+
+        if !alive {
+            mirror.0 = None;
+            return;
+        }
+
+        if let Some(ref arc) = mirror.0 {
+            f(&mut arc.write().unwrap_or_else(|err| err.into_inner()));
+        }
+    });
+}
+
+fn insert(_pool: &Pool, rc: &Rc<[u8]>) {
+    update_mirror(|mirror| mirror.insert(rc));
+}
+
+fn remove(_pool: &Pool, rc: &Rc<[u8]>) {
+    update_mirror(|mirror| mirror.remove(rc));
+}
+
+fn check(addrs: &HashSet<usize>) -> Result<(), String> {
+    let mirrored = MIRROR.with(|mirror| mirror.borrow().0.as_ref().map(|mirror| {
+        let mirror = mirror.read().unwrap_or_else(|err| err.into_inner());
+        (mirror.entries.len(), mirror.by_addr.keys().cloned().collect::<Vec<_>>())
+    }));
+
+    if let Some((len, mirrored)) = mirrored {
+        if len != addrs.len() || mirrored.len() != addrs.len() || !mirrored.iter().all(|addr| addrs.contains(addr)) {
+            return Err("mirror doesn't match the pool".into());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+//! Insertion order of the pool
+//!
+//! Auxiliary index of the pool entries by their insertion order, tracked only if enabled.
+
+use std::collections::{HashSet, HashMap, BTreeMap};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use super::{Handle, Hook, Pool, RawEntry, POOL, default_config};
+
+thread_local! {
+    static ORDER: RefCell<Option<Order>> = RefCell::new(new_order());
+}
+
+pub(super) const HOOK: Hook = Hook { insert, remove, check };
+
+/// Auxiliary index of the pool entries by their insertion order
+#[derive(Default)]
+struct Order {
+    next: u64,
+    seqs: HashMap<usize, u64>,
+    entries: BTreeMap<u64, RawEntry>,
+}
+
+impl Order {
+    fn insert(&mut self, rc: &Rc<[u8]>) {
+        let entry = RawEntry::new(rc);
+        self.seqs.insert(entry.addr(), self.next);
+        self.entries.insert(self.next, entry);
+        self.next += 1;
+    }
+
+    fn remove(&mut self, rc: &Rc<[u8]>) {
+        if let Some(seq) = self.seqs.remove(&(rc.as_ptr().addr())) {
+            self.entries.remove(&seq);
+        }
+    }
+}
+
+fn new_order() -> Option<Order> {
+    match default_config() {
+        Some(ref config) if config.insertion_order == Some(true) => Some(Order::default()),
+        _ => None,
+    }
+}
+
+#[inline]
+fn update_order<F: FnOnce(&mut Order)>(f: F) {
+    let _ = ORDER.try_with(|order| {
+        if let Some(ref mut order) = *order.borrow_mut() {
+            f(order);
+        }
+    });
+}
+
+/// Start or stop tracking the insertion order of this thread's pool.
+/// Existing entries are indexed in unspecified order when it starts.
+pub fn set_insertion_order(enabled: bool) {
+    ORDER.with(|order| {
+        let mut order = order.borrow_mut();
+
+        if !enabled {
+            *order = None;
+            return;
+        }
+
+        if order.is_none() {
+            let mut fresh = Order::default();
+            POOL.with(|pool| {
+                for rc in pool.borrow().iter() {
+                    fresh.insert(rc);
+                }
+            });
+            *order = Some(fresh);
+        }
+    })
+}
+
+/// Every entry of this thread's pool, in insertion order if it's tracked.
+pub fn entries() -> Vec<Handle> {
+    POOL.with(|pool| {
+        let pool = pool.borrow();
+
+        ORDER.with(|order| match *order.borrow() {
+            Some(ref order) => order.entries.values()
+                .filter_map(|entry| pool.get(entry.get()))
+                .map(|rc| Handle::from_rc(Rc::clone(rc)))
+                .collect(),
+            None => pool.iter()
+                .map(|rc| Handle::from_rc(Rc::clone(rc)))
+                .collect(),
+        })
+    })
+}
+
+fn insert(_pool: &Pool, rc: &Rc<[u8]>) {
+    update_order(|order| order.insert(rc));
+}
+
+fn remove(_pool: &Pool, rc: &Rc<[u8]>) {
+    update_order(|order| order.remove(rc));
+}
+
+fn check(addrs: &HashSet<usize>) -> Result<(), String> {
+    let ordered = ORDER.with(|order| order.borrow().as_ref().map(|order| {
+        (order.seqs.len(), order.entries.values().map(|entry| entry.addr()).collect::<Vec<_>>())
+    }));
+
+    if let Some((len, ordered)) = ordered {
+        if len != addrs.len() || ordered.len() != addrs.len() || !ordered.iter().all(|addr| addrs.contains(addr)) {
+            return Err("insertion order index doesn't match the pool".into());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+//! Pins of the pool entries
+//!
+//! Pinned entries hold an extra refcount so they stay in the pool without any handles.
+//! Leaked ones are never unpinned, so they can be borrowed for `'static`.
+
+use std::collections::{HashSet, HashMap};
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use super::{Handle, Hook, POOL};
+
+thread_local! {
+    static PINS: RefCell<HashMap<usize, Pin>> = Default::default();
+}
+
+// Pins don't change with the insertions and removals, as pinned entries are never removed.
+pub(super) const HOOK: Hook = Hook {
+    insert: |_, _| {},
+    remove: |_, _| {},
+    check,
+};
+
+/// Pinned entry holds an extra refcount so it stays in the pool without any handles.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pin {
+    Pinned,
+    // Borrowed for `'static`, so it can't be unpinned.
+    Leaked,
+}
+
+/// Unpin the pinned entries no handle refers. Returns the number of the removed entries.
+pub fn purge_unused() -> usize {
+    let unused: Vec<Handle> = POOL.with(|pool| PINS.with(|pins| {
+        let pins = pins.borrow();
+
+        pool.borrow().iter()
+            // Referenced by the pool and the pin only.
+            .filter(|rc| Rc::strong_count(rc) == 2)
+            .filter(|rc| pins.get(&(rc.as_ptr().addr())) == Some(&Pin::Pinned))
+            .map(|rc| Handle::from_rc(Rc::clone(rc)))
+            .collect()
+    }));
+
+    let purged = unused.len();
+    // Dropped after the pool is released, as it removes the entries.
+    for handle in unused {
+        handle.unpin();
+    }
+
+    purged
+}
+
+/// Shrink the capacity of the pins as much as possible.
+pub(super) fn shrink() {
+    PINS.with(|pins| pins.borrow_mut().shrink_to_fit());
+}
+
+/// Leak the entry without taking a refcount, as its refcount is saturated.
+/// Only the packed handles saturate their refcounts.
+#[cfg(not(feature = "forbid-unsafe"))]
+pub(super) fn leak_saturated(addr: usize) {
+    let _ = PINS.try_with(|pins| pins.borrow_mut().insert(addr, Pin::Leaked));
+}
+
+impl Handle {
+    /// Keep the pool entry even if no handle refers it, until `unpin()`. No-op if inlined.
+    pub fn pin(&self) {
+        self.set_pin(Pin::Pinned);
+    }
+
+    /// Release the pin, unless the entry is leaked. Returns whether it was pinned.
+    pub fn unpin(&self) -> bool {
+        let addr = match self.pool_id() {
+            Some(addr) => addr,
+            None => return false,
+        };
+
+        let unpinned = PINS.with(|pins| {
+            let mut pins = pins.borrow_mut();
+
+            match pins.get(&addr) {
+                Some(&Pin::Pinned) => {
+                    pins.remove(&addr);
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        if unpinned {
+            self.release_pin();
+        }
+
+        unpinned
+    }
+
+    /// Whether the pool entry is pinned or leaked, including by the refcount saturation.
+    pub fn is_pinned(&self) -> bool {
+        match self.pool_id() {
+            Some(addr) => PINS.try_with(|pins| pins.borrow().contains_key(&addr)).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Keep the pool entry alive forever and borrow it for `'static`, or `None` if inlined.
+    /// The entry outlives even the pool as its refcount never drops to zero.
+    pub fn leak(&self) -> Option<&'static [u8]> {
+        self.pool_id()?;
+        self.set_pin(Pin::Leaked);
+        Some(self.get_static())
+    }
+
+    /// Contents borrowed for `'static` if the entry is leaked.
+    pub fn as_static(&self) -> Option<&'static [u8]> {
+        let addr = self.pool_id()?;
+
+        match PINS.try_with(|pins| pins.borrow().get(&addr).cloned()) {
+            Ok(Some(Pin::Leaked)) => Some(self.get_static()),
+            _ => None,
+        }
+    }
+
+    fn set_pin(&self, pin: Pin) {
+        let addr = match self.pool_id() {
+            Some(addr) => addr,
+            None => return,
+        };
+
+        PINS.with(|pins| {
+            let mut pins = pins.borrow_mut();
+
+            match pins.get(&addr).cloned() {
+                None => {
+                    pins.insert(addr, pin);
+                    self.retain_pin();
+                }
+                Some(Pin::Pinned) if pin == Pin::Leaked => {
+                    pins.insert(addr, pin);
+                }
+                Some(_) => {}
+            }
+        });
+    }
+}
+
+fn check(addrs: &HashSet<usize>) -> Result<(), String> {
+    let pins = PINS.with(|pins| pins.borrow().keys().cloned().collect::<Vec<_>>());
+
+    match pins.into_iter().find(|addr| !addrs.contains(addr)) {
+        Some(addr) => Err(format!("pinned entry {:#x} is not in the pool", addr)),
+        None => Ok(()),
+    }
+}
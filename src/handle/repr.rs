@@ -0,0 +1,571 @@
+//! Packed representation of the handles
+//!
+//! As a optimization, small arrays are stored inline, without heap allocation.
+//! Max length of inlined array is `size_of::<usize>() * 2 - 1`.
+//!
+//! The layout per pointer width:
+//!
+//! | width | inlined length | heap length      |
+//! |-------|----------------|------------------|
+//! | 64    | up to 15       | below `1 << 60`  |
+//! | 32    | up to 7        | below `1 << 28`  |
+//! | 16    | up to 3        | below `1 << 12`  |
+//!
+//! The low bit of the pointer word discriminates them, which is always clear
+//! for the `Rc` allocations. The meta byte of inline handles keeps the flag in its
+//! bit 0, the length in bits 1..5 and the tag in bits 5..8. Heap handles keep
+//! the unpooled flag and the tag in the top 4 bits of the length.
+//!
+//! It follows the strict provenance rules. Inline handles build their pointer word
+//! with `ptr::without_provenance_mut` and update it with `map_addr`, and addresses
+//! are only read with `addr()` as keys, never cast back to pointers.
+
+use std::mem;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::ffi::{CStr, OsStr};
+use std::slice;
+use std::str;
+use std::ptr::{self, NonNull};
+
+use super::pins;
+
+#[cfg(target_endian = "little")]
+#[repr(C)]
+pub struct Handle {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+#[cfg(target_endian = "big")]
+#[repr(C)]
+pub struct Handle {
+    len: usize,
+    ptr: NonNull<u8>,
+}
+
+const INLINE_MASK: usize = 1;
+const INLINE_TRUE: usize = 1;
+const INLINE_FALSE: usize = 0;
+
+// Set on the length of heap handles allocated outside of the unavailable pool.
+const UNPOOLED_FLAG: usize = 1 << (usize::BITS - 1);
+
+/// Number of the user tag bits of the handle.
+pub const TAG_BITS: u32 = 3;
+pub(super) const TAG_MAX: u8 = (1 << TAG_BITS) - 1;
+// Inline handles keep the tag above the length in the meta byte,
+// and heap handles keep it below the unpooled flag of the length.
+const INLINE_TAG_SHIFT: u32 = 5;
+const INLINE_LEN_MASK: u8 = (1 << INLINE_TAG_SHIFT) - 1;
+const HEAP_TAG_SHIFT: u32 = usize::BITS - 1 - TAG_BITS;
+const HEAP_TAG_MASK: usize = (TAG_MAX as usize) << HEAP_TAG_SHIFT;
+
+// Refcount from which the entry is leaked instead of counting more handles,
+// so clones and drops of it stop touching the count before it overflows.
+#[cfg(not(test))]
+const SATURATED: usize = isize::MAX as usize / 2;
+#[cfg(test)]
+const SATURATED: usize = 1 << 12;
+
+const INLINE_ARRAY_SIZE: usize = mem::size_of::<Handle>();
+pub const INLINE_MAX_LEN: usize = INLINE_ARRAY_SIZE - 1;
+/// Max length of the heap allocated arrays, limited by the flags in the length.
+pub const HEAP_MAX_LEN: usize = !(UNPOOLED_FLAG | HEAP_TAG_MASK);
+
+const _: () = assert!(INLINE_MAX_LEN <= (INLINE_LEN_MASK >> 1) as usize);
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(INLINE_MAX_LEN == 15 && HEAP_MAX_LEN == (1 << 60) - 1);
+#[cfg(target_pointer_width = "32")]
+const _: () = assert!(INLINE_MAX_LEN == 7 && HEAP_MAX_LEN == (1 << 28) - 1);
+#[cfg(target_pointer_width = "16")]
+const _: () = assert!(INLINE_MAX_LEN == 3 && HEAP_MAX_LEN == (1 << 12) - 1);
+
+/// Whether `Handle::new` keeps the contents of the length in the handle.
+#[inline]
+pub fn interns_inline(len: usize) -> bool {
+    len <= INLINE_MAX_LEN
+}
+
+const BIG_ENDIAN: bool = cfg!(target_endian = "big");
+const INLINE_META: usize = inline_meta(BIG_ENDIAN);
+const INLINE_START: usize = inline_start(BIG_ENDIAN);
+
+// The pointer word comes first on little-endian targets and last on big-endian ones,
+// see the fields of `Handle`, so the inline flag stays at the low bit of the pointer.
+const fn ptr_offset(big_endian: bool) -> usize {
+    if big_endian { mem::size_of::<usize>() } else { 0 }
+}
+
+// The meta byte is the least significant byte of the pointer word, holding the inline flag.
+const fn inline_meta(big_endian: bool) -> usize {
+    ptr_offset(big_endian) + if big_endian { mem::size_of::<usize>() - 1 } else { 0 }
+}
+
+// The contents follow the meta byte contiguously, or precede it on big-endian targets.
+const fn inline_start(big_endian: bool) -> usize {
+    if big_endian { 0 } else { 1 }
+}
+
+#[cfg(debug_assertions)]
+thread_local! {
+    // Addresses of the heap allocations made by this thread, to detect handles cloned or dropped
+    // on other threads. Reads are left unchecked to keep `get` cheap in debug builds.
+    static OWNED: RefCell<HashSet<usize>> = Default::default();
+}
+
+#[cfg(debug_assertions)]
+fn register_owned(addr: usize) {
+    let _ = OWNED.try_with(|owned| owned.borrow_mut().insert(addr));
+}
+
+#[cfg(debug_assertions)]
+fn unregister_owned(addr: usize) {
+    let _ = OWNED.try_with(|owned| owned.borrow_mut().remove(&addr));
+}
+
+#[cfg(debug_assertions)]
+#[inline]
+fn check_owned(addr: usize) {
+    // Ownership can't be checked once this thread's table is destroyed.
+    if let Ok(false) = OWNED.try_with(|owned| owned.borrow().contains(&addr)) {
+        panic!("interned handle {:#x} is used on the thread {:?}, not the one which interned it",
+            addr, ::std::thread::current().id());
+    }
+}
+
+#[cfg(not(debug_assertions))]
+fn register_owned(_addr: usize) {}
+
+#[cfg(not(debug_assertions))]
+fn unregister_owned(_addr: usize) {}
+
+#[cfg(not(debug_assertions))]
+#[inline]
+fn check_owned(_addr: usize) {}
+
+/// Pool entry seen from other threads
+///
+/// It's removed from the mirror before the entry is freed by its owner thread,
+/// so it's valid while the mirror's lock is held.
+#[derive(Clone)]
+pub struct RawEntry {
+    ptr: *const u8,
+    len: usize,
+}
+
+unsafe impl Send for RawEntry {}
+unsafe impl Sync for RawEntry {}
+
+impl RawEntry {
+    #[inline]
+    pub(super) fn new(rc: &Rc<[u8]>) -> Self {
+        RawEntry { ptr: rc.as_ptr(), len: rc.len() }
+    }
+
+    /// Address of the pool entry.
+    #[inline]
+    pub fn addr(&self) -> usize {
+        self.ptr.addr()
+    }
+
+    #[inline]
+    pub fn get(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.ptr, self.len)
+        }
+    }
+}
+
+/// Bytes known to be valid UTF-8, like the contents of the interned strings.
+#[inline]
+pub fn to_str(bytes: &[u8]) -> &str {
+    unsafe {
+        str::from_utf8_unchecked(bytes)
+    }
+}
+
+/// Contents of the interned C string, which are always nul terminated without inner nuls.
+#[inline]
+pub fn to_cstr(bytes: &[u8]) -> &CStr {
+    unsafe {
+        CStr::from_bytes_with_nul_unchecked(bytes)
+    }
+}
+
+/// Contents of the interned OS string, which always came from `OsStr::as_encoded_bytes`
+/// of this platform, or from valid UTF-8 which is a subset of it.
+#[inline]
+pub fn to_os_str(bytes: &[u8]) -> &OsStr {
+    unsafe {
+        OsStr::from_encoded_bytes_unchecked(bytes)
+    }
+}
+
+impl Handle {
+    /// Words of the handle, passing its refcount to the caller.
+    #[inline]
+    pub fn into_raw_parts(self) -> (NonNull<u8>, usize) {
+        let this = mem::ManuallyDrop::new(self);
+        (this.ptr, this.len)
+    }
+
+    /// Handle from the words of `into_raw_parts()`, taking back its refcount.
+    ///
+    /// # Safety
+    ///
+    /// The words must be from `into_raw_parts()` on this thread, and the refcount
+    /// passed by it must not be taken back yet.
+    #[inline]
+    pub unsafe fn from_raw_parts(ptr: NonNull<u8>, len: usize) -> Self {
+        Handle { ptr, len }
+    }
+
+    #[inline]
+    pub(super) fn from_rc(rc: Rc<[u8]>) -> Self {
+        let len = rc.len();
+        let ptr = NonNull::new(rc.as_ptr() as *mut u8).unwrap();
+
+        let rc_ptr = Rc::into_raw(rc);
+        debug_assert_eq!(rc_ptr as *const u8, ptr.as_ptr(),
+            "slice ptr does not match with ptr from Rc::into_raw");
+        // At the time this code is written, RcBox (heap-allocated part of Rc) contains
+        // 2 usize fields to store strong/weak ref counter, which makes it to aligned for them.
+        // This is not guaranteed by spec though, It's very unlikely to change to be unaligned.
+        debug_assert_eq!(ptr.as_ptr().addr() & INLINE_MASK, INLINE_FALSE,
+            "It seems like Rc ptr is not alligned with at least 2 bytes, {:p}", ptr);
+
+        register_owned(ptr.as_ptr().addr());
+        Handle { ptr, len }
+    }
+
+    pub(super) fn from_unpooled_rc(rc: Rc<[u8]>) -> Self {
+        let mut handle = Handle::from_rc(rc);
+        handle.len |= UNPOOLED_FLAG;
+        handle
+    }
+
+    /// Inline handle of `slice`. It's `const` so inline handles can be constants.
+    #[inline]
+    pub const fn new_inline(slice: &[u8]) -> Self {
+        assert!(slice.len() <= INLINE_MAX_LEN, "Size is larger then INLINE_MAX_LEN");
+
+        let length = slice.len() as u8;
+        let meta = length << 1 | INLINE_TRUE as u8;
+
+        let mut array = [0u8; INLINE_ARRAY_SIZE];
+        array[INLINE_META] = meta;
+
+        let mut idx = 0;
+        while idx < slice.len() {
+            array[INLINE_START + idx] = slice[idx];
+            idx += 1;
+        }
+
+        // Inline handles never point to anything, so the pointer word is an address
+        // without provenance instead of an integer transmuted to a pointer.
+        let mut ptr = [0u8; mem::size_of::<usize>()];
+        let mut len = [0u8; mem::size_of::<usize>()];
+        let ptr_start = ptr_offset(BIG_ENDIAN);
+        let len_start = mem::size_of::<usize>() - ptr_start;
+
+        let mut idx = 0;
+        while idx < ptr.len() {
+            ptr[idx] = array[ptr_start + idx];
+            len[idx] = array[len_start + idx];
+            idx += 1;
+        }
+
+        Handle {
+            // The inline flag is set, so it's never null.
+            ptr: unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(usize::from_ne_bytes(ptr))) },
+            len: usize::from_ne_bytes(len),
+        }
+    }
+
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        match self.ptr.as_ptr().addr() & INLINE_MASK {
+            INLINE_TRUE => true,
+            INLINE_FALSE => false,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Whether it's allocated outside of the pool as the pool was unavailable.
+    #[inline]
+    pub fn is_unpooled(&self) -> bool {
+        !self.is_inline() && self.len & UNPOOLED_FLAG != 0
+    }
+
+    /// Whether the id is shared by every handle of the same contents, which isn't for the unpooled ones.
+    #[inline]
+    pub fn has_unique_id(&self) -> bool {
+        !self.is_unpooled()
+    }
+
+    #[inline]
+    pub(super) fn raw_id(&self) -> (usize, usize) {
+        let mut untagged = mem::ManuallyDrop::new(unsafe { ptr::read(self) });
+        untagged.set_tag(0);
+        (untagged.ptr.as_ptr().addr(), untagged.len)
+    }
+
+    #[inline]
+    pub fn tag(&self) -> u8 {
+        if self.is_inline() {
+            // The meta byte is the least significant byte of the pointer word.
+            self.ptr.as_ptr().addr() as u8 >> INLINE_TAG_SHIFT
+        } else {
+            ((self.len & HEAP_TAG_MASK) >> HEAP_TAG_SHIFT) as u8
+        }
+    }
+
+    /// Replace the tag, which is copied to the clones. Panics if it exceeds `TAG_BITS`.
+    #[inline]
+    pub fn set_tag(&mut self, tag: u8) {
+        assert!(tag <= TAG_MAX, "tag {} doesn't fit in {} bits", tag, TAG_BITS);
+
+        if self.is_inline() {
+            let mask = !(INLINE_LEN_MASK as usize) & 0xFF;
+            let ptr = self.ptr.as_ptr().map_addr(|addr| addr & !mask | (tag as usize) << INLINE_TAG_SHIFT);
+            // The inline flag is kept, so it's never null.
+            self.ptr = unsafe { NonNull::new_unchecked(ptr) };
+        } else {
+            self.len = self.len & !HEAP_TAG_MASK | (tag as usize) << HEAP_TAG_SHIFT;
+        }
+    }
+
+    /// Address of the pool entry, or `None` if inlined or unpooled.
+    #[inline]
+    pub fn pool_id(&self) -> Option<usize> {
+        if self.is_inline() || self.is_unpooled() {
+            None
+        } else {
+            Some(self.ptr.as_ptr().addr())
+        }
+    }
+
+    // Leak the entry whose refcount reached `SATURATED`.
+    #[cold]
+    fn saturate(&self) {
+        if let Some(addr) = self.pool_id() {
+            pins::leak_saturated(addr);
+        }
+    }
+
+    /// Take the extra refcount of the pin.
+    pub(super) fn retain_pin(&self) {
+        mem::forget(Rc::clone(&self.get_rc()));
+    }
+
+    /// Give back the extra refcount of the pin.
+    pub(super) fn release_pin(&self) {
+        // At least `self` still refers it, so it never frees the entry.
+        unsafe {
+            mem::ManuallyDrop::drop(&mut self.get_rc());
+        }
+    }
+
+    /// Contents of the leaked entry, whose refcount never drops to zero.
+    #[inline]
+    pub(super) fn get_static(&self) -> &'static [u8] {
+        unsafe {
+            slice::from_raw_parts(self.ptr.as_ptr(), self.heap_len())
+        }
+    }
+
+    #[inline]
+    pub fn get(&self) -> &[u8] {
+        if self.is_inline() {
+            self.get_inline()
+        } else {
+            self.get_heap()
+        }
+    }
+
+    #[inline]
+    fn get_heap(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.ptr.as_ptr(), self.heap_len())
+        }
+    }
+
+    #[inline]
+    fn heap_len(&self) -> usize {
+        self.len & !(UNPOOLED_FLAG | HEAP_TAG_MASK)
+    }
+
+    #[inline]
+    fn inline_bytes(&self) -> &[u8; INLINE_ARRAY_SIZE] {
+        unsafe {
+            &*(self as *const Handle as *const [u8; INLINE_ARRAY_SIZE])
+        }
+    }
+
+    #[inline]
+    fn get_inline(&self) -> &[u8] {
+        let bytes = self.inline_bytes();
+        let len = (bytes[INLINE_META] & INLINE_LEN_MASK) as usize >> 1;
+        &bytes[INLINE_START..INLINE_START + len]
+    }
+
+    #[inline]
+    pub(super) fn get_rc(&self) -> mem::ManuallyDrop<Rc<[u8]>> {
+        unsafe {
+            let slice_ptr = ptr::slice_from_raw_parts(self.ptr.as_ptr() as *const u8, self.heap_len());
+            mem::ManuallyDrop::new(Rc::from_raw(slice_ptr))
+        }
+    }
+}
+
+impl Drop for Handle {
+    #[inline]
+    fn drop(&mut self) {
+        if self.is_inline() {
+            return;
+        }
+
+        check_owned(self.ptr.as_ptr().addr());
+        let mut rc = self.get_rc();
+
+        // Handles of the saturated entry may not own their counts, so it's never decremented below.
+        if Rc::strong_count(&rc) == SATURATED {
+            self.saturate();
+            return;
+        }
+
+        self.release(&rc);
+
+        if Rc::strong_count(&rc) == 1 {
+            unregister_owned(self.ptr.as_ptr().addr());
+        }
+
+        unsafe {
+            mem::ManuallyDrop::drop(&mut rc);
+        }
+    }
+}
+
+impl Clone for Handle {
+    #[inline]
+    fn clone(&self) -> Self {
+        if !self.is_inline() {
+            check_owned(self.ptr.as_ptr().addr());
+            let rc = self.get_rc();
+
+            if Rc::strong_count(&rc) < SATURATED {
+                mem::forget(Rc::clone(&rc));
+            } else {
+                self.saturate();
+            }
+        }
+
+        unsafe {
+            mem::transmute_copy(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(mem::size_of::<Handle>(), 2 * mem::size_of::<usize>());
+        assert_eq!(INLINE_MAX_LEN, 2 * mem::size_of::<usize>() - 1);
+
+        let max = vec![b'x'; INLINE_MAX_LEN];
+        let inline = Handle::new(&max);
+        assert!(inline.is_inline());
+        assert_eq!(inline.get(), &max[..]);
+        // The meta byte must share the byte of the pointer word holding the inline flag.
+        assert_eq!(inline.ptr.as_ptr().addr() & INLINE_MASK, INLINE_TRUE);
+
+        let heap = Handle::new(&[b'x'; INLINE_MAX_LEN + 1][..]);
+        assert!(!heap.is_inline());
+        assert_eq!(heap.len, INLINE_MAX_LEN + 1);
+        assert_eq!(heap.get().len(), INLINE_MAX_LEN + 1);
+    }
+
+    // Simulates the inline representation of both byte orders, so it runs on any target.
+    #[test]
+    fn test_layout_endian() {
+        const WORD: usize = mem::size_of::<usize>();
+
+        for &big_endian in &[false, true] {
+            let meta = inline_meta(big_endian);
+            let start = inline_start(big_endian);
+            let content = start..start + INLINE_MAX_LEN;
+
+            assert!(meta < INLINE_ARRAY_SIZE && !content.contains(&meta));
+            assert!(content.end <= INLINE_ARRAY_SIZE);
+
+            let mut array = [0xFFu8; INLINE_ARRAY_SIZE];
+            array[meta] = (INLINE_MAX_LEN as u8) << 1 | INLINE_TRUE as u8;
+
+            let offset = ptr_offset(big_endian);
+            let mut word = [0u8; WORD];
+            word.copy_from_slice(&array[offset..offset + WORD]);
+            let ptr = if big_endian { usize::from_be_bytes(word) } else { usize::from_le_bytes(word) };
+
+            // The discriminant and the length are read from the pointer word.
+            assert_eq!(ptr & INLINE_MASK, INLINE_TRUE);
+            assert_eq!((ptr as u8 & INLINE_LEN_MASK) as usize >> 1, INLINE_MAX_LEN);
+        }
+
+        assert_eq!(INLINE_META, inline_meta(cfg!(target_endian = "big")));
+        assert_eq!(INLINE_START, inline_start(cfg!(target_endian = "big")));
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn test_layout_32() {
+        assert_eq!(INLINE_MAX_LEN, 7);
+        assert_eq!(HEAP_MAX_LEN, (1 << 28) - 1);
+        assert!(Handle::new(b"armv7ok").is_inline());
+        assert!(!Handle::new(b"armv7 ok").is_inline());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn test_foreign_thread_panics() {
+        struct Smuggled(mem::ManuallyDrop<Handle>);
+        unsafe impl Send for Smuggled {}
+
+        let handle = Handle::new(&b"a handle smuggled into another thread"[..]);
+        let smuggled = Smuggled(mem::ManuallyDrop::new(handle.clone()));
+
+        let result = ::std::thread::spawn(move || {
+            let smuggled = smuggled;
+            // Only clones and drops are checked, which touch the refcount of the entry.
+            assert_eq!(smuggled.0.get().len(), 37);
+            mem::forget(Handle::clone(&smuggled.0));
+        }).join();
+
+        assert!(result.is_err());
+        assert_eq!(handle.get().len(), 37);
+    }
+
+    #[test]
+    fn test_refcount_saturation() {
+        let data = &b"an atom cloned until its refcount saturates"[..];
+        let handle = Handle::new(data);
+        assert!(!handle.is_pinned());
+
+        let clones: Vec<_> = (0..SATURATED).map(|_| handle.clone()).collect();
+        assert_eq!(Rc::strong_count(&handle.get_rc()), SATURATED);
+        assert!(handle.is_pinned());
+        assert_eq!(handle.as_static(), Some(data));
+
+        drop(clones);
+        drop(handle);
+        for _ in 0..2 {
+            let handle = Handle::new(data);
+            assert_eq!(Rc::strong_count(&handle.get_rc()), SATURATED + 1);
+            assert!(!handle.unpin());
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Safe representation of the handles, for the `forbid-unsafe` builds
+//!
+//! Handles hold the `Rc<[u8]>` of the pool entry as is, and every string is interned
+//! in the pool regardless of its length. Only the constants like `IStr::TRUE` keep
+//! their contents in the handle, as constants can't refer to the pool. They compare equal
+//! to the pooled handles of the same contents, but their ids differ like the unpooled ones.
+//!
+//! Pins keep a clone of the entry in a thread local table instead of leaking its refcount,
+//! and leaked entries are borrowed for `'static` from their copies which are never freed.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::cell::RefCell;
+use std::ffi::{CStr, OsStr};
+use std::ops::Deref;
+use std::mem;
+use std::str;
+
+const WORD: usize = mem::size_of::<usize>();
+
+/// Whether `Handle::new` keeps the contents of the length in the handle. Never in this build.
+#[inline]
+pub fn interns_inline(_len: usize) -> bool {
+    false
+}
+
+/// Number of the user tag bits of the handle.
+pub const TAG_BITS: u32 = 3;
+pub(super) const TAG_MAX: u8 = (1 << TAG_BITS) - 1;
+
+/// Max length of the constant handles, same as the inline length of the packed ones.
+pub const INLINE_MAX_LEN: usize = 2 * WORD - 1;
+/// Max length of the heap allocated arrays, same as the packed representation's.
+pub const HEAP_MAX_LEN: usize = (1 << (usize::BITS - 1 - TAG_BITS)) - 1;
+
+thread_local! {
+    // Clones of the pinned entries, which hold their extra refcounts.
+    static KEPT: RefCell<HashMap<usize, Rc<[u8]>>> = Default::default();
+    // Copies of the leaked entries, by the address of the entry.
+    static LEAKED: RefCell<HashMap<usize, &'static [u8]>> = Default::default();
+}
+
+#[derive(Clone)]
+pub struct Handle(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    // Contents of the constant handle.
+    Const {
+        len: u8,
+        tag: u8,
+        bytes: [u8; INLINE_MAX_LEN],
+    },
+    Heap {
+        rc: Rc<[u8]>,
+        tag: u8,
+        // Allocated outside of the unavailable pool.
+        unpooled: bool,
+    },
+}
+
+/// Entry of the heap handle, borrowed like the `ManuallyDrop<Rc<[u8]>>` of the packed handles
+pub(super) struct RcRef<'a>(&'a Rc<[u8]>);
+
+impl<'a> Deref for RcRef<'a> {
+    type Target = Rc<[u8]>;
+
+    #[inline]
+    fn deref(&self) -> &Rc<[u8]> {
+        self.0
+    }
+}
+
+/// Pool entry seen from other threads
+///
+/// It's a copy of the entry, as the entry itself can't be shared across threads.
+#[derive(Clone)]
+pub struct RawEntry {
+    addr: usize,
+    data: Arc<[u8]>,
+}
+
+impl RawEntry {
+    #[inline]
+    pub(super) fn new(rc: &Rc<[u8]>) -> Self {
+        RawEntry { addr: rc.as_ptr().addr(), data: Arc::from(&**rc) }
+    }
+
+    /// Address of the pool entry.
+    #[inline]
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    #[inline]
+    pub fn get(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Bytes known to be valid UTF-8, like the contents of the interned strings.
+/// They're validated again here, and it panics if they're not.
+#[inline]
+pub fn to_str(bytes: &[u8]) -> &str {
+    str::from_utf8(bytes).expect("interned string is not valid UTF-8")
+}
+
+/// Contents of the interned C string, which are always nul terminated without inner nuls.
+#[inline]
+pub fn to_cstr(bytes: &[u8]) -> &CStr {
+    CStr::from_bytes_with_nul(bytes).expect("interned C string is not nul terminated")
+}
+
+/// Contents of the interned OS string, which always came from `OsStr::as_encoded_bytes`.
+#[cfg(unix)]
+#[inline]
+pub fn to_os_str(bytes: &[u8]) -> &OsStr {
+    use std::os::unix::ffi::OsStrExt;
+
+    OsStr::from_bytes(bytes)
+}
+
+/// Contents of the interned OS string. Other than on unix, `OsStr` can't be built
+/// from its encoded bytes without `unsafe`, so it panics if they're not valid UTF-8.
+#[cfg(not(unix))]
+#[inline]
+pub fn to_os_str(bytes: &[u8]) -> &OsStr {
+    OsStr::new(str::from_utf8(bytes).expect("interned OS string is not valid UTF-8"))
+}
+
+impl Handle {
+    #[inline]
+    pub(super) fn from_rc(rc: Rc<[u8]>) -> Self {
+        Handle(Repr::Heap { rc, tag: 0, unpooled: false })
+    }
+
+    pub(super) fn from_unpooled_rc(rc: Rc<[u8]>) -> Self {
+        Handle(Repr::Heap { rc, tag: 0, unpooled: true })
+    }
+
+    /// Handle keeping `slice` in itself, for the constants. It's never in the pool.
+    #[inline]
+    pub const fn new_inline(slice: &[u8]) -> Self {
+        assert!(slice.len() <= INLINE_MAX_LEN, "Size is larger then INLINE_MAX_LEN");
+
+        let mut bytes = [0u8; INLINE_MAX_LEN];
+        let mut idx = 0;
+        while idx < slice.len() {
+            bytes[idx] = slice[idx];
+            idx += 1;
+        }
+
+        Handle(Repr::Const { len: slice.len() as u8, tag: 0, bytes })
+    }
+
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Const { .. })
+    }
+
+    /// Whether the id is shared by every handle of the same contents,
+    /// which isn't for the unpooled and the constant handles.
+    #[inline]
+    pub fn has_unique_id(&self) -> bool {
+        matches!(self.0, Repr::Heap { unpooled: false, .. })
+    }
+
+    /// Whether it's allocated outside of the pool as the pool was unavailable.
+    #[inline]
+    pub fn is_unpooled(&self) -> bool {
+        matches!(self.0, Repr::Heap { unpooled: true, .. })
+    }
+
+    // Constant ids have the low bit set like the inline ids of the packed handles,
+    // so they never collide with the addresses of the entries.
+    #[inline]
+    pub(super) fn raw_id(&self) -> (usize, usize) {
+        match self.0 {
+            Repr::Const { len, ref bytes, .. } => {
+                let mut array = [0u8; 2 * WORD];
+                array[0] = len << 1 | 1;
+                array[1..].copy_from_slice(bytes);
+
+                let mut words = [[0u8; WORD]; 2];
+                words[0].copy_from_slice(&array[..WORD]);
+                words[1].copy_from_slice(&array[WORD..]);
+                (usize::from_le_bytes(words[0]), usize::from_le_bytes(words[1]))
+            }
+            Repr::Heap { ref rc, .. } => (rc.as_ptr().addr(), rc.len()),
+        }
+    }
+
+    #[inline]
+    pub fn tag(&self) -> u8 {
+        match self.0 {
+            Repr::Const { tag, .. } | Repr::Heap { tag, .. } => tag,
+        }
+    }
+
+    /// Replace the tag, which is copied to the clones. Panics if it exceeds `TAG_BITS`.
+    #[inline]
+    pub fn set_tag(&mut self, new: u8) {
+        assert!(new <= TAG_MAX, "tag {} doesn't fit in {} bits", new, TAG_BITS);
+
+        match self.0 {
+            Repr::Const { ref mut tag, .. } | Repr::Heap { ref mut tag, .. } => *tag = new,
+        }
+    }
+
+    /// Address of the pool entry, or `None` if inlined or unpooled.
+    #[inline]
+    pub fn pool_id(&self) -> Option<usize> {
+        match self.0 {
+            Repr::Heap { ref rc, unpooled: false, .. } => Some(rc.as_ptr().addr()),
+            _ => None,
+        }
+    }
+
+    /// Take the extra refcount of the pin.
+    pub(super) fn retain_pin(&self) {
+        let rc = Rc::clone(&self.get_rc());
+        KEPT.with(|kept| kept.borrow_mut().insert(rc.as_ptr().addr(), rc));
+    }
+
+    /// Give back the extra refcount of the pin.
+    pub(super) fn release_pin(&self) {
+        let addr = self.get_rc().as_ptr().addr();
+        let kept = KEPT.with(|kept| kept.borrow_mut().remove(&addr));
+        // At least `self` still refers it, so it never removes the entry.
+        drop(kept);
+    }
+
+    /// Copy of the leaked entry, which is never freed.
+    pub(super) fn get_static(&self) -> &'static [u8] {
+        let rc = self.get_rc();
+
+        LEAKED.with(|leaked| {
+            *leaked.borrow_mut().entry(rc.as_ptr().addr()).or_insert_with(|| {
+                let bytes: &'static [u8] = Box::leak(Box::from(&**rc));
+                bytes
+            })
+        })
+    }
+
+    #[inline]
+    pub fn get(&self) -> &[u8] {
+        match self.0 {
+            Repr::Const { len, ref bytes, .. } => &bytes[..len as usize],
+            Repr::Heap { ref rc, .. } => rc,
+        }
+    }
+
+    #[inline]
+    pub(super) fn get_rc(&self) -> RcRef<'_> {
+        match self.0 {
+            Repr::Heap { ref rc, .. } => RcRef(rc),
+            Repr::Const { .. } => unreachable!("inline handle has no pool entry"),
+        }
+    }
+}
+
+impl Drop for Handle {
+    #[inline]
+    fn drop(&mut self) {
+        if let Repr::Heap { ref rc, .. } = self.0 {
+            self.release(rc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_const_ids() {
+        let a = Handle::new_inline(b"ab");
+        let b = Handle::new_inline(b"ab\0");
+        let pooled = Handle::new(b"ab");
+
+        assert_eq!(a.raw_id(), Handle::new_inline(b"ab").raw_id());
+        assert_ne!(a.raw_id(), b.raw_id());
+        assert_eq!(a.raw_id().0 & 1, 1);
+        assert_eq!(pooled.raw_id().0 & 1, 0);
+        assert!(!pooled.is_inline() && pooled.pool_id().is_some());
+        assert_eq!(a, pooled);
+        assert_eq!(a.to_pooled().unwrap().raw_id(), pooled.raw_id());
+        assert_eq!(Handle::new_inline(&[0xFF; INLINE_MAX_LEN]).get(), &[0xFF; INLINE_MAX_LEN][..]);
+    }
+
+    #[test]
+    fn test_pin_keeps_clone() {
+        let data = &b"pinned by the clone kept in the table"[..];
+        let handle = Handle::new(data);
+
+        handle.pin();
+        assert_eq!(Rc::strong_count(&handle.get_rc()), 3);
+        assert!(handle.unpin());
+        assert_eq!(Rc::strong_count(&handle.get_rc()), 2);
+
+        let leaked = handle.leak().unwrap();
+        assert_eq!(leaked, data);
+        assert!(::std::ptr::eq(leaked, Handle::new(data).as_static().unwrap()));
+    }
+}
@@ -0,0 +1,161 @@
+//! Saturation warning of the pool
+//!
+//! Tracks the usage of the pool against the thresholds, and calls the callback
+//! once the pool is released when any of them is crossed.
+
+use std::rc::Rc;
+use std::sync::Arc;
+use std::cell::RefCell;
+use std::mem;
+
+use super::{Hook, Pool, POOL, default_config};
+
+thread_local! {
+    static WATCH: RefCell<Option<Watch>> = RefCell::new(new_watch());
+}
+
+// The usage is only checked against the thresholds, which have nothing to be consistent with.
+pub(super) const HOOK: Hook = Hook {
+    insert,
+    remove,
+    check: |_| Ok(()),
+};
+
+/// Callback of the saturation warning set by the `PoolConfig`, shared by the pools of every thread.
+pub type SharedCallback = Arc<dyn Fn(&Saturation) + Send + Sync>;
+
+/// Limits of the pool usage to warn about
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Thresholds {
+    pub entries: Option<usize>,
+    /// Total length of the entries.
+    pub bytes: Option<usize>,
+    /// Ratio of the entries to the capacity of the pool's hash table.
+    pub fill_factor: Option<f64>,
+}
+
+/// Kind of the threshold crossed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Threshold {
+    Entries,
+    Bytes,
+    FillFactor,
+}
+
+/// Usage of the pool when a threshold is crossed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Saturation {
+    pub crossed: Threshold,
+    pub entries: usize,
+    pub bytes: usize,
+    pub fill_factor: f64,
+}
+
+/// Tracker of the pool usage against the thresholds
+struct Watch {
+    thresholds: Thresholds,
+    callback: Rc<dyn Fn(&Saturation)>,
+    bytes: usize,
+    // Thresholds currently above, which warn again only after going below.
+    above: [bool; 3],
+    pending: Vec<Saturation>,
+}
+
+impl Watch {
+    fn new(thresholds: Thresholds, callback: Rc<dyn Fn(&Saturation)>, bytes: usize) -> Self {
+        Watch {
+            thresholds,
+            callback,
+            bytes,
+            above: [false; 3],
+            pending: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, pool: &Pool) {
+        let entries = pool.len();
+        let fill_factor = if pool.capacity() == 0 {
+            0.0
+        } else {
+            entries as f64 / pool.capacity() as f64
+        };
+
+        let checks = [
+            (Threshold::Entries, self.thresholds.entries.is_some_and(|limit| entries > limit)),
+            (Threshold::Bytes, self.thresholds.bytes.is_some_and(|limit| self.bytes > limit)),
+            (Threshold::FillFactor, self.thresholds.fill_factor.is_some_and(|limit| fill_factor > limit)),
+        ];
+
+        for (idx, &(crossed, above)) in checks.iter().enumerate() {
+            if above && !self.above[idx] {
+                self.pending.push(Saturation { crossed, entries, bytes: self.bytes, fill_factor });
+            }
+            self.above[idx] = above;
+        }
+    }
+}
+
+// The watch is initialized by the first insertion at the latest,
+// so it starts from the empty pool.
+fn new_watch() -> Option<Watch> {
+    let (thresholds, callback) = default_config()?.saturation?;
+    Some(Watch::new(thresholds, local_callback(callback), 0))
+}
+
+pub(super) fn local_callback(callback: SharedCallback) -> Rc<dyn Fn(&Saturation)> {
+    Rc::new(move |saturation: &Saturation| callback(saturation))
+}
+
+#[inline]
+fn update_watch<F: FnOnce(&mut Watch)>(f: F) {
+    let _ = WATCH.try_with(|watch| {
+        if let Some(ref mut watch) = *watch.borrow_mut() {
+            f(watch);
+        }
+    });
+}
+
+/// Call the callback for the thresholds crossed, after the pool is released.
+#[inline]
+pub(super) fn fire() {
+    let fired = WATCH.try_with(|watch| match *watch.borrow_mut() {
+        Some(ref mut watch) if !watch.pending.is_empty() => {
+            Some((Rc::clone(&watch.callback), mem::take(&mut watch.pending)))
+        }
+        _ => None,
+    });
+
+    if let Ok(Some((callback, pending))) = fired {
+        for saturation in &pending {
+            callback(saturation);
+        }
+    }
+}
+
+/// Call `callback` when the usage of this thread's pool goes above any of the thresholds.
+pub fn set_saturation_warning(thresholds: Thresholds, callback: Rc<dyn Fn(&Saturation)>) {
+    let bytes = POOL.with(|pool| pool.borrow().iter().map(|rc| rc.len()).sum());
+
+    WATCH.with(|watch| *watch.borrow_mut() = Some(Watch::new(thresholds, callback, bytes)));
+
+    POOL.with(|pool| update_watch(|watch| watch.update(&pool.borrow())));
+    fire();
+}
+
+pub fn clear_saturation_warning() {
+    WATCH.with(|watch| *watch.borrow_mut() = None);
+}
+
+fn insert(pool: &Pool, rc: &Rc<[u8]>) {
+    update_watch(|watch| {
+        watch.bytes += rc.len();
+        watch.update(pool);
+    });
+}
+
+fn remove(pool: &Pool, rc: &Rc<[u8]>) {
+    update_watch(|watch| {
+        watch.bytes -= rc.len();
+        watch.update(pool);
+    });
+}
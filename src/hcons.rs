@@ -0,0 +1,186 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::cell::RefCell;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+use std::fmt;
+
+thread_local! {
+    static POOLS: RefCell<HashMap<TypeId, Box<dyn Any>>> = Default::default();
+}
+
+pub(crate) fn with_pool<T: Eq + Hash + ?Sized + 'static, R, F: FnOnce(&mut HashSet<Rc<T>>) -> R>(f: F) -> R {
+    POOLS.with(|pools| {
+        let mut pools = pools.borrow_mut();
+        let pool = pools.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(HashSet::<Rc<T>>::new()));
+
+        f(pool.downcast_mut().expect("hash-consing pool of wrong type"))
+    })
+}
+
+/// Hash-consed immutable value
+///
+/// Like `IStr` for strings, equal values of `T` share one allocation per thread,
+/// which is removed once its last handle is dropped. Handles are compared and hashed
+/// by their addresses, so nodes holding `HCons` children are hashed in constant time
+/// and identical subtrees are detected without walking them.
+///
+/// ```
+/// # use internship::HCons;
+/// #[derive(PartialEq, Eq, Hash)]
+/// enum Expr {
+///     Var(&'static str),
+///     Add(HCons<Expr>, HCons<Expr>),
+/// }
+///
+/// let x = HCons::new(Expr::Var("x"));
+/// let a = HCons::new(Expr::Add(x.clone(), x.clone()));
+/// let b = HCons::new(Expr::Add(HCons::new(Expr::Var("x")), x));
+///
+/// assert!(HCons::ptr_eq(&a, &b));
+/// assert_eq!(HCons::<Expr>::pool_len(), 2);
+/// ```
+pub struct HCons<T: Eq + Hash + 'static>(Option<Rc<T>>);
+
+impl<T: Eq + Hash + 'static> HCons<T> {
+    pub fn new(value: T) -> Self {
+        let (rc, unused) = with_pool(|pool| match pool.get(&value) {
+            Some(rc) => (Rc::clone(rc), Some(value)),
+            None => {
+                let rc = Rc::new(value);
+                pool.insert(Rc::clone(&rc));
+                (rc, None)
+            }
+        });
+
+        // Dropped after the borrow is released, as it may drop other handles of the same type.
+        drop(unused);
+        HCons(Some(rc))
+    }
+
+    #[inline]
+    fn rc(&self) -> &Rc<T> {
+        self.0.as_ref().expect("dropped hash-consed handle")
+    }
+
+    #[inline]
+    pub fn get(&self) -> &T {
+        self.rc()
+    }
+
+    /// Whether both are the same value. It equals to `==`.
+    #[inline]
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Rc::ptr_eq(a.rc(), b.rc())
+    }
+
+    /// Number of the distinct values of `T` in the current thread.
+    pub fn pool_len() -> usize {
+        with_pool(|pool: &mut HashSet<Rc<T>>| pool.len())
+    }
+}
+
+impl<T: Eq + Hash + 'static> Drop for HCons<T> {
+    fn drop(&mut self) {
+        let rc = match self.0.take() {
+            Some(rc) => rc,
+            None => return,
+        };
+
+        release(rc);
+    }
+}
+
+/// Drop the handle, removing its pool entry if it's the last one.
+pub(crate) fn release<T: Eq + Hash + ?Sized + 'static>(rc: Rc<T>) {
+    if Rc::strong_count(&rc) != 2 {
+        return;
+    }
+
+    let removed = POOLS.try_with(|pools| {
+        let mut pools = pools.borrow_mut();
+        pools.get_mut(&TypeId::of::<T>())
+            .and_then(|pool| pool.downcast_mut::<HashSet<Rc<T>>>())
+            .and_then(|pool| pool.take(&*rc))
+    });
+
+    // Dropped after the borrow is released, as it may drop the children.
+    drop(removed);
+    drop(rc);
+}
+
+impl<T: Eq + Hash + 'static> Clone for HCons<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        HCons(Some(Rc::clone(self.rc())))
+    }
+}
+
+impl<T: Eq + Hash + 'static> Deref for HCons<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T: Eq + Hash + 'static> AsRef<T> for HCons<T> {
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T: Eq + Hash + 'static> PartialEq for HCons<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        HCons::ptr_eq(self, other)
+    }
+}
+
+impl<T: Eq + Hash + 'static> Eq for HCons<T> {}
+
+impl<T: Eq + Hash + 'static> Hash for HCons<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        Rc::as_ptr(self.rc()).addr().hash(hasher)
+    }
+}
+
+impl<T: Eq + Hash + fmt::Debug + 'static> fmt::Debug for HCons<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.get(), f)
+    }
+}
+
+impl<T: Eq + Hash + fmt::Display + 'static> fmt::Display for HCons<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.get(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    struct Node(u32, Vec<HCons<Node>>);
+
+    #[test]
+    fn test_subtrees_removed() {
+        let leaf = HCons::new(Node(1, vec![]));
+        let tree = HCons::new(Node(0, vec![leaf.clone(), leaf.clone()]));
+        drop(leaf);
+        assert_eq!(HCons::<Node>::pool_len(), 2);
+
+        let same = HCons::new(Node(0, vec![HCons::new(Node(1, vec![])); 2]));
+        assert_eq!(tree, same);
+
+        drop(tree);
+        drop(same);
+        assert_eq!(HCons::<Node>::pool_len(), 0);
+    }
+}
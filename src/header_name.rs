@@ -0,0 +1,164 @@
+use std::ops::Deref;
+use std::str::FromStr;
+use std::error::Error;
+use std::fmt;
+
+use istr::IStr;
+
+/// Interned HTTP header name
+///
+/// Header names are case-insensitive. `IHeaderName` stores lowercased name,
+/// so two header names are equal if and only if their handles are equal.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IHeaderName(IStr);
+
+/// Error returned when the header name contains invalid character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidHeaderName;
+
+impl IHeaderName {
+    pub fn new(src: &str) -> Result<Self, InvalidHeaderName> {
+        if src.is_empty() || !src.bytes().all(is_token) {
+            return Err(InvalidHeaderName);
+        }
+
+        if src.bytes().any(|b| b.is_ascii_uppercase()) {
+            Ok(IHeaderName(IStr::new(&src.to_ascii_lowercase())))
+        } else {
+            Ok(IHeaderName(IStr::new(src)))
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    #[inline]
+    pub fn as_istr(&self) -> &IStr {
+        &self.0
+    }
+
+    #[inline]
+    pub fn into_istr(self) -> IStr {
+        self.0
+    }
+}
+
+/// Whether the byte is allowed in `token` of RFC 7230.
+pub(crate) fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+macro_rules! common_names {
+    ($($name:ident => $value:expr,)*) => {
+        impl IHeaderName {
+            $(
+                #[doc = concat!("`", $value, "`")]
+                pub fn $name() -> IHeaderName {
+                    thread_local! {
+                        static NAME: IHeaderName = IHeaderName(IStr::new($value));
+                    }
+
+                    NAME.with(Clone::clone)
+                }
+            )*
+        }
+    };
+}
+
+common_names! {
+    accept => "accept",
+    accept_encoding => "accept-encoding",
+    accept_language => "accept-language",
+    authorization => "authorization",
+    cache_control => "cache-control",
+    connection => "connection",
+    content_encoding => "content-encoding",
+    content_length => "content-length",
+    content_type => "content-type",
+    cookie => "cookie",
+    date => "date",
+    etag => "etag",
+    host => "host",
+    location => "location",
+    origin => "origin",
+    referer => "referer",
+    set_cookie => "set-cookie",
+    user_agent => "user-agent",
+}
+
+impl FromStr for IHeaderName {
+    type Err = InvalidHeaderName;
+
+    fn from_str(src: &str) -> Result<Self, InvalidHeaderName> {
+        IHeaderName::new(src)
+    }
+}
+
+impl Deref for IHeaderName {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for IHeaderName {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for IHeaderName {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str().eq_ignore_ascii_case(other)
+    }
+}
+
+impl PartialEq<&str> for IHeaderName {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str().eq_ignore_ascii_case(other)
+    }
+}
+
+impl From<IHeaderName> for IStr {
+    #[inline]
+    fn from(v: IHeaderName) -> IStr {
+        v.0
+    }
+}
+
+impl fmt::Debug for IHeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for IHeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for InvalidHeaderName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid HTTP header name")
+    }
+}
+
+impl Error for InvalidHeaderName {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_name() {
+        assert_eq!(IHeaderName::new("Content-Type"), Ok(IHeaderName::content_type()));
+        assert_eq!(IHeaderName::host(), "HOST");
+        assert!(IHeaderName::new("foo bar").is_err());
+    }
+}
@@ -1,11 +1,13 @@
-use std::ops::{Deref, Index, Range, RangeFrom, RangeTo, RangeFull};
+use std::ops::{Deref, Index, Range, RangeFrom, RangeTo, RangeFull, RangeBounds, Bound};
 use std::cmp::{PartialEq};
 use std::hash::{Hash, Hasher};
 use std::borrow::{Borrow, Cow};
 use std::fmt;
 use std::str::{from_utf8, Utf8Error};
+use std::ffi::{CStr, CString, NulError};
+use std::convert::TryFrom;
 
-use handle::Handle;
+use handle::{Handle, INLINE_MAX_LEN};
 use istr::IStr;
 use icstr::ICStr;
 
@@ -16,10 +18,26 @@ use icstr::ICStr;
 pub struct IBytes(pub(crate) Handle);
 
 impl IBytes {
+    /// Max length of the byte strings stored inline without the pool,
+    /// `2 * size_of::<usize>() - 1` bytes, e.g. 15 on 64-bit and 7 on 32-bit targets.
+    pub const MAX_INLINE_LEN: usize = INLINE_MAX_LEN;
+
     pub fn new(src: &[u8]) -> Self {
         IBytes(Handle::new(src))
     }
 
+    /// Create an inlined bytes, or `None` if it's too long to be inlined.
+    /// It never touches the pool nor allocates.
+    #[inline]
+    pub const fn new_inline(src: &[u8]) -> Option<Self> {
+        if src.len() > INLINE_MAX_LEN {
+            None
+        } else {
+            Some(IBytes(Handle::new_inline(src)))
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(src: &str) -> Self {
         IBytes(Handle::new(src.as_bytes()))
     }
@@ -33,6 +51,112 @@ impl IBytes {
     pub fn to_istr(&self) -> Result<IStr, Utf8Error> {
         from_utf8(self).map(|_| IStr(self.0.clone()))
     }
+
+    /// Nul terminated string of the bytes. The handle is reused if it already ends with
+    /// the only nul byte, or a nul is appended if it has none.
+    ///
+    /// ```
+    /// # use internship::IBytes;
+    /// let with_nul = IBytes::new(b"foo\0");
+    /// assert_eq!(with_nul.to_icstr().unwrap().to_ibytes_with_nul(), with_nul);
+    /// assert_eq!(IBytes::new(b"foo").to_icstr().unwrap().as_bytes_with_nul(), b"foo\0");
+    /// assert!(IBytes::new(b"f\0o").to_icstr().is_err());
+    /// ```
+    pub fn to_icstr(&self) -> Result<ICStr, NulError> {
+        if CStr::from_bytes_with_nul(self).is_ok() {
+            return Ok(ICStr(self.0.clone()));
+        }
+
+        CString::new(self.as_bytes()).map(ICStr::from)
+    }
+
+    /// Intern the subslice. Panics if the range is out of bounds, like slicing.
+    ///
+    /// Fields carved out of many frames, like magic numbers and fixed headers,
+    /// share one handle instead of being copied out of each frame.
+    ///
+    /// ```
+    /// # use internship::IBytes;
+    /// let frame = IBytes::new(b"\x89PNG\r\n\x1a\n and the rest of the frame");
+    /// let magic = frame.slice_interned(..8);
+    ///
+    /// assert_eq!(magic, IBytes::new(b"\x89PNG\r\n\x1a\n"));
+    /// assert_eq!(frame.slice_interned(..), frame);
+    /// ```
+    pub fn slice_interned<R: RangeBounds<usize>>(&self, range: R) -> IBytes {
+        let bounds: (Bound<usize>, Bound<usize>) = (range.start_bound().cloned(), range.end_bound().cloned());
+        let sub = &self.as_bytes()[bounds];
+
+        if sub.len() == self.len() {
+            return self.clone();
+        }
+
+        IBytes::new(sub)
+    }
+
+    /// Id of the pool entry, which can be resolved by `pool::PoolReader`.
+    /// Returns `None` for the inlined bytes as they are not stored in the pool.
+    #[inline]
+    pub fn pool_id(&self) -> Option<usize> {
+        self.0.pool_id()
+    }
+}
+
+macro_rules! read_fixed {
+    ($($le:ident $be:ident -> $ty:ident,)*) => {
+        impl IBytes {
+            #[inline]
+            fn read_array<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+                let end = offset.checked_add(N)?;
+                <[u8; N]>::try_from(self.as_bytes().get(offset..end)?).ok()
+            }
+
+            /// Byte at the offset, or `None` if it's out of bounds.
+            /// Other `read_*` methods read the fixed-size fields likewise,
+            /// without requiring the offset to be aligned.
+            ///
+            /// ```
+            /// # use internship::IBytes;
+            /// let record = IBytes::new(&[0x01, 0x02, 0x03, 0x04, 0x05]);
+            /// assert_eq!(record.read_u8(4), Some(0x05));
+            /// assert_eq!(record.read_u16_le(1), Some(0x0302));
+            /// assert_eq!(record.read_u32_be(1), Some(0x02030405));
+            /// assert_eq!(record.read_u32_le(2), None);
+            /// ```
+            #[inline]
+            pub fn read_u8(&self, offset: usize) -> Option<u8> {
+                self.as_bytes().get(offset).cloned()
+            }
+
+            #[inline]
+            pub fn read_i8(&self, offset: usize) -> Option<i8> {
+                self.read_u8(offset).map(|b| b as i8)
+            }
+
+            $(
+                #[inline]
+                pub fn $le(&self, offset: usize) -> Option<$ty> {
+                    self.read_array(offset).map($ty::from_le_bytes)
+                }
+
+                #[inline]
+                pub fn $be(&self, offset: usize) -> Option<$ty> {
+                    self.read_array(offset).map($ty::from_be_bytes)
+                }
+            )*
+        }
+    };
+}
+
+read_fixed! {
+    read_u16_le read_u16_be -> u16,
+    read_i16_le read_i16_be -> i16,
+    read_u32_le read_u32_be -> u32,
+    read_i32_le read_i32_be -> i32,
+    read_u64_le read_u64_be -> u64,
+    read_i64_le read_i64_be -> i64,
+    read_f32_le read_f32_be -> f32,
+    read_f64_le read_f64_be -> f64,
 }
 
 impl Deref for IBytes {
@@ -50,16 +174,16 @@ impl<'a> From<Cow<'a, [u8]>> for IBytes {
     }
 }
 
-impl<'a> From<&'a [u8]> for IBytes {
+impl From<&[u8]> for IBytes {
     #[inline]
     fn from(v: &[u8]) -> Self {
         IBytes::new(v)
     }
 }
 
-impl<'a> From<&'a mut [u8]> for IBytes {
+impl From<&mut [u8]> for IBytes {
     fn from(v: &mut [u8]) -> Self {
-        IBytes::new(&v)
+        IBytes::new(v)
     }
 }
 
@@ -81,7 +205,7 @@ impl From<String> for IBytes {
     }
 }
 
-impl<'a> From<&'a str> for IBytes {
+impl From<&str> for IBytes {
     fn from(v: &str) -> Self {
         IBytes::new(v.as_bytes())
     }
@@ -99,13 +223,30 @@ impl From<ICStr> for IBytes {
     }
 }
 
+impl TryFrom<IBytes> for IStr {
+    type Error = Utf8Error;
+
+    fn try_from(v: IBytes) -> Result<Self, Utf8Error> {
+        from_utf8(&v)?;
+        Ok(IStr(v.0))
+    }
+}
+
+impl TryFrom<IBytes> for ICStr {
+    type Error = NulError;
+
+    fn try_from(v: IBytes) -> Result<Self, NulError> {
+        v.to_icstr()
+    }
+}
+
 impl PartialEq<Vec<u8>> for IBytes {
     fn eq(&self, other: &Vec<u8>) -> bool {
         PartialEq::eq(self.as_bytes(), &**other)
     }
 }
 
-impl<'a> PartialEq<&'a [u8]> for IBytes {
+impl PartialEq<&[u8]> for IBytes {
     fn eq(&self, other: &&[u8]) -> bool {
         PartialEq::eq(self.as_bytes(), *other)
     }
@@ -168,8 +309,8 @@ impl Index<RangeFull> for IBytes {
     type Output = [u8];
 
     #[inline]
-    fn index(&self, index: RangeFull) -> &[u8] {
-        &self.as_bytes()[index]
+    fn index(&self, _index: RangeFull) -> &[u8] {
+        self.as_bytes()
     }
 }
 
@@ -187,23 +328,43 @@ impl fmt::Debug for IBytes {
 }
 
 #[cfg(feature = "serde-compat")]
-mod serde_compat {
+pub(crate) mod serde_compat {
     use super::*;
-    use serde::{Serialize, Serializer, Deserialize, Deserializer, de};
+    use serde_crate::{Serialize, Serializer, Deserialize, Deserializer, de};
 
     impl Serialize for IBytes {
         fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            // Base64 text keeps JSON documents compact and valid as text.
+            #[cfg(feature = "base64")]
+            {
+                if s.is_human_readable() {
+                    use base64::Engine;
+
+                    let encoded = base64::engine::general_purpose::STANDARD.encode(self.as_bytes());
+                    return s.serialize_str(&encoded);
+                }
+            }
+
             Serialize::serialize(self.as_bytes(), s)
         }
     }
 
     impl<'d> Deserialize<'d> for IBytes {
         fn deserialize<D: Deserializer<'d>>(d: D) -> Result<IBytes, D::Error> {
+            // Human readable formats may pass strings to `visit_bytes` as is,
+            // so let them choose between base64 strings and sequences.
+            #[cfg(feature = "base64")]
+            {
+                if d.is_human_readable() {
+                    return d.deserialize_any(Visitor);
+                }
+            }
+
             d.deserialize_bytes(Visitor)
         }
     }
 
-    pub struct Visitor;
+    pub(crate) struct Visitor;
 
     impl<'d> de::Visitor<'d> for Visitor {
         type Value = IBytes;
@@ -215,5 +376,59 @@ mod serde_compat {
         fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<IBytes, E> {
             Ok(IBytes::new(value))
         }
+
+        /// Formats without byte strings, like JSON, represent them as sequences of integers.
+        fn visit_seq<A: de::SeqAccess<'d>>(self, mut seq: A) -> Result<IBytes, A::Error> {
+            let mut buf = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+
+            while let Some(byte) = seq.next_element()? {
+                buf.push(byte);
+            }
+
+            Ok(IBytes::from(buf))
+        }
+
+        /// Base64 encoded string, only accepted with the `base64` feature.
+        #[cfg(feature = "base64")]
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<IBytes, E> {
+            use base64::Engine;
+
+            base64::engine::general_purpose::STANDARD.decode(value)
+                .map(IBytes::from)
+                .map_err(E::custom)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde-compat"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_seq() {
+        let bytes: IBytes = ::serde_json::from_str("[102, 111, 111]").unwrap();
+        assert_eq!(bytes, IBytes::new(b"foo"));
+        assert!(::serde_json::from_str::<IBytes>("[256]").is_err());
+
+        #[cfg(feature = "base64")]
+        {
+            assert_eq!(::serde_json::from_str::<IBytes>(r#""Zm9v""#).unwrap(), bytes);
+            assert_eq!(::serde_json::to_string(&bytes).unwrap(), r#""Zm9v""#);
+        }
+    }
+
+    #[test]
+    fn test_msgpack() {
+        let bytes = IBytes::new(&[0xde, 0xad, 0xbe, 0xef]);
+        let encoded = ::rmp_serde::to_vec(&bytes).unwrap();
+
+        // Array of the integers, as `[u8]`
+        assert_eq!(encoded, [0x94, 0xcc, 0xde, 0xcc, 0xad, 0xcc, 0xbe, 0xcc, 0xef]);
+        assert_eq!(::rmp_serde::from_slice::<IBytes>(&encoded).unwrap(), bytes);
+
+        let text = IStr::new("interned");
+        let encoded = ::rmp_serde::to_vec(&text).unwrap();
+        assert_eq!(encoded[0], 0xa0 | 8);
+        assert_eq!(::rmp_serde::from_slice::<IStr>(&encoded).unwrap(), text);
     }
 }
@@ -1,16 +1,26 @@
-use std::ffi::{CStr, CString, FromBytesWithNulError};
+use std::ffi::{CStr, CString, FromBytesWithNulError, NulError};
+use std::os::raw::c_char;
+use std::collections::HashSet;
+use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::ops::{Deref, Index, RangeFull};
 use std::hash::{Hash, Hasher};
 use std::borrow::Borrow;
-use std::str::{from_utf8, Utf8Error};
+use std::str::{from_utf8, FromStr, Utf8Error};
+use std::fmt;
 
-use handle::Handle;
+use handle::{self, Handle};
 use ibytes::IBytes;
 use istr::IStr;
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ICStr(pub(crate) Handle);
 
+thread_local! {
+    // Inlined strings have no pool entry to leak, so they're copied here once.
+    static LEAKED_INLINE: RefCell<HashSet<&'static [u8]>> = Default::default();
+}
+
 impl ICStr {
     pub fn new(src: &CStr) -> Self {
         ICStr(Handle::new(src.to_bytes_with_nul()))
@@ -20,11 +30,24 @@ impl ICStr {
         CStr::from_bytes_with_nul(src).map(ICStr::new)
     }
 
+    /// Intern the string with a nul terminator appended,
+    /// or fail if it contains a nul byte in the middle.
+    ///
+    /// ```
+    /// # use internship::ICStr;
+    /// let name = ICStr::try_from_str("dlopen").unwrap();
+    /// assert_eq!(name.as_bytes_with_nul(), b"dlopen\0");
+    ///
+    /// let err = ICStr::try_from_str("nul\0inside").err().unwrap();
+    /// assert_eq!(err.nul_position(), 3);
+    /// ```
+    pub fn try_from_str(src: &str) -> Result<Self, NulError> {
+        CString::new(src).map(ICStr::from)
+    }
+
     #[inline]
     pub fn as_cstr(&self) -> &CStr {
-        unsafe {
-            CStr::from_bytes_with_nul_unchecked(self.0.get())
-        }
+        handle::to_cstr(self.0.get())
     }
 
     /// result slice does *not* contains trailing nul terminator.
@@ -48,6 +71,46 @@ impl ICStr {
     pub fn to_istr(&self) -> Result<IStr, Utf8Error> {
         from_utf8(self.as_bytes()).map(|_| IStr(self.0.clone()))
     }
+
+    /// Keep the string alive for the rest of the program and borrow it for `'static`.
+    /// Inlined strings are copied once per thread, as they have no pool entry.
+    pub fn leak(&self) -> &'static CStr {
+        let bytes = self.0.leak().unwrap_or_else(|| LEAKED_INLINE.with(|leaked| {
+            let mut leaked = leaked.borrow_mut();
+            let bytes = self.as_bytes_with_nul();
+
+            match leaked.get(bytes) {
+                Some(&bytes) => bytes,
+                None => {
+                    let bytes: &'static [u8] = Box::leak(bytes.into());
+                    leaked.insert(bytes);
+                    bytes
+                }
+            }
+        }));
+
+        handle::to_cstr(bytes)
+    }
+
+    /// Pointer to the nul terminated string which stays valid for the rest of the program,
+    /// so the symbol names can be passed to `dlsym`-style APIs repeatedly without `CString`s.
+    /// Calling it again for the same string returns the same pointer.
+    ///
+    /// ```
+    /// # use internship::ICStr;
+    /// # use std::ffi::CStr;
+    /// let symbol = ICStr::try_from_str("sqlite3_open_v2").unwrap();
+    /// let ptr = symbol.as_ptr_static();
+    /// drop(symbol);
+    ///
+    /// let again = ICStr::try_from_str("sqlite3_open_v2").unwrap();
+    /// assert_eq!(again.as_ptr_static(), ptr);
+    /// assert_eq!(unsafe { CStr::from_ptr(ptr) }, &*again);
+    /// ```
+    #[inline]
+    pub fn as_ptr_static(&self) -> *const c_char {
+        self.leak().as_ptr()
+    }
 }
 
 impl Deref for ICStr {
@@ -77,6 +140,54 @@ impl From<Box<CStr>> for ICStr {
     }
 }
 
+impl From<ICStr> for CString {
+    fn from(v: ICStr) -> Self {
+        v.as_cstr().to_owned()
+    }
+}
+
+impl<'a> TryFrom<&'a str> for ICStr {
+    type Error = NulError;
+
+    fn try_from(v: &'a str) -> Result<Self, NulError> {
+        ICStr::try_from_str(v)
+    }
+}
+
+impl TryFrom<String> for ICStr {
+    type Error = NulError;
+
+    fn try_from(v: String) -> Result<Self, NulError> {
+        CString::new(v).map(ICStr::from)
+    }
+}
+
+impl FromStr for ICStr {
+    type Err = NulError;
+
+    fn from_str(s: &str) -> Result<Self, NulError> {
+        ICStr::try_from_str(s)
+    }
+}
+
+impl PartialEq<CStr> for ICStr {
+    fn eq(&self, other: &CStr) -> bool {
+        PartialEq::eq(self.as_cstr(), other)
+    }
+}
+
+impl PartialEq<&CStr> for ICStr {
+    fn eq(&self, other: &&CStr) -> bool {
+        PartialEq::eq(self.as_cstr(), *other)
+    }
+}
+
+impl PartialEq<CString> for ICStr {
+    fn eq(&self, other: &CString) -> bool {
+        PartialEq::eq(self.as_cstr(), &**other)
+    }
+}
+
 impl Default for ICStr {
     #[inline]
     fn default() -> Self {
@@ -112,3 +223,63 @@ impl AsRef<CStr> for ICStr {
         self.as_cstr()
     }
 }
+
+impl fmt::Debug for ICStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_cstr(), f)
+    }
+}
+
+#[cfg(feature = "serde-compat")]
+mod serde_compat {
+    use super::*;
+    use serde_crate::{Serialize, Serializer, Deserialize, Deserializer};
+
+    impl Serialize for ICStr {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            self.as_cstr().serialize(s)
+        }
+    }
+
+    impl<'d> Deserialize<'d> for ICStr {
+        fn deserialize<D: Deserializer<'d>>(d: D) -> Result<ICStr, D::Error> {
+            CString::deserialize(d).map(ICStr::from)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runtime strings are never inlined in the `forbid-unsafe` build.
+    #[cfg(not(feature = "forbid-unsafe"))]
+    #[test]
+    fn test_leak_inline_once() {
+        let short = ICStr::try_from_str("dlsym").unwrap();
+        assert!(short.0.is_inline());
+
+        let leaked = short.leak();
+        assert_eq!(leaked, short.as_cstr());
+        assert_eq!(ICStr::try_from_str("dlsym").unwrap().as_ptr_static(), leaked.as_ptr());
+        assert_eq!(LEAKED_INLINE.with(|leaked| leaked.borrow().len()), 1);
+    }
+
+    #[test]
+    fn test_std_equivalents() {
+        let name = ICStr::try_from_str("a C string long enough to be pooled").unwrap();
+        let owned = CString::from(name.clone());
+
+        assert_eq!(name, owned);
+        assert_eq!(name, owned.as_c_str());
+        assert_eq!(format!("{:?}", name), format!("{:?}", owned));
+    }
+
+    #[cfg(feature = "serde-compat")]
+    #[test]
+    fn test_serde() {
+        let name = ICStr::try_from_str("dlsym").unwrap();
+        let json = ::serde_json::to_string(&name).unwrap();
+        assert_eq!(::serde_json::from_str::<ICStr>(&json).unwrap(), name);
+    }
+}
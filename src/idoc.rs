@@ -0,0 +1,174 @@
+use std::cell::OnceCell;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::fmt;
+
+use istr::IStr;
+use side;
+
+/// Interned document with cached line offsets
+///
+/// The line offset table is computed on the first query and attached to the pool entry,
+/// so every `IDoc` of the same contents shares it until the entry is removed.
+///
+/// ```
+/// # use internship::IDoc;
+/// let doc = IDoc::new("fn main() {\n    println!();\n}\n");
+///
+/// assert_eq!(doc.line_count(), 4);
+/// assert_eq!(doc.line_col(16), Some((1, 4)));
+/// assert_eq!(doc.offset(1, 4), Some(16));
+/// assert_eq!(doc.line(2), Some("}\n"));
+/// ```
+#[derive(Clone)]
+pub struct IDoc {
+    text: IStr,
+    lines: OnceCell<Rc<LineStarts>>,
+}
+
+// Byte offsets of the start of each line.
+struct LineStarts(Vec<usize>);
+
+impl LineStarts {
+    fn new(text: &str) -> Self {
+        let starts = Some(0).into_iter()
+            .chain(text.bytes().enumerate().filter(|&(_, b)| b == b'\n').map(|(idx, _)| idx + 1))
+            .collect();
+        LineStarts(starts)
+    }
+}
+
+impl IDoc {
+    pub fn new(src: &str) -> Self {
+        IDoc::from(IStr::new(src))
+    }
+
+    #[inline]
+    pub fn as_istr(&self) -> &IStr {
+        &self.text
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.text.as_str()
+    }
+
+    fn starts(&self) -> &[usize] {
+        &self.lines.get_or_init(|| {
+            // Inlined documents are too short to be worth caching.
+            if self.text.0.is_inline() {
+                return Rc::new(LineStarts::new(&self.text));
+            }
+
+            side::entry(&self.text).or_insert_with(|| LineStarts::new(&self.text))
+        }).0
+    }
+
+    /// Number of lines. Text after the last newline counts as a line, even if it's empty.
+    pub fn line_count(&self) -> usize {
+        self.starts().len()
+    }
+
+    /// Zero-based line and byte column of the byte offset, or `None` if it's out of bounds.
+    pub fn line_col(&self, offset: usize) -> Option<(usize, usize)> {
+        if offset > self.text.len() {
+            return None;
+        }
+
+        let starts = self.starts();
+        let line = starts.partition_point(|&start| start <= offset) - 1;
+        Some((line, offset - starts[line]))
+    }
+
+    /// Byte offset of the zero-based line and byte column, or `None` if it's out of the line.
+    pub fn offset(&self, line: usize, col: usize) -> Option<usize> {
+        let starts = self.starts();
+        let start = *starts.get(line)?;
+
+        // Only the last line has the position after its end, which is the end of the document.
+        let in_line = match starts.get(line + 1) {
+            Some(&next) => start + col < next,
+            None => start + col <= self.text.len(),
+        };
+
+        if in_line {
+            Some(start + col)
+        } else {
+            None
+        }
+    }
+
+    /// Contents of the zero-based line, including its newline.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let starts = self.starts();
+        let start = *starts.get(line)?;
+        let end = starts.get(line + 1).cloned().unwrap_or_else(|| self.text.len());
+
+        Some(&self.text[start..end])
+    }
+}
+
+impl From<IStr> for IDoc {
+    fn from(text: IStr) -> Self {
+        IDoc {
+            text,
+            lines: OnceCell::new(),
+        }
+    }
+}
+
+impl From<IDoc> for IStr {
+    #[inline]
+    fn from(doc: IDoc) -> Self {
+        doc.text
+    }
+}
+
+impl Deref for IDoc {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for IDoc {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for IDoc {
+    #[inline]
+    fn eq(&self, other: &IDoc) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for IDoc {}
+
+impl fmt::Debug for IDoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.text, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_line_starts() {
+        let src = "first line\nsecond line\nno newline at the end";
+        let a = IDoc::new(src);
+        assert_eq!(a.line_col(src.len()), Some((2, 21)));
+        assert_eq!(a.line_col(src.len() + 1), None);
+
+        let b = IDoc::new(src);
+        assert_eq!(b.line_count(), 3);
+        assert!(Rc::ptr_eq(a.lines.get().unwrap(), b.lines.get().unwrap()));
+        assert_eq!(b.offset(0, 11), None);
+    }
+}
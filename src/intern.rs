@@ -0,0 +1,191 @@
+//! Interning of user types
+//!
+//! `Intern<T>` pools any immutable `T` per thread like `IStr` does for strings,
+//! including unsized ones like `[u32]`. It shares the pools of `HCons`,
+//! so `Intern<T>` and `HCons<T>` of equal values refer to the same entry.
+//!
+//! Unlike `IStr` and `IBytes`, small values are not inlined. Inlined contents
+//! are stored unaligned within the handle, so they can't be borrowed as `&T`.
+
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+use std::fmt;
+
+use hcons;
+
+/// Types which can be interned by `Intern`
+///
+/// It's implemented for every `Clone` type, slices of them and `str`.
+/// Values are compared by their `Eq` and `Hash` to find the pool entry,
+/// so they must be consistent and must not change while interned.
+pub trait AllowIntern: Eq + Hash + 'static {
+    /// Allocate a copy of the value to be stored in the pool.
+    fn to_rc(&self) -> Rc<Self>;
+}
+
+impl<T: Clone + Eq + Hash + 'static> AllowIntern for T {
+    fn to_rc(&self) -> Rc<Self> {
+        Rc::new(self.clone())
+    }
+}
+
+impl<T: Clone + Eq + Hash + 'static> AllowIntern for [T] {
+    fn to_rc(&self) -> Rc<Self> {
+        Rc::from(self)
+    }
+}
+
+impl AllowIntern for str {
+    fn to_rc(&self) -> Rc<Self> {
+        Rc::from(self)
+    }
+}
+
+/// Interned value of user type
+///
+/// Equal values share one allocation per thread, which is removed once its last
+/// handle is dropped. Handles are compared and hashed by their addresses.
+///
+/// ```
+/// # use internship::Intern;
+/// let a: Intern<[u32]> = Intern::new(&[1, 2, 3][..]);
+/// let b: Intern<[u32]> = Intern::new(&vec![1, 2, 3][..]);
+///
+/// assert!(Intern::ptr_eq(&a, &b));
+/// assert_eq!(&*a, &[1, 2, 3]);
+/// assert_eq!(Intern::<[u32]>::pool_len(), 1);
+/// ```
+pub struct Intern<T: AllowIntern + ?Sized>(Option<Rc<T>>);
+
+impl<T: AllowIntern + ?Sized> Intern<T> {
+    pub fn new(value: &T) -> Self {
+        let rc = hcons::with_pool(|pool| match pool.get(value) {
+            Some(rc) => Rc::clone(rc),
+            None => {
+                let rc = value.to_rc();
+                pool.insert(Rc::clone(&rc));
+                rc
+            }
+        });
+
+        Intern(Some(rc))
+    }
+
+    #[inline]
+    fn rc(&self) -> &Rc<T> {
+        self.0.as_ref().expect("dropped interned handle")
+    }
+
+    #[inline]
+    pub fn get(&self) -> &T {
+        self.rc()
+    }
+
+    /// Whether both are the same value. It equals to `==`.
+    #[inline]
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Rc::ptr_eq(a.rc(), b.rc())
+    }
+
+    /// Number of the distinct values of `T` in the current thread.
+    pub fn pool_len() -> usize {
+        hcons::with_pool(|pool: &mut ::std::collections::HashSet<Rc<T>>| pool.len())
+    }
+}
+
+impl<T: AllowIntern + ?Sized> Drop for Intern<T> {
+    fn drop(&mut self) {
+        if let Some(rc) = self.0.take() {
+            hcons::release(rc);
+        }
+    }
+}
+
+impl<T: AllowIntern + ?Sized> Clone for Intern<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Intern(Some(Rc::clone(self.rc())))
+    }
+}
+
+impl<T: AllowIntern + ?Sized> Deref for Intern<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T: AllowIntern + ?Sized> AsRef<T> for Intern<T> {
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T: AllowIntern + ?Sized> Borrow<T> for Intern<T> {
+    #[inline]
+    fn borrow(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<'a, T: AllowIntern + ?Sized> From<&'a T> for Intern<T> {
+    fn from(value: &'a T) -> Self {
+        Intern::new(value)
+    }
+}
+
+impl<T: AllowIntern + ?Sized> PartialEq for Intern<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Intern::ptr_eq(self, other)
+    }
+}
+
+impl<T: AllowIntern + ?Sized> Eq for Intern<T> {}
+
+impl<T: AllowIntern + ?Sized> Hash for Intern<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        Rc::as_ptr(self.rc()).addr().hash(hasher)
+    }
+}
+
+impl<T: AllowIntern + fmt::Debug + ?Sized> fmt::Debug for Intern<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.get(), f)
+    }
+}
+
+impl<T: AllowIntern + fmt::Display + ?Sized> fmt::Display for Intern<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.get(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use HCons;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct Node(u32, Vec<Intern<Node>>);
+
+    #[test]
+    fn test_shared_with_hcons() {
+        let leaf = Intern::new(&Node(1, vec![]));
+        let tree = Intern::new(&Node(0, vec![leaf.clone(), leaf.clone()]));
+        let consed = HCons::new(Node(1, vec![]));
+
+        assert!(::std::ptr::eq(leaf.get(), consed.get()));
+        drop((leaf, consed));
+        assert_eq!(Intern::<Node>::pool_len(), 2);
+
+        drop(tree);
+        assert_eq!(Intern::<Node>::pool_len(), 0);
+    }
+}
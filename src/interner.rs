@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::ops::{Bound, Deref, RangeBounds};
+use std::fmt;
+
+use handle::PoolHasher;
+use istr::IStr;
+
+/// Dense id of a string in the `Interner`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    #[inline]
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Generation of the `Interner`, which starts at `0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Generation(u32);
+
+/// Local interner mapping strings to dense `Symbol`s
+///
+/// Unlike the thread local pool, entries of the `Interner` live until the interner
+/// discards them, and each of them has a dense `Symbol` usable as an index.
+///
+/// Symbols are grouped by the generation they're introduced in.
+/// Starting a new generation with `next_generation` and later calling `retain_generation`
+/// discards every symbol introduced after it, e.g. by a rolled back editing session.
+///
+/// ```
+/// # use internship::Interner;
+/// let mut interner = Interner::new();
+/// let foo = interner.intern("foo");
+///
+/// let base = interner.next_generation();
+/// let bar = interner.intern("bar");
+/// assert_eq!(interner.resolve(bar).unwrap(), "bar");
+///
+/// interner.retain_generation(base);
+/// assert_eq!(interner.resolve(bar), None);
+/// assert_eq!(interner.get("foo"), Some(foo));
+/// ```
+#[derive(Clone, Default)]
+pub struct Interner {
+    strings: Vec<IStr>,
+    symbols: HashMap<IStr, Symbol, PoolHasher>,
+    // Index of the first symbol of each generation after the first.
+    generation_starts: Vec<usize>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner::default()
+    }
+
+    pub(crate) fn with_capacity_and_hasher(capacity: usize, hasher: PoolHasher) -> Self {
+        Interner {
+            strings: Vec::with_capacity(capacity),
+            symbols: HashMap::with_capacity_and_hasher(capacity, hasher),
+            generation_starts: Vec::new(),
+        }
+    }
+
+    pub fn intern(&mut self, src: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(src) {
+            return symbol;
+        }
+
+        self.intern_istr(IStr::new(src))
+    }
+
+    pub fn intern_istr(&mut self, src: IStr) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(&src) {
+            return symbol;
+        }
+
+        assert!(self.strings.len() < u32::MAX as usize, "too many symbols");
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(src.clone());
+        self.symbols.insert(src, symbol);
+        symbol
+    }
+
+    #[inline]
+    pub fn get(&self, src: &str) -> Option<Symbol> {
+        self.symbols.get(src).cloned()
+    }
+
+    #[inline]
+    pub fn resolve(&self, symbol: Symbol) -> Option<&IStr> {
+        self.strings.get(symbol.index())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Iterate over the strings in the order of their symbols.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &IStr)> {
+        self.strings.iter().enumerate().map(|(idx, s)| (Symbol(idx as u32), s))
+    }
+
+    #[inline]
+    pub fn generation(&self) -> Generation {
+        Generation(self.generation_starts.len() as u32)
+    }
+
+    /// Generation in which the symbol is introduced.
+    pub fn generation_of(&self, symbol: Symbol) -> Option<Generation> {
+        if symbol.index() >= self.strings.len() {
+            return None;
+        }
+
+        let gen = self.generation_starts.partition_point(|&start| start <= symbol.index());
+        Some(Generation(gen as u32))
+    }
+
+    /// Start a new generation. Returns the previous one.
+    pub fn next_generation(&mut self) -> Generation {
+        let prev = self.generation();
+        self.generation_starts.push(self.strings.len());
+        prev
+    }
+
+    /// Discard every symbol introduced after `gen`, making `gen` the current generation.
+    /// Discarded symbols may be reused by later interned strings.
+    pub fn retain_generation(&mut self, gen: Generation) {
+        let gen = gen.0 as usize;
+
+        if gen >= self.generation_starts.len() {
+            return;
+        }
+
+        let start = self.generation_starts[gen];
+        for s in self.strings.drain(start..) {
+            self.symbols.remove(&s);
+        }
+        self.generation_starts.truncate(gen);
+    }
+
+    /// Stop interning, building the sorted index for `FrozenInterner::range`.
+    pub fn freeze(self) -> FrozenInterner {
+        let mut sorted: Vec<Symbol> = self.iter().map(|(symbol, _)| symbol).collect();
+        sorted.sort_unstable_by(|&a, &b| self.strings[a.index()].cmp(&self.strings[b.index()]));
+
+        FrozenInterner {
+            interner: self,
+            sorted,
+        }
+    }
+}
+
+/// Read-only `Interner` with lexicographic range queries
+///
+/// It derefs to the `Interner` for lookups.
+///
+/// ```
+/// # use internship::Interner;
+/// let mut interner = Interner::new();
+/// for word in &["abc", "aa", "b", "ab", "aab"] {
+///     interner.intern(word);
+/// }
+/// let frozen = interner.freeze();
+///
+/// let words: Vec<_> = frozen.range("aa".."ab").map(|(_, s)| s.as_str()).collect();
+/// assert_eq!(words, ["aa", "aab"]);
+/// assert_eq!(frozen.range("ab"..).count(), 3);
+/// assert_eq!(frozen.get("b"), Some(frozen.range("b"..="b").next().unwrap().0));
+/// ```
+#[derive(Clone)]
+pub struct FrozenInterner {
+    interner: Interner,
+    // Symbols sorted by their strings.
+    sorted: Vec<Symbol>,
+}
+
+impl FrozenInterner {
+    /// Iterate over the strings within the range in ascending order.
+    pub fn range<'a, R: RangeBounds<&'a str>>(&self, range: R) -> impl Iterator<Item = (Symbol, &IStr)> {
+        let strings = &self.interner.strings;
+        let key = |symbol: &Symbol| strings[symbol.index()].as_str();
+
+        let start = match range.start_bound() {
+            Bound::Included(&start) => self.sorted.partition_point(|s| key(s) < start),
+            Bound::Excluded(&start) => self.sorted.partition_point(|s| key(s) <= start),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => self.sorted.partition_point(|s| key(s) <= end),
+            Bound::Excluded(&end) => self.sorted.partition_point(|s| key(s) < end),
+            Bound::Unbounded => self.sorted.len(),
+        };
+
+        self.sorted[start..end.max(start)].iter().map(move |&symbol| (symbol, &strings[symbol.index()]))
+    }
+
+    /// Make it internable again, dropping the sorted index.
+    pub fn thaw(self) -> Interner {
+        self.interner
+    }
+}
+
+impl Deref for FrozenInterner {
+    type Target = Interner;
+
+    #[inline]
+    fn deref(&self) -> &Interner {
+        &self.interner
+    }
+}
+
+impl fmt::Debug for Interner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl fmt::Debug for FrozenInterner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.interner, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generations() {
+        let mut interner = Interner::new();
+        let a = interner.intern("a");
+        let g0 = interner.next_generation();
+        let b = interner.intern("b");
+        let g1 = interner.next_generation();
+        let c = interner.intern("c");
+
+        assert_eq!(interner.intern("a"), a);
+        assert_eq!(interner.generation_of(a), Some(g0));
+        assert_eq!(interner.generation_of(b), Some(g1));
+        assert_eq!(interner.generation_of(c), Some(interner.generation()));
+
+        interner.retain_generation(g1);
+        assert_eq!(interner.get("c"), None);
+        assert_eq!(interner.get("b"), Some(b));
+        assert_eq!(interner.generation(), g1);
+
+        interner.retain_generation(g0);
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.intern("c"), b);
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use istr::IStr;
+
+/// Inverted index mapping interned terms to sorted lists of `u32` document ids
+///
+/// Terms are kept sorted, so prefix queries are a range of them.
+///
+/// ```
+/// # use internship::InvertedIndexBuilder;
+/// let mut builder = InvertedIndexBuilder::new();
+/// builder.add_doc(0, "the quick brown fox".split(' '));
+/// builder.add_doc(1, "the lazy dog".split(' '));
+/// builder.add_doc(2, "quick quick dog".split(' '));
+/// let index = builder.build();
+///
+/// assert_eq!(index.get("quick"), &[0, 2]);
+/// assert_eq!(index.all(&["the", "dog"]), vec![1]);
+/// assert_eq!(index.any(&["fox", "lazy"]), vec![0, 1]);
+/// assert_eq!(index.prefix("qu").count(), 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InvertedIndex {
+    terms: Vec<IStr>,
+    postings: Vec<Box<[u32]>>,
+}
+
+/// Builder of `InvertedIndex`
+#[derive(Debug, Clone, Default)]
+pub struct InvertedIndexBuilder {
+    postings: HashMap<IStr, Vec<u32>>,
+}
+
+impl InvertedIndexBuilder {
+    pub fn new() -> Self {
+        InvertedIndexBuilder::default()
+    }
+
+    #[inline]
+    pub fn add(&mut self, term: &str, doc: u32) {
+        match self.postings.get_mut(term) {
+            Some(docs) => docs.push(doc),
+            None => {
+                self.postings.insert(IStr::new(term), vec![doc]);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn add_istr(&mut self, term: IStr, doc: u32) {
+        self.postings.entry(term).or_default().push(doc);
+    }
+
+    /// Add every term of the document.
+    pub fn add_doc<I, T>(&mut self, doc: u32, terms: I)
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        for term in terms {
+            self.add(term.as_ref(), doc);
+        }
+    }
+
+    /// Build the index. Documents can be added in any order and more than once per term.
+    pub fn build(self) -> InvertedIndex {
+        let mut entries: Vec<_> = self.postings.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut index = InvertedIndex {
+            terms: Vec::with_capacity(entries.len()),
+            postings: Vec::with_capacity(entries.len()),
+        };
+
+        for (term, mut docs) in entries {
+            docs.sort_unstable();
+            docs.dedup();
+            index.terms.push(term);
+            index.postings.push(docs.into_boxed_slice());
+        }
+
+        index
+    }
+}
+
+impl InvertedIndex {
+    #[inline]
+    fn position(&self, term: &str) -> Option<usize> {
+        self.terms.binary_search_by(|probe| probe.as_str().cmp(term)).ok()
+    }
+
+    /// Sorted ids of the documents with the term, empty if it's not indexed.
+    #[inline]
+    pub fn get(&self, term: &str) -> &[u32] {
+        match self.position(term) {
+            Some(idx) => &self.postings[idx],
+            None => &[],
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, term: &str) -> bool {
+        self.position(term).is_some()
+    }
+
+    /// Sorted ids of the documents with every term.
+    pub fn all<T: AsRef<str>>(&self, terms: &[T]) -> Vec<u32> {
+        let mut lists: Vec<_> = terms.iter().map(|term| self.get(term.as_ref())).collect();
+        lists.sort_by_key(|list| list.len());
+
+        let (first, rest) = match lists.split_first() {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+
+        first.iter().cloned()
+            .filter(|doc| rest.iter().all(|list| list.binary_search(doc).is_ok()))
+            .collect()
+    }
+
+    /// Sorted ids of the documents with any of the terms.
+    pub fn any<T: AsRef<str>>(&self, terms: &[T]) -> Vec<u32> {
+        let mut docs: Vec<u32> = terms.iter()
+            .flat_map(|term| self.get(term.as_ref()).iter().cloned())
+            .collect();
+        docs.sort_unstable();
+        docs.dedup();
+        docs
+    }
+
+    /// Terms starting with the prefix and their documents, in term order.
+    pub fn prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a IStr, &'a [u32])> + 'a {
+        let start = self.terms.partition_point(|term| term.as_str() < prefix);
+
+        self.terms[start..].iter()
+            .zip(&self.postings[start..])
+            .take_while(move |&(term, _)| term.starts_with(prefix))
+            .map(|(term, docs)| (term, &**docs))
+    }
+
+    /// Terms and their documents, in term order.
+    pub fn iter(&self) -> impl Iterator<Item = (&IStr, &[u32])> {
+        self.terms.iter().zip(self.postings.iter().map(|docs| &**docs))
+    }
+
+    /// Indexed terms in order.
+    #[inline]
+    pub fn terms(&self) -> &[IStr] {
+        &self.terms
+    }
+
+    /// Number of the terms.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+impl<'a> Extend<(&'a str, u32)> for InvertedIndexBuilder {
+    fn extend<I: IntoIterator<Item = (&'a str, u32)>>(&mut self, iter: I) {
+        for (term, doc) in iter {
+            self.add(term, doc);
+        }
+    }
+}
+
+impl Extend<(IStr, u32)> for InvertedIndexBuilder {
+    fn extend<I: IntoIterator<Item = (IStr, u32)>>(&mut self, iter: I) {
+        for (term, doc) in iter {
+            self.add_istr(term, doc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries() {
+        let mut builder = InvertedIndexBuilder::new();
+        builder.extend(vec![("b", 3), ("a", 2), ("b", 1), ("ab", 1), ("b", 3), ("c", 9)]);
+        let index = builder.build();
+
+        assert_eq!(index.terms(), &["a", "ab", "b", "c"][..]);
+        assert_eq!(index.get("b"), &[1, 3]);
+        assert_eq!(index.get("z"), &[] as &[u32]);
+        assert!(index.all::<&str>(&[]).is_empty());
+        assert!(index.all(&["a", "b"]).is_empty());
+        assert_eq!(index.all(&["b", "ab"]), vec![1]);
+        assert_eq!(index.any(&["a", "b", "z"]), vec![1, 2, 3]);
+        assert_eq!(index.prefix("a").map(|(term, _)| term.as_str()).collect::<Vec<_>>(), ["a", "ab"]);
+        assert_eq!(index.prefix("d").count(), 0);
+    }
+}
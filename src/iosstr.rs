@@ -0,0 +1,231 @@
+use std::ffi::{OsStr, OsString};
+use std::ops::Deref;
+use std::hash::{Hash, Hasher};
+use std::borrow::{Borrow, Cow};
+use std::fmt;
+
+use handle::{self, Handle};
+use istr::IStr;
+
+/// Interned OS string type
+///
+/// `IOsStr` stores the platform's own encoding of `OsStr`, which is raw bytes on Unix
+/// and WTF-8 on Windows. Conversions from and to `OsStr` are lossless on every platform,
+/// including unpaired surrogates of Windows strings.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IOsStr(pub(crate) Handle);
+
+impl IOsStr {
+    pub fn new(src: &OsStr) -> Self {
+        IOsStr(Handle::new(src.as_encoded_bytes()))
+    }
+
+    #[inline]
+    pub fn as_os_str(&self) -> &OsStr {
+        handle::to_os_str(self.0.get())
+    }
+
+    /// Platform's own encoding of the string. See `OsStr::as_encoded_bytes`.
+    #[inline]
+    pub fn as_encoded_bytes(&self) -> &[u8] {
+        self.0.get()
+    }
+
+    #[inline]
+    pub fn to_str(&self) -> Option<&str> {
+        self.as_os_str().to_str()
+    }
+
+    /// Convert into `IStr` without copying if it's valid unicode.
+    #[inline]
+    pub fn to_istr(&self) -> Option<IStr> {
+        self.to_str().map(|_| IStr(self.0.clone()))
+    }
+
+    #[inline]
+    pub fn to_os_string(&self) -> OsString {
+        self.as_os_str().to_os_string()
+    }
+}
+
+impl IStr {
+    /// Convert into `IOsStr` without copying.
+    #[inline]
+    pub fn to_iosstr(&self) -> IOsStr {
+        IOsStr(self.0.clone())
+    }
+}
+
+impl Deref for IOsStr {
+    type Target = OsStr;
+
+    #[inline]
+    fn deref(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl From<&OsStr> for IOsStr {
+    fn from(v: &OsStr) -> Self {
+        IOsStr::new(v)
+    }
+}
+
+impl From<OsString> for IOsStr {
+    fn from(v: OsString) -> Self {
+        IOsStr::new(&v)
+    }
+}
+
+impl From<&str> for IOsStr {
+    fn from(v: &str) -> Self {
+        IOsStr::new(v.as_ref())
+    }
+}
+
+impl From<String> for IOsStr {
+    fn from(v: String) -> Self {
+        IOsStr::new(v.as_ref())
+    }
+}
+
+impl<'a> From<Cow<'a, OsStr>> for IOsStr {
+    fn from(v: Cow<'a, OsStr>) -> Self {
+        IOsStr::new(&v)
+    }
+}
+
+impl From<IStr> for IOsStr {
+    #[inline]
+    fn from(v: IStr) -> Self {
+        IOsStr(v.0)
+    }
+}
+
+impl From<IOsStr> for OsString {
+    fn from(v: IOsStr) -> Self {
+        v.to_os_string()
+    }
+}
+
+impl PartialEq<OsStr> for IOsStr {
+    fn eq(&self, other: &OsStr) -> bool {
+        PartialEq::eq(self.as_os_str(), other)
+    }
+}
+
+impl PartialEq<&OsStr> for IOsStr {
+    fn eq(&self, other: &&OsStr) -> bool {
+        PartialEq::eq(self.as_os_str(), *other)
+    }
+}
+
+impl PartialEq<OsString> for IOsStr {
+    fn eq(&self, other: &OsString) -> bool {
+        PartialEq::eq(self.as_os_str(), &**other)
+    }
+}
+
+impl PartialEq<str> for IOsStr {
+    fn eq(&self, other: &str) -> bool {
+        PartialEq::eq(self.as_os_str(), other)
+    }
+}
+
+impl PartialEq<&str> for IOsStr {
+    fn eq(&self, other: &&str) -> bool {
+        PartialEq::eq(self.as_os_str(), *other)
+    }
+}
+
+impl Default for IOsStr {
+    #[inline]
+    fn default() -> Self {
+        IOsStr::new(OsStr::new(""))
+    }
+}
+
+impl Hash for IOsStr {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.as_os_str().hash(hasher)
+    }
+}
+
+impl Borrow<OsStr> for IOsStr {
+    #[inline]
+    fn borrow(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl AsRef<OsStr> for IOsStr {
+    #[inline]
+    fn as_ref(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl fmt::Debug for IOsStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_os_str(), f)
+    }
+}
+
+// Serialized in the platform dependent form of `OsStr`.
+#[cfg(feature = "serde-compat")]
+mod serde_compat {
+    use super::*;
+    use serde_crate::{Serialize, Serializer, Deserialize, Deserializer};
+
+    impl Serialize for IOsStr {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            self.as_os_str().serialize(s)
+        }
+    }
+
+    impl<'d> Deserialize<'d> for IOsStr {
+        fn deserialize<D: Deserializer<'d>>(d: D) -> Result<IOsStr, D::Error> {
+            OsString::deserialize(d).map(IOsStr::from)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lossless() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let raw = OsStr::from_bytes(b"invalid \xFF utf-8 bytes in the path");
+        let s = IOsStr::new(raw);
+
+        assert_eq!(s.as_os_str(), raw);
+        assert_eq!(s, IOsStr::from(raw.to_os_string()));
+        assert_eq!(s.to_istr(), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_lossless() {
+        use std::os::windows::ffi::{OsStringExt, OsStrExt};
+
+        let wide = [0x61, 0xD800, 0x62];
+        let raw = OsString::from_wide(&wide);
+        let s = IOsStr::new(&raw);
+
+        assert_eq!(s.encode_wide().collect::<Vec<_>>(), wide);
+        assert_eq!(OsString::from(s.clone()), raw);
+        assert_eq!(s.to_istr(), None);
+    }
+
+    #[test]
+    fn test_unicode() {
+        let s = IOsStr::from("foo");
+
+        assert_eq!(s.to_istr().unwrap(), "foo");
+        assert_eq!(IStr::new("foo").to_iosstr(), s);
+    }
+}
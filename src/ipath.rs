@@ -0,0 +1,245 @@
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::ops::Deref;
+use std::hash::{Hash, Hasher};
+use std::borrow::{Borrow, Cow};
+use std::fmt;
+
+use handle::{self, Handle};
+use istr::IStr;
+use iosstr::IOsStr;
+
+/// Interned path type
+///
+/// `IPath` shares the representation of `IOsStr`, thus conversions between them are free.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IPath(pub(crate) Handle);
+
+impl IPath {
+    pub fn new<P: AsRef<Path> + ?Sized>(src: &P) -> Self {
+        IPath(Handle::new(src.as_ref().as_os_str().as_encoded_bytes()))
+    }
+
+    #[inline]
+    pub fn as_path(&self) -> &Path {
+        Path::new(self.as_os_str())
+    }
+
+    #[inline]
+    pub fn as_os_str(&self) -> &OsStr {
+        handle::to_os_str(self.0.get())
+    }
+
+    #[inline]
+    pub fn to_iosstr(&self) -> IOsStr {
+        IOsStr(self.0.clone())
+    }
+
+    #[inline]
+    pub fn to_path_buf(&self) -> PathBuf {
+        self.as_path().to_path_buf()
+    }
+}
+
+impl IOsStr {
+    #[inline]
+    pub fn to_ipath(&self) -> IPath {
+        IPath(self.0.clone())
+    }
+}
+
+impl Deref for IPath {
+    type Target = Path;
+
+    #[inline]
+    fn deref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl From<&Path> for IPath {
+    fn from(v: &Path) -> Self {
+        IPath::new(v)
+    }
+}
+
+impl From<PathBuf> for IPath {
+    fn from(v: PathBuf) -> Self {
+        IPath::new(&v)
+    }
+}
+
+impl From<&str> for IPath {
+    fn from(v: &str) -> Self {
+        IPath::new(v)
+    }
+}
+
+impl From<String> for IPath {
+    fn from(v: String) -> Self {
+        IPath::new(&v)
+    }
+}
+
+impl From<OsString> for IPath {
+    fn from(v: OsString) -> Self {
+        IPath::new(&v)
+    }
+}
+
+impl<'a> From<Cow<'a, Path>> for IPath {
+    fn from(v: Cow<'a, Path>) -> Self {
+        IPath::new(&v)
+    }
+}
+
+impl From<IOsStr> for IPath {
+    #[inline]
+    fn from(v: IOsStr) -> Self {
+        IPath(v.0)
+    }
+}
+
+impl From<IStr> for IPath {
+    #[inline]
+    fn from(v: IStr) -> Self {
+        IPath(v.0)
+    }
+}
+
+impl From<IPath> for PathBuf {
+    fn from(v: IPath) -> Self {
+        v.to_path_buf()
+    }
+}
+
+impl PartialEq<Path> for IPath {
+    fn eq(&self, other: &Path) -> bool {
+        PartialEq::eq(self.as_path(), other)
+    }
+}
+
+impl PartialEq<&Path> for IPath {
+    fn eq(&self, other: &&Path) -> bool {
+        PartialEq::eq(self.as_path(), *other)
+    }
+}
+
+impl PartialEq<PathBuf> for IPath {
+    fn eq(&self, other: &PathBuf) -> bool {
+        PartialEq::eq(self.as_path(), &**other)
+    }
+}
+
+impl Default for IPath {
+    #[inline]
+    fn default() -> Self {
+        IPath::new("")
+    }
+}
+
+impl Hash for IPath {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.as_path().hash(hasher)
+    }
+}
+
+impl Borrow<Path> for IPath {
+    #[inline]
+    fn borrow(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl AsRef<Path> for IPath {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl AsRef<OsStr> for IPath {
+    #[inline]
+    fn as_ref(&self) -> &OsStr {
+        self.as_os_str()
+    }
+}
+
+impl AsRef<Path> for IOsStr {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        Path::new(self.as_os_str())
+    }
+}
+
+impl AsRef<OsStr> for IStr {
+    #[inline]
+    fn as_ref(&self) -> &OsStr {
+        OsStr::new(self.as_str())
+    }
+}
+
+impl AsRef<Path> for IStr {
+    #[inline]
+    fn as_ref(&self) -> &Path {
+        Path::new(self.as_str())
+    }
+}
+
+impl fmt::Debug for IPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_path(), f)
+    }
+}
+
+// Serialized as a string like `Path`, which fails if it's not valid unicode.
+#[cfg(feature = "serde-compat")]
+mod serde_compat {
+    use super::*;
+    use serde_crate::{Serialize, Serializer, Deserialize, Deserializer};
+
+    impl Serialize for IPath {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            self.as_path().serialize(s)
+        }
+    }
+
+    impl<'d> Deserialize<'d> for IPath {
+        fn deserialize<D: Deserializer<'d>>(d: D) -> Result<IPath, D::Error> {
+            PathBuf::deserialize(d).map(IPath::from)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_command() {
+        let program = IStr::new("cargo");
+        let dir = IPath::new("/tmp");
+        let arg = IOsStr::from("--version");
+
+        let mut cmd = Command::new(&program);
+        cmd.arg(&arg).arg(&dir).env(&program, &arg).current_dir(&dir);
+
+        let args: Vec<_> = cmd.get_args().collect();
+        assert_eq!(args, [OsStr::new("--version"), OsStr::new("/tmp")]);
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("/tmp")));
+        assert_eq!(dir.to_iosstr().to_ipath(), dir);
+    }
+
+    #[cfg(feature = "serde-compat")]
+    #[test]
+    fn test_serde() {
+        let dir = IPath::from(String::from("/usr/local/share/applications"));
+        let arg = IOsStr::from("--verbose");
+
+        assert_eq!(::serde_json::to_string(&dir).unwrap(), r#""/usr/local/share/applications""#);
+        assert_eq!(::serde_json::from_str::<IPath>(r#""/usr/local/share/applications""#).unwrap(), dir);
+        assert_eq!(dir, PathBuf::from("/usr/local/share/applications"));
+        assert_eq!(::serde_json::from_str::<IOsStr>(&::serde_json::to_string(&arg).unwrap()).unwrap(), "--verbose");
+    }
+}
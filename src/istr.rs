@@ -1,14 +1,20 @@
 use std::ops::{Deref, Index, Range, RangeFrom, RangeTo, RangeFull};
 use std::borrow::{Cow, Borrow};
-use std::string::ParseError;
+use std::string::{ParseError, FromUtf16Error};
 use std::cmp::PartialEq;
 use std::hash::{Hash, Hasher};
 use std::str::{self, FromStr, from_utf8, Utf8Error};
-use std::fmt;
+use std::fmt::{self, Write};
+use std::cell::Cell;
+use std::iter::FromIterator;
+use std::convert::TryInto;
+use std::mem;
+use std::char;
 use std::net::ToSocketAddrs;
 
-use handle::Handle;
+use handle::{self, Handle, INLINE_MAX_LEN};
 use ibytes::IBytes;
+use pool::BudgetExceeded;
 
 /// Interned string type
 ///
@@ -16,23 +22,233 @@ use ibytes::IBytes;
 ///
 /// Conceptually, `IStr` is similar to `Rc<str>` as both are immutable and zero-copy shareable.
 /// But `IStr` is interned, and inlined if small enough.
+///
+/// Equality compares the handles in constant time, as equal contents share one handle
+/// within a thread. Hashing uses the contents so maps keyed by `IStr` can be looked up
+/// by `&str`. Use `ById` for keys hashed in constant time.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct IStr(pub(crate) Handle);
 
 impl IStr {
+    /// Max length of the strings stored inline without the pool,
+    /// `2 * size_of::<usize>() - 1` bytes, e.g. 15 on 64-bit and 7 on 32-bit targets.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let max = "x".repeat(IStr::MAX_INLINE_LEN);
+    /// assert!(IStr::new_inline(&max).is_some());
+    /// assert!(IStr::new_inline(&(max + "x")).is_none());
+    /// ```
+    pub const MAX_INLINE_LEN: usize = INLINE_MAX_LEN;
+
+    /// `"true"`
+    pub const TRUE: IStr = IStr(Handle::new_inline(b"true"));
+    /// `"false"`
+    pub const FALSE: IStr = IStr(Handle::new_inline(b"false"));
+    /// `"null"`
+    pub const NULL: IStr = IStr(Handle::new_inline(b"null"));
+    /// `""`
+    pub const EMPTY: IStr = IStr(Handle::new_inline(b""));
+
     pub fn new(src: &str) -> Self {
         IStr(Handle::new(src.as_bytes()))
     }
 
+    /// Like `new`, but fails instead of adding a new pool entry over the budget of its class.
+    /// Strings already in the pool or short enough to be inlined always succeed.
+    /// See `pool::register_class` for the classes.
+    pub fn try_new(src: &str) -> Result<Self, BudgetExceeded> {
+        Handle::try_new(src.as_bytes())
+            .map(IStr)
+            .map_err(|class| BudgetExceeded { class })
+    }
+
+    /// Create an inlined string, or `None` if it's too long to be inlined.
+    /// It never touches the pool nor allocates, so it's safe to use in signal handlers.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// assert_eq!(IStr::new_inline("short").unwrap(), "short");
+    /// assert!(IStr::new_inline("Lorem ipsum dolor sit amet").is_none());
+    /// ```
+    #[inline]
+    pub const fn new_inline(src: &str) -> Option<Self> {
+        if src.len() > INLINE_MAX_LEN {
+            None
+        } else {
+            Some(IStr(Handle::new_inline(src.as_bytes())))
+        }
+    }
+
+    /// Create an inlined string in `const` context. Panics, at compile time if evaluated
+    /// in `const` context, if it's too long to be inlined. See `istr!` for longer strings.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// const OK: IStr = IStr::const_inline("ok");
+    /// assert_eq!(OK, "ok");
+    /// ```
+    #[inline]
+    pub const fn const_inline(src: &str) -> Self {
+        assert!(src.len() <= INLINE_MAX_LEN, "string is too long to be inlined");
+        IStr(Handle::new_inline(src.as_bytes()))
+    }
+
+    /// Check whether both are the same interned string by comparing their handles,
+    /// which is cheaper than comparing the contents. It equals to `==`.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let value = IStr::new("null");
+    /// assert!(value.is(&IStr::NULL));
+    /// assert!(!value.is(&IStr::FALSE));
+    /// ```
+    #[inline]
+    pub fn is(&self, atom: &IStr) -> bool {
+        self.0 == atom.0
+    }
+
+    /// Check whether `prefix` is a prefix of this string.
+    ///
+    /// Equal handles are the same string, and distinct handles of the same length
+    /// are never equal within a thread, so those cases don't touch the contents.
+    /// Otherwise the contents are compared a word at a time.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let path = IStr::new("/api/v1/users");
+    /// assert!(path.starts_with_istr(&IStr::new("/api/")));
+    /// assert!(path.starts_with_istr(&path.clone()));
+    /// assert!(!path.starts_with_istr(&IStr::new("/api/v2/users")));
+    /// ```
+    #[inline]
+    pub fn starts_with_istr(&self, prefix: &IStr) -> bool {
+        match self.affix_len(prefix) {
+            Some(len) => words_eq(&self.as_bytes()[..len], prefix.as_bytes()),
+            None => self.0 == prefix.0,
+        }
+    }
+
+    /// Check whether `suffix` is a suffix of this string. See `starts_with_istr`.
+    #[inline]
+    pub fn ends_with_istr(&self, suffix: &IStr) -> bool {
+        match self.affix_len(suffix) {
+            Some(len) => words_eq(&self.as_bytes()[self.len() - len..], suffix.as_bytes()),
+            None => self.0 == suffix.0,
+        }
+    }
+
+    // Length of the affix if its contents need to be compared.
+    #[inline]
+    fn affix_len(&self, affix: &IStr) -> Option<usize> {
+        let len = affix.len();
+
+        if len < self.len() {
+            Some(len)
+        } else {
+            None
+        }
+    }
+
     pub fn from_utf8(src: &[u8]) -> Result<Self, Utf8Error> {
         from_utf8(src).map(IStr::new)
     }
 
+    /// Like `from_utf8`, but replaces invalid sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    /// Valid input is interned without copying to a `String`.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// assert_eq!(IStr::from_utf8_lossy(b"Hello \xF0\x90\x80World"), "Hello \u{FFFD}World");
+    /// ```
+    pub fn from_utf8_lossy(src: &[u8]) -> Self {
+        IStr::new(&String::from_utf8_lossy(src))
+    }
+
+    /// Decode UTF-16 into the interned string, failing on unpaired surrogates.
+    /// Short strings are decoded on stack without allocation.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// let music = [0xD834, 0xDD1E, 0x006d, 0x0075, 0x0073, 0x0069, 0x0063];
+    /// assert_eq!(IStr::from_utf16(&music).unwrap(), "𝄞music");
+    /// assert!(IStr::from_utf16(&[0xD834, 0x006d]).is_err());
+    ///
+    /// let long: Vec<u16> = "a string longer than the stack buffer, ".repeat(4).encode_utf16().collect();
+    /// assert_eq!(IStr::from_utf16(&long).unwrap(), String::from_utf16(&long).unwrap());
+    /// ```
+    pub fn from_utf16(src: &[u16]) -> Result<Self, FromUtf16Error> {
+        match decode_utf16(src, false) {
+            Some(value) => Ok(value),
+            None => String::from_utf16(src).map(IStr::from),
+        }
+    }
+
+    /// Like `from_utf16`, but replaces unpaired surrogates with `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// assert_eq!(IStr::from_utf16_lossy(&[0xD834, 0x006d]), "\u{FFFD}m");
+    /// ```
+    pub fn from_utf16_lossy(src: &[u16]) -> Self {
+        match decode_utf16(src, true) {
+            Some(value) => value,
+            None => IStr::from(String::from_utf16_lossy(src)),
+        }
+    }
+
+    /// Intern the formatted string, built in the scratch buffer of the thread
+    /// instead of a new `String`. See `format_istr!`.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// # use std::fmt;
+    /// struct Nested;
+    ///
+    /// impl fmt::Display for Nested {
+    ///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    ///         f.write_str(&IStr::from_fmt(format_args!("{}-{}", "inner", 1)))
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(IStr::from_fmt(format_args!("outer[{}]", Nested)), "outer[inner-1]");
+    /// assert_eq!("abc".chars().rev().collect::<IStr>(), "cba");
+    /// ```
+    pub fn from_fmt(args: fmt::Arguments) -> Self {
+        if let Some(literal) = args.as_str() {
+            return IStr::new(literal);
+        }
+
+        with_scratch(|buf| {
+            buf.write_fmt(args).expect("a Display implementation returned an error unexpectedly")
+        })
+    }
+
+    /// Decimal representation of `n`, formatted on stack without allocation.
+    /// Values up to `MAX_INLINE_LEN` digits long are inlined, so they never touch the pool.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// assert_eq!(IStr::from_u64(42), "42");
+    /// assert_eq!(IStr::from_u64(u64::MAX), u64::MAX.to_string());
+    /// assert_eq!(IStr::from_i64(i64::MIN), i64::MIN.to_string());
+    /// ```
+    pub fn from_u64(n: u64) -> Self {
+        format_u64(n, false)
+    }
+
+    /// Decimal representation of `n`. See `from_u64`.
+    pub fn from_i64(n: i64) -> Self {
+        if n >= 0 {
+            IStr::from_u64(n as u64)
+        } else {
+            format_u64(n.unsigned_abs(), true)
+        }
+    }
+
     #[inline]
     pub fn as_str(&self) -> &str {
-        unsafe {
-            str::from_utf8_unchecked(self.0.get())
-        }
+        handle::to_str(self.0.get())
     }
 
     #[inline]
@@ -44,6 +260,188 @@ impl IStr {
     pub fn to_ibytes(&self) -> IBytes {
         IBytes(self.0.clone())
     }
+
+    /// Id of the pool entry, which can be resolved by `pool::PoolReader`.
+    /// Returns `None` for the inlined strings as they are not stored in the pool.
+    #[inline]
+    pub fn pool_id(&self) -> Option<usize> {
+        self.0.pool_id()
+    }
+
+    /// The same string with the user tag of 0 to 7, which is copied to its clones.
+    ///
+    /// The tag is stored in the spare bits of the handle, so parsers can mark tokens
+    /// without a side table. It's ignored by comparison and hashing.
+    /// Panics if the tag is larger than 7.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// const KEYWORD: u8 = 1;
+    ///
+    /// let token = IStr::new("while").with_tag(KEYWORD);
+    /// assert_eq!(token.clone().tag(), KEYWORD);
+    /// assert_eq!(token, IStr::new("while"));
+    /// ```
+    #[inline]
+    pub fn with_tag(mut self, tag: u8) -> Self {
+        self.0.set_tag(tag);
+        self
+    }
+
+    #[inline]
+    pub fn tag(&self) -> u8 {
+        self.0.tag()
+    }
+
+    /// Whether it's allocated outside of the pool as the pool was unavailable.
+    /// See `pool::is_available()`.
+    #[inline]
+    pub fn is_unpooled(&self) -> bool {
+        self.0.is_unpooled()
+    }
+
+    /// Keep the string in the pool even if every `IStr` of it is dropped, until `unpin()`.
+    /// It makes lookups of the finite set of hot strings, like the uniform names
+    /// queried every frame, never allocate again. Inlined strings are never in the pool,
+    /// so it's no-op for them.
+    ///
+    /// ```
+    /// # use internship::{IStr, pool};
+    /// let name = "u_model_view_projection_matrix";
+    /// IStr::new(name).pin();
+    /// let id = IStr::new(name).pool_id();
+    /// assert_eq!(IStr::new(name).pool_id(), id);
+    ///
+    /// assert!(IStr::new(name).unpin());
+    /// assert!(!pool::entries().iter().any(|entry| entry == name.as_bytes()));
+    /// ```
+    #[inline]
+    pub fn pin(&self) {
+        self.0.pin()
+    }
+
+    /// Release the pin so the string is removed from the pool with its last `IStr`.
+    /// Returns `false` if it's not pinned, or leaked as it can't be unpinned.
+    #[inline]
+    pub fn unpin(&self) -> bool {
+        self.0.unpin()
+    }
+
+    /// Whether the string is kept in the pool without any `IStr`, by `pin()` or `leak()`.
+    ///
+    /// An entry cloned so many times that its refcount would overflow is leaked
+    /// instead, after which clones and drops of it don't count anymore.
+    #[inline]
+    pub fn is_pinned(&self) -> bool {
+        self.0.is_pinned()
+    }
+
+    /// Keep the string in the pool for the rest of the program and borrow it for `'static`.
+    /// Returns `None` for the inlined strings as they are not stored in the pool.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// # use std::borrow::Cow;
+    /// let name = IStr::new("a name long enough to be pooled");
+    /// assert!(matches!(Cow::from(&name), Cow::Owned(_)));
+    ///
+    /// let leaked: &'static str = name.leak().unwrap();
+    /// assert_eq!(name, leaked);
+    /// assert!(matches!(Cow::from(name), Cow::Borrowed(_)));
+    /// ```
+    pub fn leak(&self) -> Option<&'static str> {
+        self.0.leak().map(handle::to_str)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new_unpooled(src: &str) -> Self {
+        IStr(Handle::unpooled(src.as_bytes()))
+    }
+
+    /// The string borrowed for `'static` if it's leaked by `leak()`.
+    pub fn as_static(&self) -> Option<&'static str> {
+        self.0.as_static().map(handle::to_str)
+    }
+}
+
+thread_local! {
+    static SCRATCH: Cell<String> = const { Cell::new(String::new()) };
+}
+
+fn format_u64(mut n: u64, negative: bool) -> IStr {
+    // 20 digits of u64::MAX and the sign.
+    let mut buf = [0u8; 21];
+    let mut pos = buf.len();
+
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+
+    if negative {
+        pos -= 1;
+        buf[pos] = b'-';
+    }
+
+    IStr::new(handle::to_str(&buf[pos..]))
+}
+
+// Compare the slices of the same length by words, then the remaining bytes.
+// Affixes are mostly short, where it beats the call to `memcmp`.
+#[inline]
+fn words_eq(a: &[u8], b: &[u8]) -> bool {
+    const WORD: usize = mem::size_of::<usize>();
+    debug_assert_eq!(a.len(), b.len());
+
+    let (a_words, b_words) = (a.chunks_exact(WORD), b.chunks_exact(WORD));
+    let rest_eq = a_words.remainder() == b_words.remainder();
+
+    rest_eq && a_words.zip(b_words).all(|(a, b)| {
+        usize::from_ne_bytes(a.try_into().unwrap()) == usize::from_ne_bytes(b.try_into().unwrap())
+    })
+}
+
+// Larger buffers are freed instead of being kept for the next use.
+const SCRATCH_MAX_CAPACITY: usize = 64 * 1024;
+
+// Build the string in the thread's scratch buffer and intern it. Nested calls,
+// e.g. from the `Display` impls being formatted, get their own buffer.
+fn with_scratch<F: FnOnce(&mut String)>(build: F) -> IStr {
+    let mut buf = SCRATCH.try_with(Cell::take).unwrap_or_default();
+    build(&mut buf);
+    let value = IStr::new(&buf);
+
+    if buf.capacity() <= SCRATCH_MAX_CAPACITY {
+        buf.clear();
+        let _ = SCRATCH.try_with(|scratch| scratch.set(mem::take(&mut buf)));
+    }
+
+    value
+}
+
+// Decode into the stack buffer. `None` if it's invalid and not `lossy`, or too long for the buffer.
+fn decode_utf16(src: &[u16], lossy: bool) -> Option<IStr> {
+    let mut buf = [0u8; 64];
+    let mut pos = 0;
+
+    for ch in char::decode_utf16(src.iter().cloned()) {
+        let ch = match ch {
+            Ok(ch) => ch,
+            Err(_) if lossy => char::REPLACEMENT_CHARACTER,
+            Err(_) => return None,
+        };
+
+        if pos + ch.len_utf8() > buf.len() {
+            return None;
+        }
+        pos += ch.encode_utf8(&mut buf[pos..]).len();
+    }
+
+    Some(IStr::new(handle::to_str(&buf[..pos])))
 }
 
 impl Deref for IStr {
@@ -55,13 +453,25 @@ impl Deref for IStr {
     }
 }
 
+impl FromIterator<char> for IStr {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        with_scratch(|buf| buf.extend(iter))
+    }
+}
+
+impl<'a> FromIterator<&'a str> for IStr {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        with_scratch(|buf| buf.extend(iter))
+    }
+}
+
 impl From<String> for IStr {
     fn from(v: String) -> Self {
         IStr::new(&v)
     }
 }
 
-impl<'a> From<&'a str> for IStr {
+impl From<&str> for IStr {
     fn from(v: &str) -> Self {
         IStr::new(v)
     }
@@ -79,6 +489,22 @@ impl<'a> From<Cow<'a, str>> for IStr {
     }
 }
 
+/// Borrowed if the string is leaked, copied otherwise.
+impl From<IStr> for Cow<'static, str> {
+    fn from(v: IStr) -> Self {
+        Cow::from(&v)
+    }
+}
+
+impl From<&IStr> for Cow<'static, str> {
+    fn from(v: &IStr) -> Self {
+        match v.as_static() {
+            Some(v) => Cow::Borrowed(v),
+            None => Cow::Owned(v.as_str().into()),
+        }
+    }
+}
+
 impl<'a> PartialEq<Cow<'a, str>> for IStr {
     fn eq(&self, other: &Cow<str>) -> bool {
         PartialEq::eq(self.as_str(), other)
@@ -91,7 +517,7 @@ impl PartialEq<String> for IStr {
     }
 }
 
-impl<'a> PartialEq<&'a str> for IStr {
+impl PartialEq<&str> for IStr {
     fn eq(&self, other: &&str) -> bool {
         PartialEq::eq(self.as_str(), *other)
     }
@@ -154,8 +580,8 @@ impl Index<RangeFull> for IStr {
     type Output = str;
 
     #[inline]
-    fn index(&self, index: RangeFull) -> &str {
-        &self.as_str()[index]
+    fn index(&self, _index: RangeFull) -> &str {
+        self.as_str()
     }
 }
 
@@ -204,16 +630,21 @@ impl ToSocketAddrs for IStr {
 #[cfg(feature = "serde-compat")]
 mod serde_compat {
     use super::*;
-    use serde::{Serialize, Serializer, Deserialize, Deserializer, de};
+    use serde_crate::{Serialize, Serializer, Deserialize, Deserializer, de};
+    use dedup;
 
     impl Serialize for IStr {
         fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
-            Serialize::serialize(self.as_str(), s)
+            dedup::serialize(self, s)
         }
     }
 
     impl<'d> Deserialize<'d> for IStr {
         fn deserialize<D: Deserializer<'d>>(d: D) -> Result<IStr, D::Error> {
+            if dedup::is_reading() {
+                return dedup::deserialize(d);
+            }
+
             d.deserialize_str(Visitor)
         }
     }
@@ -232,3 +663,87 @@ mod serde_compat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(src: &str) -> Vec<u16> {
+        src.encode_utf16().collect()
+    }
+
+    #[test]
+    fn test_utf16_buffer_boundary() {
+        let exact = utf16(&"a".repeat(64));
+        assert!(decode_utf16(&exact, false).is_some());
+        assert_eq!(IStr::from_utf16(&exact).unwrap(), "a".repeat(64));
+
+        let over = utf16(&"a".repeat(65));
+        assert!(decode_utf16(&over, false).is_none());
+        assert_eq!(IStr::from_utf16(&over).unwrap(), "a".repeat(65));
+        assert_eq!(IStr::from_utf16_lossy(&over), "a".repeat(65));
+
+        // The pair takes 4 bytes from the 62nd, 2 bytes over the buffer.
+        let straddle = format!("{}\u{1D11E}", "a".repeat(62));
+        assert!(decode_utf16(&utf16(&straddle), false).is_none());
+        assert_eq!(IStr::from_utf16(&utf16(&straddle)).unwrap(), straddle);
+        assert_eq!(IStr::from_utf16_lossy(&utf16(&straddle)), straddle);
+    }
+
+    #[test]
+    fn test_utf16_unpaired_surrogates() {
+        let short = [0x0061, 0xDD1E, 0x0062];
+        assert!(IStr::from_utf16(&short).is_err());
+        assert_eq!(IStr::from_utf16_lossy(&short), "a\u{FFFD}b");
+
+        // Lone high surrogate at the end of the input.
+        let mut long = utf16(&"a".repeat(70));
+        long.push(0xD834);
+        assert!(decode_utf16(&long, true).is_none());
+        assert!(IStr::from_utf16(&long).is_err());
+        assert_eq!(IStr::from_utf16_lossy(&long), format!("{}\u{FFFD}", "a".repeat(70)));
+    }
+
+    #[test]
+    fn test_integer_edges() {
+        for &(ref value, text) in &[
+            (IStr::from_u64(0), "0"),
+            (IStr::from_i64(0), "0"),
+            (IStr::from_u64(u64::MAX), "18446744073709551615"),
+            (IStr::from_i64(i64::MAX), "9223372036854775807"),
+            (IStr::from_i64(i64::MIN), "-9223372036854775808"),
+        ] {
+            assert_eq!(value, text);
+            assert_eq!(value.pool_id().is_none(), handle::interns_inline(text.len()));
+        }
+    }
+
+    #[test]
+    fn test_affix_istr() {
+        let heap = IStr::new("/api/v1/users/profile");
+        let inline = IStr::new("/api/");
+        let empty = IStr::new("");
+        assert!(heap.pool_id().is_some());
+        assert_eq!(inline.pool_id().is_none(), handle::interns_inline(inline.len()));
+
+        assert!(heap.starts_with_istr(&inline));
+        assert!(heap.starts_with_istr(&IStr::new("/api/v1/users/")));
+        assert!(!heap.starts_with_istr(&IStr::new("/api/v2/users/")));
+        assert!(heap.ends_with_istr(&IStr::new("/profile")));
+        assert!(heap.ends_with_istr(&IStr::new("v1/users/profile")));
+        assert!(!heap.ends_with_istr(&IStr::new("v2/users/profile")));
+        assert!(inline.starts_with_istr(&IStr::new("/a")));
+        assert!(!inline.ends_with_istr(&heap));
+
+        // Same length, by identity
+        assert!(heap.starts_with_istr(&heap.clone()));
+        assert!(!heap.ends_with_istr(&IStr::new("/api/v1/users/profilE")));
+        assert!(heap.starts_with_istr(&IStr::new_unpooled("/api/v1/users/profile")));
+
+        for value in &[&heap, &inline, &empty] {
+            assert!(value.starts_with_istr(&empty));
+            assert!(value.ends_with_istr(&empty));
+        }
+        assert!(!empty.starts_with_istr(&inline));
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::hash::{Hash, Hasher};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+use istr::IStr;
+
+/// Interned hierarchical key
+///
+/// `IKeyPath` is an interned list of interned segments, like `a.b.c` or JSON pointer `/a/b/c`.
+/// Same paths share the same list within a thread, so equality check is a pointer comparison.
+///
+/// ```
+/// # use internship::IKeyPath;
+/// let path = IKeyPath::parse_dotted("server.http.port");
+///
+/// assert_eq!(path, IKeyPath::parse_pointer("/server/http/port").unwrap());
+/// assert_eq!(path.parent().unwrap().to_dotted(), "server.http");
+/// assert_eq!(path.parent().unwrap().child("port"), path);
+/// ```
+pub struct IKeyPath(Rc<[IStr]>);
+
+thread_local! {
+    static POOL: RefCell<HashSet<Rc<[IStr]>>> = Default::default();
+}
+
+/// Error returned when the JSON pointer is malformed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPointer;
+
+impl IKeyPath {
+    pub fn from_segments(segments: &[IStr]) -> Self {
+        let cached = POOL.with(|pool| pool.borrow().get(segments).cloned());
+
+        let rc = match cached {
+            Some(rc) => rc,
+            None => {
+                let rc: Rc<[IStr]> = Rc::from(segments);
+                POOL.with(|pool| pool.borrow_mut().insert(Rc::clone(&rc)));
+                rc
+            }
+        };
+
+        IKeyPath(rc)
+    }
+
+    /// Path with no segments.
+    pub fn root() -> Self {
+        IKeyPath::from_segments(&[])
+    }
+
+    /// Parse `a.b.c` style path. Empty string is the root.
+    pub fn parse_dotted(src: &str) -> Self {
+        if src.is_empty() {
+            return IKeyPath::root();
+        }
+
+        let segments: Vec<_> = src.split('.').map(IStr::new).collect();
+        IKeyPath::from_segments(&segments)
+    }
+
+    /// Parse JSON pointer of RFC 6901. Empty string is the root.
+    pub fn parse_pointer(src: &str) -> Result<Self, InvalidPointer> {
+        if src.is_empty() {
+            return Ok(IKeyPath::root());
+        }
+
+        if !src.starts_with('/') {
+            return Err(InvalidPointer);
+        }
+
+        let segments = src[1..].split('/')
+            .map(|seg| {
+                if !seg.contains('~') {
+                    return Ok(IStr::new(seg));
+                }
+
+                let mut buf = String::with_capacity(seg.len());
+                let mut chars = seg.chars();
+
+                while let Some(c) = chars.next() {
+                    match c {
+                        '~' => match chars.next() {
+                            Some('0') => buf.push('~'),
+                            Some('1') => buf.push('/'),
+                            _ => return Err(InvalidPointer),
+                        },
+                        c => buf.push(c),
+                    }
+                }
+
+                Ok(IStr::from(buf))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(IKeyPath::from_segments(&segments))
+    }
+
+    #[inline]
+    pub fn segments(&self) -> &[IStr] {
+        &self.0
+    }
+
+    /// Number of segments.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    pub fn last(&self) -> Option<&IStr> {
+        self.0.last()
+    }
+
+    /// Path without the last segment, or `None` if this is the root.
+    pub fn parent(&self) -> Option<IKeyPath> {
+        match self.0.len() {
+            0 => None,
+            len => Some(IKeyPath::from_segments(&self.0[..len - 1])),
+        }
+    }
+
+    pub fn child(&self, segment: &str) -> IKeyPath {
+        self.child_istr(IStr::new(segment))
+    }
+
+    pub fn child_istr(&self, segment: IStr) -> IKeyPath {
+        let mut segments = Vec::with_capacity(self.0.len() + 1);
+        segments.extend_from_slice(&self.0);
+        segments.push(segment);
+        IKeyPath::from_segments(&segments)
+    }
+
+    /// Whether `prefix` is an ancestor of this path or the same path.
+    pub fn starts_with(&self, prefix: &IKeyPath) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+
+    pub fn to_dotted(&self) -> String {
+        let mut buf = String::new();
+
+        for (idx, seg) in self.0.iter().enumerate() {
+            if idx > 0 {
+                buf.push('.');
+            }
+            buf.push_str(seg);
+        }
+
+        buf
+    }
+
+    pub fn to_pointer(&self) -> String {
+        let mut buf = String::new();
+
+        for seg in self.0.iter() {
+            buf.push('/');
+            buf.push_str(&seg.replace('~', "~0").replace('/', "~1"));
+        }
+
+        buf
+    }
+}
+
+impl Drop for IKeyPath {
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.0) == 2 {
+            let _ = POOL.try_with(|pool| {
+                pool.borrow_mut().remove(&*self.0);
+            });
+        }
+    }
+}
+
+impl Clone for IKeyPath {
+    #[inline]
+    fn clone(&self) -> Self {
+        IKeyPath(Rc::clone(&self.0))
+    }
+}
+
+impl PartialEq for IKeyPath {
+    #[inline]
+    fn eq(&self, other: &IKeyPath) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for IKeyPath {}
+
+impl PartialOrd for IKeyPath {
+    #[inline]
+    fn partial_cmp(&self, other: &IKeyPath) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IKeyPath {
+    #[inline]
+    fn cmp(&self, other: &IKeyPath) -> Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl Hash for IKeyPath {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.0.hash(hasher)
+    }
+}
+
+impl Default for IKeyPath {
+    fn default() -> Self {
+        IKeyPath::root()
+    }
+}
+
+impl fmt::Debug for IKeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.0.iter()).finish()
+    }
+}
+
+impl fmt::Display for IKeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_pointer())
+    }
+}
+
+impl fmt::Display for InvalidPointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid JSON pointer")
+    }
+}
+
+impl Error for InvalidPointer {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_escape() {
+        let path = IKeyPath::parse_pointer("/a~1b/m~0n/~01").unwrap();
+        assert_eq!(path.segments(), ["a/b", "m~n", "~1"]);
+        assert_eq!(path.to_pointer(), "/a~1b/m~0n/~01");
+
+        let path = IKeyPath::from_segments(&[IStr::new("~/"), IStr::new("")]);
+        assert_eq!(path.to_pointer(), "/~0~1/");
+        assert_eq!(IKeyPath::parse_pointer(&path.to_pointer()).unwrap(), path);
+        assert_eq!(IKeyPath::parse_pointer("/").unwrap().segments(), [""]);
+    }
+
+    #[test]
+    fn test_invalid_pointer() {
+        for src in &["a/b", "/a~", "/a~2", "/~a", "/a/~/b"] {
+            assert_eq!(IKeyPath::parse_pointer(src), Err(InvalidPointer), "{}", src);
+        }
+
+        assert_eq!(InvalidPointer.to_string(), "invalid JSON pointer");
+        assert!(IKeyPath::parse_pointer("").unwrap().is_root());
+    }
+}
@@ -0,0 +1,277 @@
+use std::ops::Deref;
+use std::str::FromStr;
+use std::error::Error;
+use std::fmt;
+
+use istr::IStr;
+
+/// Interned BCP 47 language tag
+///
+/// Tags are validated against the syntax of RFC 5646 and canonicalized by its case conventions
+/// before interning: language in lowercase, script in titlecase, region in uppercase.
+/// Thus equal tags share the same handle regardless of the case of input.
+///
+/// ```
+/// # use internship::ILangTag;
+/// let tag = ILangTag::new("ZH-hant-tw").unwrap();
+///
+/// assert_eq!(tag.as_str(), "zh-Hant-TW");
+/// assert_eq!(tag.script(), Some("Hant"));
+/// assert!(tag.matches_range("zh-Hant"));
+/// assert!(!tag.matches_range("zh-Hans"));
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ILangTag(IStr);
+
+/// Error returned when the language tag is malformed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidLangTag;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Pos {
+    Language,
+    ExtLang,
+    Script,
+    Region,
+    Variant,
+    Extension,
+    PrivateUse,
+}
+
+impl ILangTag {
+    pub fn new(src: &str) -> Result<Self, InvalidLangTag> {
+        let mut buf = String::with_capacity(src.len());
+        let mut pos = Pos::Language;
+        let mut extlangs = 0;
+        let mut after_singleton = false;
+
+        for (idx, subtag) in src.split('-').enumerate() {
+            let len = subtag.len();
+
+            if len == 0 || len > 8 || !subtag.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                return Err(InvalidLangTag);
+            }
+
+            let alpha = subtag.bytes().all(|b| b.is_ascii_alphabetic());
+            let digit = subtag.bytes().all(|b| b.is_ascii_digit());
+
+            if idx > 0 {
+                buf.push('-');
+            }
+
+            if idx == 0 {
+                if subtag.eq_ignore_ascii_case("x") {
+                    pos = Pos::PrivateUse;
+                    after_singleton = true;
+                } else if !alpha || len == 1 {
+                    return Err(InvalidLangTag);
+                }
+
+                buf.extend(subtag.chars().map(|c| c.to_ascii_lowercase()));
+                continue;
+            }
+
+            if after_singleton {
+                // Extension singleton should be followed by a subtag longer than 1.
+                if len == 1 && pos == Pos::Extension {
+                    return Err(InvalidLangTag);
+                }
+                after_singleton = false;
+            }
+
+            let next = if pos == Pos::PrivateUse {
+                Pos::PrivateUse
+            } else if len == 1 {
+                after_singleton = true;
+                if subtag.eq_ignore_ascii_case("x") { Pos::PrivateUse } else { Pos::Extension }
+            } else if pos == Pos::Extension {
+                if len < 2 {
+                    return Err(InvalidLangTag);
+                }
+                Pos::Extension
+            } else if pos <= Pos::ExtLang && alpha && len == 3 && extlangs < 3 {
+                extlangs += 1;
+                Pos::ExtLang
+            } else if pos <= Pos::ExtLang && alpha && len == 4 {
+                Pos::Script
+            } else if pos <= Pos::Script && ((alpha && len == 2) || (digit && len == 3)) {
+                Pos::Region
+            } else if len >= 5 || (len == 4 && subtag.as_bytes()[0].is_ascii_digit()) {
+                Pos::Variant
+            } else {
+                return Err(InvalidLangTag);
+            };
+
+            match next {
+                Pos::Script => {
+                    buf.push(subtag.as_bytes()[0].to_ascii_uppercase() as char);
+                    buf.extend(subtag[1..].chars().map(|c| c.to_ascii_lowercase()));
+                }
+                Pos::Region => buf.extend(subtag.chars().map(|c| c.to_ascii_uppercase())),
+                _ => buf.extend(subtag.chars().map(|c| c.to_ascii_lowercase())),
+            }
+
+            pos = next;
+        }
+
+        if after_singleton {
+            return Err(InvalidLangTag);
+        }
+
+        Ok(ILangTag(IStr::new(&buf)))
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    #[inline]
+    pub fn as_istr(&self) -> &IStr {
+        &self.0
+    }
+
+    #[inline]
+    pub fn into_istr(self) -> IStr {
+        self.0
+    }
+
+    /// Primary language subtag.
+    pub fn language(&self) -> &str {
+        self.as_str().split('-').next().unwrap()
+    }
+
+    pub fn script(&self) -> Option<&str> {
+        self.as_str().split('-').skip(1)
+            .take_while(|s| s.len() > 1)
+            .find(|s| s.len() == 4 && s.as_bytes()[0].is_ascii_uppercase())
+    }
+
+    pub fn region(&self) -> Option<&str> {
+        self.as_str().split('-').skip(1)
+            .take_while(|s| s.len() > 1)
+            .find(|s| (s.len() == 2 && s.bytes().all(|b| b.is_ascii_uppercase()))
+                || (s.len() == 3 && s.bytes().all(|b| b.is_ascii_digit())))
+    }
+
+    /// Basic filtering of RFC 4647. `range` matches if it's `*`,
+    /// or it equals to or is a prefix of this tag ending at subtag boundary, ignoring case.
+    pub fn matches_range(&self, range: &str) -> bool {
+        let tag = self.as_str();
+
+        range == "*" || (tag.len() >= range.len()
+            && tag.as_bytes()[..range.len()].eq_ignore_ascii_case(range.as_bytes())
+            && (tag.len() == range.len() || tag.as_bytes()[range.len()] == b'-'))
+    }
+
+    /// Truncated tag for lookup fallback of RFC 4647, or `None` if nothing to truncate.
+    pub fn parent(&self) -> Option<ILangTag> {
+        let tag = self.as_str();
+        let mut end = tag.rfind('-')?;
+
+        // Singleton should not be the last subtag.
+        if end >= 2 && tag.as_bytes()[end - 2] == b'-' {
+            end -= 2;
+        }
+
+        // Nor the only one, e.g. of the private use tag `x-foo`.
+        if end == 1 {
+            return None;
+        }
+
+        Some(ILangTag(IStr::new(&tag[..end])))
+    }
+}
+
+impl FromStr for ILangTag {
+    type Err = InvalidLangTag;
+
+    fn from_str(src: &str) -> Result<Self, InvalidLangTag> {
+        ILangTag::new(src)
+    }
+}
+
+impl Deref for ILangTag {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ILangTag {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<ILangTag> for IStr {
+    #[inline]
+    fn from(v: ILangTag) -> IStr {
+        v.0
+    }
+}
+
+impl fmt::Debug for ILangTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ILangTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for InvalidLangTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid language tag")
+    }
+}
+
+impl Error for InvalidLangTag {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical() {
+        let cases = [
+            ("EN-us", "en-US"),
+            ("sr-latn-rs", "sr-Latn-RS"),
+            ("es-419", "es-419"),
+            ("de-CH-1901", "de-CH-1901"),
+            ("en-US-u-CA-gregory", "en-US-u-ca-gregory"),
+            ("x-Whatever", "x-whatever"),
+            ("de-u-co-phonebk-t-zh", "de-u-co-phonebk-t-zh"),
+            ("zh-yue-HK", "zh-yue-HK"),
+        ];
+
+        for &(src, expected) in cases.iter() {
+            assert_eq!(ILangTag::new(src).unwrap().as_str(), expected);
+        }
+
+        for src in &["", "e", "en-", "en-u", "en-u-t-foo", "en-US-x", "toolongtag", "en_US"] {
+            assert_eq!(ILangTag::new(src), Err(InvalidLangTag), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_parent() {
+        let tag = ILangTag::new("en-Latn-US-u-ca-gregory").unwrap();
+        let chain: Vec<_> = ::std::iter::successors(Some(tag), ILangTag::parent)
+            .map(|tag| tag.to_string())
+            .collect();
+
+        assert_eq!(chain, ["en-Latn-US-u-ca-gregory", "en-Latn-US-u-ca", "en-Latn-US", "en-Latn", "en"]);
+
+        let private = ILangTag::new("x-foo-bar").unwrap();
+        assert_eq!(private.parent().unwrap().as_str(), "x-foo");
+        assert_eq!(private.parent().unwrap().parent(), None);
+        assert_eq!(ILangTag::new("en-x-foo").unwrap().parent().unwrap().as_str(), "en");
+    }
+}
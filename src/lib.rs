@@ -21,20 +21,268 @@
 //! This crate exposes a set of interned types which correspond to `Rc`
 //! but guaranteed to be unique over its value within thread.
 //! Instances of them are per-thread cached to archive this goal.
+//! The pools live in the thread local storage, so the crate requires `std`.
 //!
 //! Additionally, these types does not heap-allocate small data that can be fit on stack.
 //! Size limit of inline-able data is 15 bytes on 64-byte machines.
 //!
 //! `IStr`, `IBytes`, and `ICStr` correspond to `str`, `[u8]`, and `CStr` respectively.
+//!
+//! # Forbidding unsafe code
+//!
+//! With the `forbid-unsafe` feature, the handles of every interned type are built on
+//! plain `Rc<[u8]>` without any `unsafe` code, and the crate is compiled with
+//! `#![forbid(unsafe_code)]`. The types and their methods are the same, but it differs
+//! from the default build in a few ways:
+//!
+//! - Handles are larger, and small data is interned in the pool like any other.
+//!   Only the constants like `IStr::TRUE` keep their contents in the handle, and they
+//!   have their own ids like the unpooled handles, while still comparing equal.
+//! - `leak()` borrows a copy of the entry which is never freed, not the entry itself.
+//! - Refcounts of the entries never saturate, so they're never leaked by cloning.
+//! - `RawHandle` is not available, and the `tower`, `blob`, `epoch` and `numa`
+//!   features can't be enabled with it.
+#![cfg_attr(feature = "forbid-unsafe", forbid(unsafe_code))]
+
+#[cfg(all(feature = "forbid-unsafe", any(feature = "tower", feature = "blob",
+    feature = "epoch", feature = "numa")))]
+compile_error!("the `tower`, `blob`, `epoch` and `numa` features require unsafe code, \
+    so they can't be enabled with `forbid-unsafe`");
 
 #[cfg(feature = "serde-compat")]
-extern crate serde;
+extern crate serde as serde_crate;
+#[cfg(all(test, feature = "serde-compat"))]
+extern crate serde_json;
+#[cfg(all(test, feature = "serde-compat"))]
+extern crate rmp_serde;
+#[cfg(all(test, feature = "parquet"))]
+extern crate bytes;
+#[cfg(feature = "csv")]
+extern crate csv as csv_crate;
+#[cfg(feature = "arrow")]
+extern crate arrow_array;
+#[cfg(feature = "arrow")]
+extern crate arrow_buffer;
+#[cfg(feature = "arrow")]
+extern crate arrow_schema;
+#[cfg(feature = "parquet")]
+extern crate parquet_crate;
+#[cfg(feature = "rayon")]
+extern crate rayon as rayon_crate;
+#[cfg(feature = "tower")]
+extern crate http;
+#[cfg(feature = "tower")]
+extern crate tower_layer;
+#[cfg(feature = "tower")]
+extern crate tower_service;
+#[cfg(feature = "url")]
+extern crate url as url_crate;
+#[cfg(feature = "blob")]
+extern crate blake3;
+#[cfg(feature = "blob")]
+extern crate lz4_flex;
+#[cfg(all(feature = "numa", target_os = "linux"))]
+extern crate libc;
+#[cfg(feature = "epoch")]
+extern crate crossbeam_epoch;
+#[cfg(feature = "html")]
+extern crate html_escape;
+#[cfg(feature = "encoding")]
+extern crate encoding_rs;
+#[cfg(feature = "async-graphql")]
+extern crate async_graphql;
+#[cfg(feature = "juniper")]
+extern crate juniper;
+#[cfg(feature = "base64")]
+extern crate base64;
+#[cfg(feature = "clap")]
+extern crate clap;
+#[cfg(feature = "toml")]
+extern crate toml;
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+#[cfg(feature = "regex")]
+extern crate regex;
+#[cfg(feature = "crc32")]
+extern crate crc32fast;
+#[cfg(feature = "xxh3")]
+extern crate xxhash_rust;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+/// Interned string literal, cached per thread
+///
+/// Short literals are inlined at compile time, and longer ones are interned
+/// once per thread at the first evaluation of each call site.
+/// The argument must be a constant expression.
+///
+/// ```
+/// # #[macro_use] extern crate internship;
+/// # fn main() {
+/// let short = istr!("ok");
+/// let long = istr!("a literal long enough to be pooled");
+///
+/// assert_eq!(short, "ok");
+/// assert_eq!(long, internship::IStr::new("a literal long enough to be pooled"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! istr {
+    ($value:expr) => {{
+        const INLINE: ::std::option::Option<$crate::IStr> = $crate::IStr::new_inline($value);
+
+        match INLINE {
+            Some(v) => v,
+            None => {
+                thread_local! {
+                    static VALUE: $crate::IStr = $crate::IStr::new($value);
+                }
+
+                VALUE.with(Clone::clone)
+            }
+        }
+    }};
+}
+
+/// Create an interned string with the formatting syntax of `format!`.
+///
+/// It's built in a reusable scratch buffer of the thread instead of a new `String`,
+/// so formatting keys in hot loops doesn't allocate except for new pool entries.
+///
+/// ```
+/// # #[macro_use] extern crate internship;
+/// # fn main() {
+/// let id = 42;
+/// let key = format_istr!("user:{}:session", id);
+///
+/// assert_eq!(key, "user:42:session");
+/// assert_eq!(key, format_istr!("user:{}:{}", id, "session"));
+///
+/// let joined: internship::IStr = ["a", "b", "c"].iter().cloned().collect();
+/// assert_eq!(joined, "abc");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! format_istr {
+    ($($arg:tt)*) => {
+        $crate::IStr::from_fmt(format_args!($($arg)*))
+    };
+}
 
 mod handle;
 mod istr;
 mod ibytes;
 mod icstr;
+mod iosstr;
+mod ipath;
+mod map;
+mod vocab;
+mod inverted;
+mod send_istr;
+mod header_name;
+mod media_type;
+mod dns_name;
+mod version;
+mod lang_tag;
+#[cfg(feature = "url")]
+mod url;
+mod key_path;
+mod decode;
+mod escape;
+mod case;
+mod soft;
+mod memo;
+mod lru;
+mod hcons;
+mod intern;
+#[cfg(not(feature = "forbid-unsafe"))]
+mod raw;
+mod side;
+mod interner;
+mod algo;
+mod column;
+mod trie;
+mod diff;
+mod idoc;
+mod glob;
+mod command;
+#[cfg(feature = "regex")]
+mod regex_cache;
+#[cfg(any(feature = "crc32", feature = "xxh3"))]
+mod checksum;
+#[cfg(feature = "serde-compat")]
+mod value;
+#[cfg(feature = "serde-compat")]
+mod dedup;
+#[cfg(any(feature = "async-graphql", feature = "juniper"))]
+mod graphql;
+#[cfg(feature = "blob")]
+mod blob;
+pub mod shared;
+pub mod pool;
+pub mod template;
+pub mod atoms;
+pub mod env;
+pub mod record;
+pub mod nanbox;
+#[cfg(feature = "serde-compat")]
+pub mod serde;
+
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "rayon")]
+pub mod rayon;
+#[cfg(feature = "tower")]
+pub mod middleware;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+#[cfg(feature = "reference")]
+pub mod reference;
+#[cfg(feature = "differential")]
+pub mod differential;
 
 pub use istr::IStr;
 pub use ibytes::IBytes;
 pub use icstr::ICStr;
+pub use iosstr::IOsStr;
+pub use ipath::IPath;
+pub use map::{InternMapExt, StrEntry, VacantStrEntry, KeyRef};
+pub use vocab::{Vocab, VocabBuilder};
+pub use inverted::{InvertedIndex, InvertedIndexBuilder};
+pub use send_istr::SendIStr;
+pub use soft::SoftIStr;
+pub use header_name::{IHeaderName, InvalidHeaderName};
+pub use media_type::{IMediaType, InvalidMediaType, Params};
+pub use dns_name::{IDnsName, InvalidDnsName, DnsAddr};
+pub use version::{IVersion, InvalidVersion};
+pub use lang_tag::{ILangTag, InvalidLangTag};
+#[cfg(feature = "url")]
+pub use url::IUrl;
+#[cfg(feature = "blob")]
+pub use blob::{IBlob, BlobRef, ContentHash};
+pub use key_path::{IKeyPath, InvalidPointer};
+pub use decode::{InvalidEscape, InvalidPercentEncoding};
+pub use memo::{InternMemo, ById};
+pub use lru::InternLru;
+pub use hcons::HCons;
+pub use intern::{Intern, AllowIntern};
+#[cfg(not(feature = "forbid-unsafe"))]
+pub use raw::RawHandle;
+pub use interner::{Interner, FrozenInterner, Symbol, Generation};
+pub use algo::{sort_interned, group_by_interned, GroupBy};
+pub use column::{EncodedColumn, encode_column, encode_column_rle};
+pub use trie::InternTrie;
+pub use diff::{diff, Patch, Hunk, PatchError};
+pub use idoc::IDoc;
+pub use glob::{GlobSet, InvalidGlob};
+pub use command::CommandBuilder;
+#[cfg(feature = "serde-compat")]
+pub use value::{IValue, IValueMap};
+#[cfg(feature = "serde-compat")]
+pub use dedup::Dedup;
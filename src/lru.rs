@@ -0,0 +1,203 @@
+use std::collections::{HashMap, BTreeMap};
+use std::fmt;
+
+use istr::IStr;
+use memo::ById;
+
+struct Slot<V> {
+    value: V,
+    weight: usize,
+    tick: u64,
+}
+
+/// Least recently used cache keyed by `IStr`
+///
+/// Keys are hashed and compared by their handles like `InternMemo`.
+/// The cache holds entries up to its capacity, which counts entries by default
+/// or their sizes with `weigh_by`. The least recently used entries are evicted first.
+///
+/// ```
+/// # use internship::{IStr, InternLru};
+/// let mut cache = InternLru::new(64).weigh_by(|key, value: &String| key.len() + value.len());
+/// let (a, b) = (IStr::new("alpha"), IStr::new("beta"));
+///
+/// cache.insert(a.clone(), "x".repeat(30));
+/// cache.insert(b.clone(), "y".repeat(20));
+/// assert!(cache.get(&a).is_some());
+///
+/// // `b` is the least recently used one.
+/// cache.insert(IStr::new("gamma"), "z".repeat(10));
+/// assert!(!cache.contains(&b));
+/// assert_eq!(cache.weight(), 35 + 15);
+/// ```
+pub struct InternLru<V> {
+    map: HashMap<ById, Slot<V>>,
+    recency: BTreeMap<u64, ById>,
+    next_tick: u64,
+    weight: usize,
+    capacity: usize,
+    weigher: fn(&IStr, &V) -> usize,
+}
+
+impl<V> InternLru<V> {
+    /// Cache holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        InternLru {
+            map: HashMap::new(),
+            recency: BTreeMap::new(),
+            next_tick: 0,
+            weight: 0,
+            capacity,
+            weigher: |_, _| 1,
+        }
+    }
+
+    /// Weigh each entry by `f`, so the capacity limits the total of the weights.
+    /// Call it before inserting any entry.
+    pub fn weigh_by(mut self, f: fn(&IStr, &V) -> usize) -> Self {
+        assert!(self.map.is_empty(), "weigher should be set on empty cache");
+        self.weigher = f;
+        self
+    }
+
+    #[inline]
+    fn tick(&mut self) -> u64 {
+        self.next_tick += 1;
+        self.next_tick
+    }
+
+    /// Get the value, marking it as the most recently used one.
+    pub fn get(&mut self, key: &IStr) -> Option<&V> {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut(&mut self, key: &IStr) -> Option<&mut V> {
+        let tick = self.tick();
+        let slot = self.map.get_mut(&ById(key.clone()))?;
+
+        let key = self.recency.remove(&slot.tick).expect("recency of the entry");
+        self.recency.insert(tick, key);
+        slot.tick = tick;
+
+        Some(&mut slot.value)
+    }
+
+    /// Get the value without updating its recency.
+    pub fn peek(&self, key: &IStr) -> Option<&V> {
+        self.map.get(&ById(key.clone())).map(|slot| &slot.value)
+    }
+
+    #[inline]
+    pub fn contains(&self, key: &IStr) -> bool {
+        self.map.contains_key(&ById(key.clone()))
+    }
+
+    /// Insert the value as the most recently used one, returning the previous one if any.
+    /// Entries are evicted until the cache fits in its capacity, which may include the new one.
+    pub fn insert(&mut self, key: IStr, value: V) -> Option<V> {
+        let prev = self.remove(&key);
+
+        let tick = self.tick();
+        let weight = (self.weigher)(&key, &value);
+        let key = ById(key);
+
+        self.weight += weight;
+        self.recency.insert(tick, key.clone());
+        self.map.insert(key, Slot { value, weight, tick });
+
+        while self.weight > self.capacity {
+            if self.pop_lru().is_none() {
+                break;
+            }
+        }
+
+        prev
+    }
+
+    pub fn remove(&mut self, key: &IStr) -> Option<V> {
+        let slot = self.map.remove(&ById(key.clone()))?;
+        self.recency.remove(&slot.tick);
+        self.weight -= slot.weight;
+        Some(slot.value)
+    }
+
+    /// Remove the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(IStr, V)> {
+        let tick = *self.recency.keys().next()?;
+        let key = self.recency.remove(&tick)?;
+        let slot = self.map.remove(&key).expect("entry of the recency");
+        self.weight -= slot.weight;
+        Some((key.0, slot.value))
+    }
+
+    /// Total weight of the entries, which is the number of them unless `weigh_by` is used.
+    #[inline]
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the capacity, evicting entries if needed.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.weight > self.capacity {
+            if self.pop_lru().is_none() {
+                break;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.recency.clear();
+        self.weight = 0;
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for InternLru<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // From the most recently used one.
+        f.debug_map()
+            .entries(self.recency.values().rev().map(|key| (&key.0, &self.map[key].value)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_order() {
+        let keys: Vec<_> = ["a", "b", "c"].iter().map(|k| IStr::new(k)).collect();
+        let mut cache = InternLru::new(2);
+
+        cache.insert(keys[0].clone(), 0);
+        cache.insert(keys[1].clone(), 1);
+        assert_eq!(cache.get(&keys[0]), Some(&0));
+        assert_eq!(cache.insert(keys[2].clone(), 2), None);
+
+        assert_eq!(cache.peek(&keys[1]), None);
+        assert_eq!(cache.pop_lru(), Some((keys[0].clone(), 0)));
+        assert_eq!(cache.insert(keys[2].clone(), 3), Some(2));
+        assert_eq!((cache.len(), cache.weight()), (1, 1));
+
+        cache.set_capacity(0);
+        assert!(cache.is_empty() && cache.recency.is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+//! Map extensions
+//!
+//! Lookup helpers for maps keyed by `IStr`.
+//! Lookups only borrow the given `&str`, and the key is interned only when it's inserted.
+
+use std::collections::{HashMap, BTreeMap};
+use std::hash::{Hash, Hasher, BuildHasher};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::fmt;
+
+use istr::IStr;
+
+mod sealed {
+    use istr::IStr;
+
+    /// Insertion of the key known to be vacant, which only the map types provide
+    pub trait InsertVacant<V> {
+        fn insert_vacant(&mut self, key: IStr, value: V) -> &mut V;
+    }
+}
+
+use self::sealed::InsertVacant;
+
+/// Extension methods for `HashMap<IStr, V>` and `BTreeMap<IStr, V>`
+///
+/// It's sealed, so it can't be implemented for other types.
+pub trait InternMapExt<V>: InsertVacant<V> {
+    fn get_str(&self, key: &str) -> Option<&V>;
+
+    fn get_str_mut(&mut self, key: &str) -> Option<&mut V>;
+
+    #[inline]
+    fn contains_str(&self, key: &str) -> bool {
+        self.get_str(key).is_some()
+    }
+
+    /// Insert value with interned key. Returns the previous value if any.
+    fn insert_str(&mut self, key: &str, value: V) -> Option<V>;
+
+    /// Get the entry of the given key. The key is not interned unless the entry is vacant
+    /// and a value is inserted into it.
+    fn entry_str<'a>(&'a mut self, key: &'a str) -> StrEntry<'a, Self, V> where Self: Sized {
+        if self.contains_str(key) {
+            StrEntry::Occupied(self.get_str_mut(key).unwrap())
+        } else {
+            StrEntry::Vacant(VacantStrEntry {
+                map: self,
+                key,
+                _marker: PhantomData,
+            })
+        }
+    }
+
+    /// Lookup with either `&str` or `&IStr`.
+    #[inline]
+    fn get_key<'k, K: Into<KeyRef<'k>>>(&self, key: K) -> Option<&V> {
+        self.get_str(key.into().as_str())
+    }
+
+    /// Insert with either `&str` or `&IStr`. `IStr` key is reused without interning again.
+    fn insert_key<'k, K: Into<KeyRef<'k>>>(&mut self, key: K, value: V) -> Option<V> {
+        match key.into() {
+            KeyRef::Str(key) => self.insert_str(key, value),
+            KeyRef::IStr(key) => match self.get_str_mut(key) {
+                Some(slot) => Some(::std::mem::replace(slot, value)),
+                None => {
+                    self.insert_vacant(key.clone(), value);
+                    None
+                }
+            },
+        }
+    }
+}
+
+/// Borrowed key which is either `&str` or `&IStr`
+///
+/// It hashes and compares same as `str`, so code can take both key types
+/// through `Into<KeyRef>` and look up maps keyed by `IStr` with it.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use internship::{IStr, InternMapExt, KeyRef};
+/// fn count<'a, K: Into<KeyRef<'a>>>(map: &HashMap<IStr, u32>, key: K) -> u32 {
+///     map.get_key(key).cloned().unwrap_or(0)
+/// }
+///
+/// let mut map = HashMap::new();
+/// map.insert_key(&IStr::new("foo"), 3);
+///
+/// assert_eq!(count(&map, "foo"), 3);
+/// assert_eq!(count(&map, &IStr::new("foo")), 3);
+/// ```
+#[derive(Clone, Copy)]
+pub enum KeyRef<'a> {
+    Str(&'a str),
+    IStr(&'a IStr),
+}
+
+impl<'a> KeyRef<'a> {
+    #[inline]
+    pub fn as_str(&self) -> &'a str {
+        match *self {
+            KeyRef::Str(key) => key,
+            KeyRef::IStr(key) => key.as_str(),
+        }
+    }
+
+    /// Get the interned key, without interning again if it's already `IStr`.
+    #[inline]
+    pub fn to_istr(&self) -> IStr {
+        match *self {
+            KeyRef::Str(key) => IStr::new(key),
+            KeyRef::IStr(key) => key.clone(),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for KeyRef<'a> {
+    #[inline]
+    fn from(v: &'a str) -> Self {
+        KeyRef::Str(v)
+    }
+}
+
+impl<'a> From<&'a String> for KeyRef<'a> {
+    #[inline]
+    fn from(v: &'a String) -> Self {
+        KeyRef::Str(v)
+    }
+}
+
+impl<'a> From<&'a IStr> for KeyRef<'a> {
+    #[inline]
+    fn from(v: &'a IStr) -> Self {
+        KeyRef::IStr(v)
+    }
+}
+
+impl Deref for KeyRef<'_> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for KeyRef<'_> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (*self, *other) {
+            (KeyRef::IStr(a), KeyRef::IStr(b)) => a == b,
+            _ => self.as_str() == other.as_str(),
+        }
+    }
+}
+
+impl Eq for KeyRef<'_> {}
+
+impl PartialEq<str> for KeyRef<'_> {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<IStr> for KeyRef<'_> {
+    #[inline]
+    fn eq(&self, other: &IStr) -> bool {
+        match *self {
+            KeyRef::IStr(key) => key == other,
+            KeyRef::Str(key) => key == other.as_str(),
+        }
+    }
+}
+
+impl Hash for KeyRef<'_> {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        Hash::hash(self.as_str(), hasher)
+    }
+}
+
+impl fmt::Debug for KeyRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Entry returned from `InternMapExt::entry_str`
+pub enum StrEntry<'a, M: 'a, V: 'a> {
+    Occupied(&'a mut V),
+    Vacant(VacantStrEntry<'a, M, V>),
+}
+
+/// Vacant entry whose key is not interned yet
+pub struct VacantStrEntry<'a, M: 'a, V: 'a> {
+    map: &'a mut M,
+    key: &'a str,
+    _marker: PhantomData<&'a mut V>,
+}
+
+impl<'a, M: InternMapExt<V>, V> StrEntry<'a, M, V> {
+    #[inline]
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            StrEntry::Occupied(value) => value,
+            StrEntry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            StrEntry::Occupied(value) => {
+                f(value);
+                StrEntry::Occupied(value)
+            }
+            StrEntry::Vacant(entry) => StrEntry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, M: InternMapExt<V>, V: Default> StrEntry<'a, M, V> {
+    #[inline]
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(Default::default)
+    }
+}
+
+impl<'a, M: InternMapExt<V>, V> VacantStrEntry<'a, M, V> {
+    #[inline]
+    pub fn key(&self) -> &'a str {
+        self.key
+    }
+
+    /// Intern the key and insert the value.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.insert_vacant(IStr::new(self.key), value)
+    }
+}
+
+impl<V, S: BuildHasher> InternMapExt<V> for HashMap<IStr, V, S> {
+    #[inline]
+    fn get_str(&self, key: &str) -> Option<&V> {
+        self.get(key)
+    }
+
+    #[inline]
+    fn get_str_mut(&mut self, key: &str) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+
+    fn insert_str(&mut self, key: &str, value: V) -> Option<V> {
+        match self.get_mut(key) {
+            Some(slot) => Some(::std::mem::replace(slot, value)),
+            None => self.insert(IStr::new(key), value),
+        }
+    }
+}
+
+impl<V, S: BuildHasher> InsertVacant<V> for HashMap<IStr, V, S> {
+    fn insert_vacant(&mut self, key: IStr, value: V) -> &mut V {
+        self.entry(key).or_insert(value)
+    }
+}
+
+impl<V> InternMapExt<V> for BTreeMap<IStr, V> {
+    #[inline]
+    fn get_str(&self, key: &str) -> Option<&V> {
+        self.get(key)
+    }
+
+    #[inline]
+    fn get_str_mut(&mut self, key: &str) -> Option<&mut V> {
+        self.get_mut(key)
+    }
+
+    fn insert_str(&mut self, key: &str, value: V) -> Option<V> {
+        match self.get_mut(key) {
+            Some(slot) => Some(::std::mem::replace(slot, value)),
+            None => self.insert(IStr::new(key), value),
+        }
+    }
+}
+
+impl<V> InsertVacant<V> for BTreeMap<IStr, V> {
+    fn insert_vacant(&mut self, key: IStr, value: V) -> &mut V {
+        self.entry(key).or_insert(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_str() {
+        let mut map = HashMap::new();
+
+        *map.entry_str("Lorem ipsum dolor sit amet").or_insert(0) += 1;
+        *map.entry_str("Lorem ipsum dolor sit amet").or_insert(0) += 1;
+        *map.entry_str("foo").or_default() += 5;
+
+        assert_eq!(map.get_str("Lorem ipsum dolor sit amet"), Some(&2));
+        assert_eq!(map.get_str("foo"), Some(&5));
+        assert!(!map.contains_str("bar"));
+        assert_eq!(map.insert_str("foo", 3), Some(5));
+    }
+
+    #[test]
+    fn test_btree_entry_str() {
+        let mut map = BTreeMap::new();
+
+        map.entry_str("foo").or_insert_with(Vec::new).push(1);
+        map.entry_str("foo").and_modify(|v| v.push(2)).or_default();
+
+        assert_eq!(map.get_str("foo"), Some(&vec![1, 2]));
+    }
+
+    #[test]
+    fn test_btree_str_order() {
+        let words = ["zeta", "a string long enough to be pooled", "alpha", "the other pooled string"];
+        let map: BTreeMap<IStr, usize> = words.iter().enumerate()
+            .map(|(idx, word)| (IStr::new(word), idx))
+            .collect();
+
+        let mut sorted = words.to_vec();
+        sorted.sort();
+        assert!(map.keys().map(IStr::as_str).eq(sorted));
+
+        for (idx, word) in words.iter().enumerate() {
+            assert_eq!(map.get_str(word), Some(&idx));
+        }
+    }
+}
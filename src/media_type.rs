@@ -0,0 +1,295 @@
+use std::ops::Deref;
+use std::str::FromStr;
+use std::error::Error;
+use std::fmt;
+
+use istr::IStr;
+use header_name::is_token;
+
+/// Interned media type (MIME type)
+///
+/// Media types are validated and canonicalized before interning.
+/// Type, subtype and parameter names are lowercased, whitespaces around the parameters
+/// are removed, so the media types which only differ in case are equal.
+/// Parameter values are kept as is.
+///
+/// ```
+/// # use internship::IMediaType;
+/// let ty = IMediaType::new("Text/HTML; Charset=utf-8").unwrap();
+///
+/// assert_eq!(ty.as_str(), "text/html;charset=utf-8");
+/// assert_eq!(ty.essence(), "text/html");
+/// assert_eq!(ty.param("charset"), Some("utf-8"));
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IMediaType(IStr);
+
+/// Error returned when the media type is malformed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidMediaType;
+
+impl IMediaType {
+    pub fn new(src: &str) -> Result<Self, InvalidMediaType> {
+        let mut segments = split_params(src);
+        let essence = segments.next().unwrap_or("").trim();
+
+        let slash = essence.find('/').ok_or(InvalidMediaType)?;
+        let (ty, subtype) = (&essence[..slash], &essence[slash + 1..]);
+
+        if !is_token_str(ty) || !is_token_str(subtype) {
+            return Err(InvalidMediaType);
+        }
+
+        let mut buf = String::with_capacity(src.len());
+        buf.push_str(essence);
+        buf.make_ascii_lowercase();
+
+        for param in segments {
+            let param = param.trim();
+
+            if param.is_empty() {
+                continue;
+            }
+
+            let eq = param.find('=').ok_or(InvalidMediaType)?;
+            let (name, value) = (&param[..eq], &param[eq + 1..]);
+
+            if !is_token_str(name) || !(is_token_str(value) || is_quoted_str(value)) {
+                return Err(InvalidMediaType);
+            }
+
+            buf.push(';');
+            buf.extend(name.chars().map(|c| c.to_ascii_lowercase()));
+            buf.push('=');
+            buf.push_str(value);
+        }
+
+        Ok(IMediaType(IStr::new(&buf)))
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    #[inline]
+    pub fn as_istr(&self) -> &IStr {
+        &self.0
+    }
+
+    #[inline]
+    pub fn into_istr(self) -> IStr {
+        self.0
+    }
+
+    /// `type/subtype` part without parameters.
+    pub fn essence(&self) -> &str {
+        let s = self.as_str();
+        match s.find(';') {
+            Some(idx) => &s[..idx],
+            None => s,
+        }
+    }
+
+    pub fn type_(&self) -> &str {
+        let essence = self.essence();
+        &essence[..essence.find('/').unwrap()]
+    }
+
+    pub fn subtype(&self) -> &str {
+        let essence = self.essence();
+        &essence[essence.find('/').unwrap() + 1..]
+    }
+
+    /// Parameters as name and raw value pairs. Quoted values are returned with quotes.
+    pub fn params(&self) -> Params<'_> {
+        let mut segments = split_params(self.as_str());
+        segments.next();
+        Params(segments)
+    }
+
+    /// Value of the parameter with given name, unquoted if possible.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params()
+            .find(|&(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| {
+                if is_quoted_str(value) && !value.contains('\\') {
+                    &value[1..value.len() - 1]
+                } else {
+                    value
+                }
+            })
+    }
+}
+
+fn is_token_str(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(is_token)
+}
+
+fn is_quoted_str(s: &str) -> bool {
+    let bytes = s.as_bytes();
+
+    if bytes.len() < 2 || bytes[0] != b'"' || bytes[bytes.len() - 1] != b'"' {
+        return false;
+    }
+
+    let mut escaped = false;
+
+    for &b in &bytes[1..bytes.len() - 1] {
+        match (escaped, b) {
+            (true, _) => escaped = false,
+            (false, b'\\') => escaped = true,
+            (false, b'"') => return false,
+            _ => {}
+        }
+    }
+
+    !escaped
+}
+
+/// Split at `;`s outside of quoted strings.
+fn split_params(src: &str) -> SplitParams<'_> {
+    SplitParams(Some(src))
+}
+
+struct SplitParams<'a>(Option<&'a str>);
+
+impl<'a> Iterator for SplitParams<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let src = self.0?;
+        let mut quoted = false;
+        let mut escaped = false;
+
+        for (idx, b) in src.bytes().enumerate() {
+            match (quoted, escaped, b) {
+                (true, true, _) => escaped = false,
+                (true, false, b'\\') => escaped = true,
+                (_, false, b'"') => quoted = !quoted,
+                (false, _, b';') => {
+                    self.0 = Some(&src[idx + 1..]);
+                    return Some(&src[..idx]);
+                }
+                _ => {}
+            }
+        }
+
+        self.0 = None;
+        Some(src)
+    }
+}
+
+/// Iterator over parameters of `IMediaType`
+pub struct Params<'a>(SplitParams<'a>);
+
+impl<'a> Iterator for Params<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<(&'a str, &'a str)> {
+        let param = self.0.next()?;
+        let eq = param.find('=').unwrap();
+        Some((&param[..eq], &param[eq + 1..]))
+    }
+}
+
+macro_rules! common_types {
+    ($($name:ident => $value:expr,)*) => {
+        impl IMediaType {
+            $(
+                #[doc = concat!("`", $value, "`")]
+                pub fn $name() -> IMediaType {
+                    thread_local! {
+                        static TYPE: IMediaType = IMediaType(IStr::new($value));
+                    }
+
+                    TYPE.with(Clone::clone)
+                }
+            )*
+        }
+    };
+}
+
+common_types! {
+    application_json => "application/json",
+    application_octet_stream => "application/octet-stream",
+    application_x_www_form_urlencoded => "application/x-www-form-urlencoded",
+    application_xml => "application/xml",
+    multipart_form_data => "multipart/form-data",
+    text_css => "text/css",
+    text_html => "text/html",
+    text_javascript => "text/javascript",
+    text_plain => "text/plain",
+    text_plain_utf8 => "text/plain;charset=utf-8",
+    image_png => "image/png",
+    image_jpeg => "image/jpeg",
+}
+
+impl FromStr for IMediaType {
+    type Err = InvalidMediaType;
+
+    fn from_str(src: &str) -> Result<Self, InvalidMediaType> {
+        IMediaType::new(src)
+    }
+}
+
+impl Deref for IMediaType {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for IMediaType {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<IMediaType> for IStr {
+    #[inline]
+    fn from(v: IMediaType) -> IStr {
+        v.0
+    }
+}
+
+impl fmt::Debug for IMediaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for IMediaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for InvalidMediaType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid media type")
+    }
+}
+
+impl Error for InvalidMediaType {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical() {
+        let ty = IMediaType::new(" multipart/Form-Data ; boundary=\"a;b\" ").unwrap();
+
+        assert_eq!(ty.as_str(), "multipart/form-data;boundary=\"a;b\"");
+        assert_eq!(ty.type_(), "multipart");
+        assert_eq!(ty.subtype(), "form-data");
+        assert_eq!(ty.param("Boundary"), Some("a;b"));
+        assert_eq!(IMediaType::new("TEXT/plain;charset=utf-8"), Ok(IMediaType::text_plain_utf8()));
+        assert!(IMediaType::new("text").is_err());
+        assert!(IMediaType::new("text/plain; charset").is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+use std::fmt;
+
+use istr::IStr;
+
+/// Key compared and hashed by its handle instead of its contents
+///
+/// `IStr` is compared by its handle already, but it's hashed by the contents
+/// to be looked up by `&str` in maps. `ById` hashes the handle too, so both take
+/// constant time regardless of the length, like symbols of compilers.
+/// Holding the `IStr` keeps the handle unique to its contents.
+/// Unpooled strings keep their own identity, so they're distinct keys from the pooled ones.
+///
+/// ```
+/// # use internship::{IStr, ById};
+/// # use std::collections::HashSet;
+/// let mut seen = HashSet::new();
+/// let ident = IStr::new("a_rather_long_identifier_name");
+///
+/// assert!(seen.insert(ById::new(ident.clone())));
+/// assert!(!seen.insert(ById::new(IStr::new("a_rather_long_identifier_name"))));
+/// assert_eq!(*seen.iter().next().unwrap().get(), ident);
+/// ```
+#[derive(Clone)]
+pub struct ById(pub(crate) IStr);
+
+impl ById {
+    #[inline]
+    pub fn new(value: IStr) -> Self {
+        ById(value)
+    }
+
+    #[inline]
+    pub fn get(&self) -> &IStr {
+        &self.0
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> IStr {
+        self.0
+    }
+}
+
+impl PartialEq for ById {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        (self.0).0.id() == (other.0).0.id()
+    }
+}
+
+impl Eq for ById {}
+
+impl Hash for ById {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        (self.0).0.id().hash(hasher)
+    }
+}
+
+impl From<IStr> for ById {
+    #[inline]
+    fn from(value: IStr) -> Self {
+        ById(value)
+    }
+}
+
+impl fmt::Debug for ById {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+struct Slot<V> {
+    value: V,
+    seq: u64,
+    inserted: Instant,
+}
+
+/// Memoization cache keyed by `IStr`
+///
+/// Keys are hashed and compared by their handles, so lookup costs the same
+/// regardless of the length of the key. Entries can be limited by count,
+/// in which case the oldest inserted entry is evicted first, and by age.
+///
+/// ```
+/// # use internship::{IStr, InternMemo};
+/// let mut memo = InternMemo::new().max_size(2);
+/// let ident = IStr::new("some_identifier");
+///
+/// let len = *memo.get_or_insert_with(&ident, || ident.len());
+/// assert_eq!(len, 15);
+/// assert_eq!(memo.get(&ident), Some(&15));
+/// ```
+pub struct InternMemo<V> {
+    map: HashMap<ById, Slot<V>>,
+    order: VecDeque<(ById, u64)>,
+    next_seq: u64,
+    max_size: Option<usize>,
+    ttl: Option<Duration>,
+}
+
+impl<V> InternMemo<V> {
+    pub fn new() -> Self {
+        InternMemo {
+            map: HashMap::new(),
+            order: VecDeque::new(),
+            next_seq: 0,
+            max_size: None,
+            ttl: None,
+        }
+    }
+
+    /// Keep at most `size` entries, evicting the oldest inserted one.
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Treat entries older than `ttl` as absent.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    #[inline]
+    fn is_expired(&self, slot: &Slot<V>) -> bool {
+        self.ttl.is_some_and(|ttl| slot.inserted.elapsed() > ttl)
+    }
+
+    pub fn get(&self, key: &IStr) -> Option<&V> {
+        self.map.get(&ById(key.clone()))
+            .filter(|slot| !self.is_expired(slot))
+            .map(|slot| &slot.value)
+    }
+
+    #[inline]
+    pub fn contains(&self, key: &IStr) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Insert the value, returning the previous one if any and not expired.
+    pub fn insert(&mut self, key: IStr, value: V) -> Option<V> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let key = ById(key);
+        self.order.push_back((key.clone(), seq));
+
+        let prev = self.map.insert(key, Slot {
+            value,
+            seq,
+            inserted: Instant::now(),
+        });
+        let prev = prev.filter(|slot| !self.is_expired(slot)).map(|slot| slot.value);
+
+        self.evict();
+        prev
+    }
+
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: &IStr, f: F) -> &V {
+        if !self.contains(key) {
+            self.insert(key.clone(), f());
+        }
+
+        &self.map[&ById(key.clone())].value
+    }
+
+    pub fn remove(&mut self, key: &IStr) -> Option<V> {
+        let slot = self.map.remove(&ById(key.clone()))?;
+
+        if self.is_expired(&slot) {
+            None
+        } else {
+            Some(slot.value)
+        }
+    }
+
+    /// Remove every expired entry.
+    pub fn purge_expired(&mut self) {
+        if let Some(ttl) = self.ttl {
+            self.map.retain(|_, slot| slot.inserted.elapsed() <= ttl);
+            self.compact();
+        }
+    }
+
+    fn evict(&mut self) {
+        let max_size = self.max_size.unwrap_or(usize::MAX);
+
+        while self.map.len() > max_size {
+            let (key, seq) = match self.order.pop_front() {
+                Some(front) => front,
+                None => break,
+            };
+
+            if self.map.get(&key).is_some_and(|slot| slot.seq == seq) {
+                self.map.remove(&key);
+            }
+        }
+
+        if self.order.len() > self.map.len() * 2 + 16 {
+            self.compact();
+        }
+    }
+
+    /// Drop the order records of overwritten or removed entries.
+    fn compact(&mut self) {
+        let map = &self.map;
+        self.order.retain(|(key, seq)| map.get(key).is_some_and(|slot| slot.seq == *seq));
+    }
+
+    /// Number of entries, including expired ones not purged yet.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.order.clear();
+    }
+}
+
+impl<V> Default for InternMemo<V> {
+    fn default() -> Self {
+        InternMemo::new()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for InternMemo<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.map.iter().map(|(key, slot)| (&key.0, &slot.value)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eviction() {
+        let mut memo = InternMemo::new().max_size(2);
+        let keys: Vec<_> = ["a", "b", "c"].iter().map(|k| IStr::new(k)).collect();
+
+        memo.insert(keys[0].clone(), 0);
+        memo.insert(keys[1].clone(), 1);
+        assert_eq!(memo.insert(keys[0].clone(), 2), Some(0));
+        memo.insert(keys[2].clone(), 3);
+
+        assert_eq!(memo.len(), 2);
+        assert_eq!(memo.get(&keys[0]), Some(&2));
+        assert_eq!(memo.get(&keys[1]), None);
+        assert_eq!(memo.get(&keys[2]), Some(&3));
+
+        let mut memo = InternMemo::new().ttl(Duration::from_secs(0));
+        memo.insert(keys[0].clone(), 0);
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(memo.get(&keys[0]), None);
+        memo.purge_expired();
+        assert!(memo.is_empty());
+    }
+
+    #[test]
+    fn test_dropped_key_invalidated() {
+        let src = "an identifier long enough to be pooled";
+        let mut memo = InternMemo::new().max_size(1);
+        let before = ::pool::len();
+
+        let key = IStr::new(src);
+        memo.insert(key.clone(), 1);
+        drop(key);
+        // The memo keeps its key alive, so it still hits.
+        assert_eq!(memo.get(&IStr::new(src)), Some(&1));
+
+        memo.insert(IStr::new("another identifier which evicts the first"), 2);
+        assert_eq!(::pool::len(), before + 1);
+
+        // Other strings may take the freed address, which must not hit the evicted value.
+        let others: Vec<_> = (0..16)
+            .map(|idx| IStr::new(&format!("an identifier long enough to be pool{:02}", idx)))
+            .collect();
+        assert!(others.iter().all(|other| memo.get(other).is_none()));
+        assert_eq!(memo.get(&IStr::new(src)), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! Request-scoped interner middleware
+//!
+//! `InternLayer` is a tower middleware, usable with axum and other tower based frameworks.
+//! It installs a fresh `RequestInterner` into the extensions of every request,
+//! and clears it after the response is produced. Handlers can extract it with
+//! `Extension<RequestInterner>` to intern header or query values without
+//! keeping them alive after the request.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::Request;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use shared::{SharedPool, SharedStr};
+
+/// Interner which lives during a single request
+#[derive(Debug, Clone)]
+pub struct RequestInterner(Arc<SharedPool>);
+
+impl RequestInterner {
+    pub fn new() -> Self {
+        RequestInterner(Arc::new(SharedPool::with_shards(1)))
+    }
+
+    /// Get the interner installed by `InternLayer`.
+    #[inline]
+    pub fn from_request<B>(req: &Request<B>) -> Option<&Self> {
+        req.extensions().get()
+    }
+
+    #[inline]
+    pub fn intern(&self, src: &str) -> SharedStr {
+        self.0.intern(src)
+    }
+
+    #[inline]
+    pub fn pool(&self) -> &SharedPool {
+        &self.0
+    }
+}
+
+impl Default for RequestInterner {
+    fn default() -> Self {
+        RequestInterner::new()
+    }
+}
+
+/// Layer which applies `InternService`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InternLayer;
+
+impl<S> Layer<S> for InternLayer {
+    type Service = InternService<S>;
+
+    fn layer(&self, inner: S) -> InternService<S> {
+        InternService { inner }
+    }
+}
+
+/// Service which installs `RequestInterner` to each request
+#[derive(Debug, Clone)]
+pub struct InternService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for InternService<S> where S: Service<Request<B>> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let interner = RequestInterner::new();
+        req.extensions_mut().insert(interner.clone());
+
+        ResponseFuture {
+            inner: self.inner.call(req),
+            interner,
+        }
+    }
+}
+
+/// Response future of `InternService`
+#[derive(Debug)]
+pub struct ResponseFuture<F> {
+    inner: F,
+    interner: RequestInterner,
+}
+
+impl<F: Future> Future for ResponseFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Pin projection. `inner` is never moved out of `self`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+
+        match inner.poll(cx) {
+            Poll::Ready(res) => {
+                this.interner.pool().clear();
+                Poll::Ready(res)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::{ready, Ready};
+    use std::task::Waker;
+
+    struct Echo;
+
+    impl Service<Request<()>> for Echo {
+        type Response = (RequestInterner, SharedStr);
+        type Error = ();
+        type Future = Ready<Result<Self::Response, ()>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: Request<()>) -> Self::Future {
+            let interner = RequestInterner::from_request(&req).unwrap().clone();
+            let path = interner.intern(req.uri().path());
+            assert!(interner.pool().contains("/foo"));
+            ready(Ok((interner, path)))
+        }
+    }
+
+    #[test]
+    fn test_cleared_after_response() {
+        let mut service = InternLayer.layer(Echo);
+        let mut fut = service.call(Request::get("/foo").body(()).unwrap());
+        let mut cx = Context::from_waker(Waker::noop());
+
+        match Pin::new(&mut fut).poll(&mut cx) {
+            Poll::Ready(Ok((interner, path))) => {
+                assert_eq!(&*path, "/foo");
+                assert!(interner.pool().is_empty());
+            }
+            _ => panic!("service should be ready"),
+        }
+    }
+}
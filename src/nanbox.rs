@@ -0,0 +1,200 @@
+//! NaN-boxing of interned strings
+//!
+//! Interpreters of dynamic languages often pack their values into the 48-bit payload
+//! of NaN floats. `encode` turns an `IStr` into such a payload, and `decode` checks
+//! the payload and turns it back. Strings up to 5 bytes are packed into the payload itself.
+//! Others are kept in a table of the current thread, and the payload refers to their slot
+//! with a generation, so a payload of a released slot is rejected instead of
+//! resolving to another string.
+//!
+//! The table keeps its strings alive until the garbage collector of the interpreter
+//! releases them with `retain`.
+//!
+//! ```
+//! # use internship::{IStr, nanbox};
+//! let short = nanbox::encode(&IStr::new("nil"));
+//! let long = nanbox::encode(&IStr::new("a string stored in the value word"));
+//!
+//! assert!(short < 1 << nanbox::PAYLOAD_BITS && long < 1 << nanbox::PAYLOAD_BITS);
+//! assert_eq!(nanbox::decode(short).unwrap(), "nil");
+//! assert_eq!(nanbox::decode(long).unwrap(), "a string stored in the value word");
+//!
+//! // Collected by the interpreter.
+//! nanbox::retain(|payload| payload != long);
+//! assert_eq!(nanbox::decode(long), None);
+//! ```
+
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::str;
+
+use istr::IStr;
+use memo::ById;
+
+/// Number of the bits of the payloads.
+pub const PAYLOAD_BITS: u32 = 48;
+
+/// Max length of the strings packed into the payload without the table.
+pub const MAX_PACKED_LEN: usize = 5;
+
+const PACKED_FLAG: u64 = 1 << (PAYLOAD_BITS - 1);
+const PACKED_LEN_SHIFT: u32 = 8 * MAX_PACKED_LEN as u32;
+const GENERATION_SHIFT: u32 = 32;
+const GENERATION_MASK: u64 = (1 << (PAYLOAD_BITS - 1 - GENERATION_SHIFT)) - 1;
+
+#[derive(Default)]
+struct Table {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    index: HashMap<ById, u32>,
+}
+
+struct Slot {
+    value: Option<IStr>,
+    generation: u64,
+}
+
+thread_local! {
+    static TABLE: RefCell<Table> = Default::default();
+}
+
+/// Encode the string into a payload of `PAYLOAD_BITS` bits.
+/// Equal strings are encoded into the same payload until it's released by `retain`.
+pub fn encode(value: &IStr) -> u64 {
+    if value.len() <= MAX_PACKED_LEN {
+        return value.bytes().rev()
+            .fold(0, |packed, byte| packed << 8 | byte as u64)
+            | (value.len() as u64) << PACKED_LEN_SHIFT
+            | PACKED_FLAG;
+    }
+
+    TABLE.with(|table| {
+        let mut table = table.borrow_mut();
+        let table = &mut *table;
+
+        if let Some(&index) = table.index.get(&ById(value.clone())) {
+            return payload(index, table.slots[index as usize].generation);
+        }
+
+        let index = match table.free.pop() {
+            Some(index) => index,
+            None => {
+                assert!(table.slots.len() < u32::MAX as usize, "too many strings are NaN-boxed");
+                table.slots.push(Slot { value: None, generation: 0 });
+                (table.slots.len() - 1) as u32
+            }
+        };
+
+        let slot = &mut table.slots[index as usize];
+        slot.value = Some(value.clone());
+        table.index.insert(ById(value.clone()), index);
+        payload(index, slot.generation)
+    })
+}
+
+/// Decode the payload of `encode`, or `None` if it's not a valid payload
+/// or its string is already released.
+pub fn decode(payload: u64) -> Option<IStr> {
+    if payload >> PAYLOAD_BITS != 0 {
+        return None;
+    }
+
+    if payload & PACKED_FLAG != 0 {
+        let len = (payload >> PACKED_LEN_SHIFT & 0x7F) as usize;
+        // Only the payloads of `encode` are accepted, with zeroes after the contents.
+        if len > MAX_PACKED_LEN || (payload & ((1 << PACKED_LEN_SHIFT) - 1)) >> (8 * len) != 0 {
+            return None;
+        }
+
+        let bytes = payload.to_le_bytes();
+        return str::from_utf8(&bytes[..len]).ok().and_then(IStr::new_inline);
+    }
+
+    let index = payload as u32 as usize;
+    let generation = payload >> GENERATION_SHIFT;
+
+    TABLE.with(|table| {
+        match table.borrow().slots.get(index) {
+            Some(slot) if slot.generation == generation => slot.value.clone(),
+            _ => None,
+        }
+    })
+}
+
+/// Release the strings of the table whose payloads `f` returns `false` for,
+/// returning the number of them. Their payloads are not decoded anymore.
+/// Payloads packed without the table are not passed to `f`.
+pub fn retain<F: FnMut(u64) -> bool>(mut f: F) -> usize {
+    let released: Vec<IStr> = TABLE.with(|table| {
+        let mut table = table.borrow_mut();
+        let table = &mut *table;
+        let mut released = Vec::new();
+
+        for (index, slot) in table.slots.iter_mut().enumerate() {
+            if slot.value.is_none() || f(payload(index as u32, slot.generation)) {
+                continue;
+            }
+
+            let value = slot.value.take().expect("released slot");
+            table.index.remove(&ById(value.clone()));
+            table.free.push(index as u32);
+            slot.generation = (slot.generation + 1) & GENERATION_MASK;
+            released.push(value);
+        }
+
+        released
+    });
+
+    // Dropped after the table is released.
+    released.len()
+}
+
+/// Number of the strings kept in the table of the current thread.
+pub fn len() -> usize {
+    TABLE.with(|table| table.borrow().index.len())
+}
+
+/// Whether the table of the current thread is empty.
+pub fn is_empty() -> bool {
+    len() == 0
+}
+
+#[inline]
+fn payload(index: u32, generation: u64) -> u64 {
+    generation << GENERATION_SHIFT | index as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for src in &["", "a", "ab\u{e9}", "12345", "123456", "a string long enough to be pooled"] {
+            let value = IStr::new(src);
+            let payload = encode(&value);
+
+            assert!(payload < 1 << PAYLOAD_BITS);
+            assert_eq!(encode(&value), payload);
+            assert_eq!(decode(payload), Some(value));
+        }
+
+        assert_eq!(decode(1 << PAYLOAD_BITS), None);
+        assert_eq!(decode(PACKED_FLAG | 6 << PACKED_LEN_SHIFT), None);
+        assert_eq!(decode(PACKED_FLAG | 1 << PACKED_LEN_SHIFT | 0xFF), None);
+        assert_eq!(decode(encode(&IStr::new("ab")) | 0x63 << 16), None);
+        assert_eq!(decode(u32::MAX as u64), None);
+    }
+
+    #[test]
+    fn test_stale_payload() {
+        let first = encode(&IStr::new("a string released by the collector"));
+        assert_eq!(retain(|_| false), 1);
+        assert!(is_empty());
+
+        let second = encode(&IStr::new("another string reusing the slot"));
+        assert_ne!(first, second);
+        assert_eq!(decode(first), None);
+        assert_eq!(decode(second).unwrap(), "another string reusing the slot");
+    }
+}
@@ -0,0 +1,115 @@
+//! Parquet dictionary column reader
+//!
+//! Reads dictionary-encoded string columns directly as interned values.
+//! Dictionary pages are kept as Arrow dictionaries while decoding,
+//! so each dictionary entry is interned once and the rows are resolved by index.
+
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::Int32Type;
+use arrow_schema::{DataType, Field, Schema};
+use parquet_crate::arrow::ProjectionMask;
+use parquet_crate::arrow::arrow_reader::{
+    ArrowReaderMetadata, ArrowReaderOptions, ParquetRecordBatchReaderBuilder,
+};
+use parquet_crate::errors::{ParquetError, Result};
+use parquet_crate::file::reader::ChunkReader;
+
+use arrow::from_dictionary;
+use istr::IStr;
+
+/// Read a string column of the parquet file as interned values.
+///
+/// Null values become `None`. Returns error if the column does not exist
+/// or it's not a string column.
+pub fn read_column<R: ChunkReader + 'static>(reader: R, column: &str) -> Result<Vec<Option<IStr>>> {
+    let metadata = ArrowReaderMetadata::load(&reader, ArrowReaderOptions::new())?;
+    let schema = metadata.schema();
+
+    let (idx, field) = schema.column_with_name(column)
+        .ok_or_else(|| ParquetError::General(format!("column {} not found", column)))?;
+
+    match field.data_type() {
+        DataType::Utf8 => {}
+        DataType::Dictionary(_, value) if **value == DataType::Utf8 => {}
+        other => return Err(ParquetError::General(format!(
+            "column {} has type {}, expected Utf8", column, other))),
+    }
+
+    let dict_type = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+    let fields: Vec<Field> = schema.fields().iter()
+        .enumerate()
+        .map(|(i, f)| if i == idx {
+            f.as_ref().clone().with_data_type(dict_type.clone())
+        } else {
+            f.as_ref().clone()
+        })
+        .collect();
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+
+    let options = ArrowReaderOptions::new().with_schema(Arc::new(schema));
+    let metadata = ArrowReaderMetadata::try_new(Arc::clone(metadata.metadata()), options)?;
+
+    let builder = ParquetRecordBatchReaderBuilder::new_with_metadata(reader, metadata);
+    // Arrow field indices don't match the parquet columns if any field is nested,
+    // so the root is looked up by name in the parquet schema.
+    let root = builder.parquet_schema().root_schema().get_fields().iter()
+        .position(|root| root.name() == column)
+        .ok_or_else(|| ParquetError::General(format!("column {} not found", column)))?;
+    let mask = ProjectionMask::roots(builder.parquet_schema(), [root]);
+    let batches = builder.with_projection(mask).build()?;
+
+    let mut res = Vec::new();
+
+    for batch in batches {
+        let batch = batch?;
+        let array = batch.column(0).as_dictionary_opt::<Int32Type>()
+            .ok_or_else(|| ParquetError::General(format!("column {} is not dictionary", column)))?;
+
+        res.extend(from_dictionary(array)?);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{ArrayRef, Int32Array, RecordBatch, StringArray, StructArray};
+    use parquet_crate::arrow::ArrowWriter;
+    use parquet_crate::file::properties::WriterProperties;
+    use bytes::Bytes;
+    use std::convert::TryFrom;
+
+    // Written in memory, so parallel runs don't share any file.
+    fn write(batch: &RecordBatch) -> Bytes {
+        let props = WriterProperties::builder().set_dictionary_enabled(true).build();
+        let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), Some(props)).unwrap();
+        writer.write(batch).unwrap();
+        Bytes::from(writer.into_inner().unwrap())
+    }
+
+    #[test]
+    fn test_read_column() {
+        let values = vec![Some("Seoul"), None, Some("Busan"), Some("Seoul")];
+        let array: ArrayRef = Arc::new(StringArray::from(values.clone()));
+        let batch = RecordBatch::try_from_iter(vec![("city", array)]).unwrap();
+
+        let res = read_column(write(&batch), "city").unwrap();
+        let expected: Vec<_> = values.into_iter().map(|v| v.map(IStr::new)).collect();
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn test_after_nested_column() {
+        let x: ArrayRef = Arc::new(Int32Array::from(vec![1, 2]));
+        let y: ArrayRef = Arc::new(Int32Array::from(vec![3, 4]));
+        let point: ArrayRef = Arc::new(StructArray::try_from(vec![("x", x), ("y", y)]).unwrap());
+        let city: ArrayRef = Arc::new(StringArray::from(vec!["Seoul", "Busan"]));
+        let batch = RecordBatch::try_from_iter(vec![("point", point), ("city", city)]).unwrap();
+
+        let res = read_column(write(&batch), "city").unwrap();
+        assert_eq!(res, vec![Some(IStr::new("Seoul")), Some(IStr::new("Busan"))]);
+    }
+}
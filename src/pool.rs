@@ -0,0 +1,721 @@
+//! Operations on the thread-local pools
+//!
+//! Every interned type keeps its pool per thread,
+//! so functions here only affect the pools of the calling thread.
+
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::error::Error;
+use std::fmt;
+
+use handle::{self, Handle, Mirror, PoolHasher, SharedCallback};
+use interner::Interner;
+use ibytes::IBytes;
+use istr::IStr;
+use side;
+use soft;
+
+pub use side::SideEntry;
+pub use handle::ClassStats;
+pub use handle::{Thresholds, Threshold, Saturation};
+#[cfg(feature = "blob")]
+use blob;
+
+/// Scope of interning, e.g. a request of the server
+///
+/// The epoch tracks the entries first interned within it. When it ends, the ones
+/// kept only by the pool itself, i.e. by their pins or soft references, are removed,
+/// and the ones still referenced by handles are reported, which usually means
+/// strings leaked out of the request. Entries without any reference are already removed
+/// as soon as their last handle is dropped, and leaked entries are never removed.
+///
+/// Epochs can be nested. Ending an outer epoch ends its inner ones too,
+/// and ending an epoch which is already ended that way does nothing.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// let kept;
+/// let epoch = pool::epoch();
+/// {
+///     let _temp = IStr::new("temporary string long enough to be pooled");
+///     kept = IStr::new("another string long enough to be pooled");
+///     IStr::new("pinned string long enough to be pooled").pin();
+/// }
+/// let survivors = epoch.end();
+///
+/// assert_eq!(survivors.len(), 1);
+/// assert_eq!(survivors[0], kept.to_ibytes());
+/// // The pinned one is removed.
+/// assert_eq!(pool::len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct Epoch {
+    id: u64,
+    ended: bool,
+    // The epoch belongs to the pool of its thread.
+    _marker: PhantomData<Rc<()>>,
+}
+
+/// Start an epoch on the current thread's pool.
+pub fn epoch() -> Epoch {
+    Epoch {
+        id: handle::begin_epoch(),
+        ended: false,
+        _marker: PhantomData,
+    }
+}
+
+impl Epoch {
+    /// Number of the entries first interned in this epoch and still in the pool.
+    pub fn len(&self) -> usize {
+        handle::epoch_len(self.id)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// End the epoch, removing its entries kept only by the pool,
+    /// and returning the others still in the pool.
+    pub fn end(mut self) -> Vec<IBytes> {
+        self.ended = true;
+        end_epoch(self.id)
+    }
+}
+
+impl Drop for Epoch {
+    fn drop(&mut self) {
+        if !self.ended {
+            end_epoch(self.id);
+        }
+    }
+}
+
+fn end_epoch(id: u64) -> Vec<IBytes> {
+    handle::end_epoch(id).into_iter()
+        .filter(|handle| !release_unreferenced(handle))
+        .map(IBytes)
+        .collect()
+}
+
+// Release the pool's own references of the entry if no other handle refers it.
+fn release_unreferenced(handle: &Handle) -> bool {
+    let addr = match handle.pool_id() {
+        Some(addr) => addr,
+        None => return false,
+    };
+    if handle.as_static().is_some() {
+        return false;
+    }
+
+    let pinned = handle.is_pinned();
+    let soft = soft::contains(addr);
+    // The pool's and this handle's.
+    if handle.ref_count() != 2 + pinned as usize + soft as usize {
+        return false;
+    }
+
+    if soft {
+        soft::release_entry(addr);
+    }
+    if pinned {
+        handle.unpin();
+    }
+    true
+}
+
+/// Slot of the side data of type `V` for the string.
+///
+/// Each interned string can have one value per type, which is dropped
+/// when the string is removed from the pool. Short strings stored inline are never removed,
+/// so their data stay until `SideEntry::remove` is called.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// # use std::cell::Cell;
+/// let word = IStr::new("antidisestablishmentarianism");
+///
+/// pool::entry::<Cell<u32>>(&word).or_default().set(3);
+/// assert_eq!(pool::entry::<Cell<u32>>(&word).get().unwrap().get(), 3);
+/// ```
+pub fn entry<V: 'static>(key: &IStr) -> SideEntry<V> {
+    side::entry(key)
+}
+
+/// Make `entries()` return the entries in their insertion order, or stop it.
+///
+/// It keeps an auxiliary index besides the pool, which costs a bit on every insertion and removal.
+/// Entries already in the pool when it's enabled are ordered arbitrarily but before the later ones.
+pub fn set_insertion_order(enabled: bool) {
+    handle::set_insertion_order(enabled)
+}
+
+/// Every entry of the current thread's pool.
+///
+/// Order is unspecified unless `set_insertion_order(true)` is called beforehand.
+/// Contents short enough to be inlined are never in the pool.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// pool::set_insertion_order(true);
+///
+/// let names: Vec<_> = ["first string in the pool", "second string in the pool"]
+///     .iter().map(|s| IStr::new(s)).collect();
+/// let entries: Vec<_> = pool::entries().iter().map(|v| v.to_istr().unwrap()).collect();
+///
+/// assert_eq!(entries, names);
+/// ```
+pub fn entries() -> Vec<IBytes> {
+    handle::entries().into_iter().map(IBytes).collect()
+}
+
+/// Number of the entries in the current thread's pool. Inlined values are not counted.
+pub fn len() -> usize {
+    handle::pool_len()
+}
+
+/// Whether the current thread's pool has no entries.
+pub fn is_empty() -> bool {
+    len() == 0
+}
+
+/// Number of the entries the current thread's pool can hold without reallocating.
+///
+/// The pool doesn't shrink by itself, so it stays as large as its peak usage.
+/// See `shrink_to_fit()`.
+pub fn capacity() -> usize {
+    handle::pool_capacity()
+}
+
+/// Shrink the capacity of the current thread's pool to fit its entries,
+/// e.g. after a burst of short-lived strings.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// let burst: Vec<_> = (0..1000).map(|n| IStr::from(format!("short-lived string number {}", n))).collect();
+/// let peak = pool::capacity();
+/// drop(burst);
+///
+/// pool::shrink_to_fit();
+/// assert!(pool::capacity() < peak);
+/// assert!(pool::capacity() >= pool::len());
+/// ```
+pub fn shrink_to_fit() {
+    handle::shrink_pool()
+}
+
+/// Remove the entries no handle refers, returning the number of them.
+///
+/// Entries are removed as soon as their last handle is dropped, so the only
+/// entries without handles are the pinned ones. This unpins and removes them.
+/// Leaked entries are kept as they're borrowed for `'static`,
+/// and the entries of `SoftIStr`s are released by `release_soft()` instead.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// let keyword = IStr::new("a pinned keyword nobody refers anymore");
+/// keyword.pin();
+/// drop(keyword);
+///
+/// assert!(pool::len() >= 1);
+/// assert_eq!(pool::purge_unused(), 1);
+/// assert_eq!(pool::len(), 0);
+/// ```
+pub fn purge_unused() -> usize {
+    handle::purge_unused()
+}
+
+/// Whether the current thread's pool can be used right now.
+///
+/// It can't while the thread local storage is being destroyed, e.g. in the destructors
+/// of other thread locals, or while the pool is borrowed by its own callbacks like class predicates.
+/// Strings created then are allocated outside of the pool with their own refcount,
+/// which still compare equal to the interned ones by their contents but keep their own ids,
+/// e.g. as `ById` keys. See `IStr::is_unpooled()`.
+pub fn is_available() -> bool {
+    handle::Handle::is_pool_available()
+}
+
+/// Verify the consistency of the current thread's pool, panicking if it's broken.
+///
+/// It checks that every entry is referenced, found by its contents, not duplicated
+/// and not short enough to be inlined, and that the auxiliary indices
+/// of the readers, insertion order, epochs and pins match the pool.
+/// It walks the whole pool, so it's meant for tests and fuzz targets
+/// catching regressions of the unsafe code.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// let _value = IStr::new("a string long enough to be pooled");
+/// pool::check_invariants();
+/// ```
+pub fn check_invariants() {
+    if let Err(msg) = handle::check_pool() {
+        panic!("pool invariant is broken: {}", msg);
+    }
+}
+
+/// Classify the entries of the current thread's pool by their contents.
+///
+/// Classifiers are tried in registration order and the first match wins.
+/// Registering a class of the same name again replaces its classifier.
+/// Every entry already in the pool is reclassified, so register them early.
+/// Classifiers are called on every insertion and must not intern anything themselves.
+///
+/// Usage of each class is reported by `class_stats()`, and the budget set by `set_class_budget()`
+/// limits the new entries interned with `IStr::try_new()`, so that a runaway class of
+/// user-supplied values can't grow the pool while the stable strings keep working.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// pool::register_class("path", |bytes| bytes.starts_with(b"/"));
+/// pool::set_class_budget(Some("path"), Some(64));
+///
+/// let _home = IStr::try_new("/home/someone/.config/app.toml").unwrap();
+/// assert!(IStr::try_new("/home/someone/.local/share/app/data.db").is_err());
+///
+/// let path = pool::class_stats().into_iter().find(|class| class.name == Some("path")).unwrap();
+/// assert_eq!(path.entries, 1);
+/// ```
+pub fn register_class(name: &'static str, classify: fn(&[u8]) -> bool) {
+    handle::register_class(name, classify)
+}
+
+/// Limit the total length of the class's entries interned with `IStr::try_new()`.
+/// `None` name is for the entries no classifier matched.
+/// Returns `false` if no such class is registered.
+pub fn set_class_budget(name: Option<&str>, budget: Option<usize>) -> bool {
+    handle::set_class_budget(name, budget)
+}
+
+/// Usage of every class of the current thread's pool, with the unclassified entries first.
+/// Empty if no class is registered.
+pub fn class_stats() -> Vec<ClassStats> {
+    handle::class_stats()
+}
+
+/// Error returned when a new entry would exceed the budget of its class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    /// Name of the class, or `None` for the unclassified entries.
+    pub class: Option<&'static str>,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.class {
+            Some(name) => write!(f, "budget of the pool class {:?} is exceeded", name),
+            None => f.write_str("budget of the unclassified pool entries is exceeded"),
+        }
+    }
+}
+
+impl Error for BudgetExceeded {}
+
+/// Release the pool's references to the entries of `SoftIStr`s, returning the number of them.
+///
+/// Entries not referenced by any `IStr` are freed, and every soft reference
+/// made so far needs to re-intern its string. Call it on memory pressure.
+pub fn release_soft() -> usize {
+    soft::release()
+}
+
+/// Call `callback` when the usage of the current thread's pool goes above any of the thresholds.
+///
+/// It's called once per crossing, and again only after the usage goes below the threshold
+/// and crosses it again. It's called after the pool is released, so it may intern strings,
+/// e.g. to log the warning. Setting it again replaces the previous one.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// # use std::cell::Cell;
+/// # use std::rc::Rc;
+/// let warned = Rc::new(Cell::new(0));
+/// let counter = Rc::clone(&warned);
+///
+/// let thresholds = pool::Thresholds { entries: Some(2), ..Default::default() };
+/// pool::set_saturation_warning(thresholds, move |saturation| {
+///     assert_eq!(saturation.crossed, pool::Threshold::Entries);
+///     counter.set(counter.get() + 1);
+/// });
+///
+/// let values: Vec<_> = (0..5).map(|n| IStr::from(format!("hostile input number {}", n))).collect();
+/// assert_eq!(warned.get(), 1);
+/// # drop(values);
+/// ```
+pub fn set_saturation_warning<F: Fn(&Saturation) + 'static>(thresholds: Thresholds, callback: F) {
+    handle::set_saturation_warning(thresholds, Rc::new(callback))
+}
+
+/// Stop warning about the pool usage.
+pub fn clear_saturation_warning() {
+    handle::clear_saturation_warning()
+}
+
+type Classify = fn(&[u8]) -> bool;
+
+/// Configuration of the pools
+///
+/// It can be applied to the current thread's pool, set as the default of every thread's pool
+/// at its first use, or used to build a local `Interner`. Only the options set on it are applied,
+/// so applying it leaves the others as they are.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// # use internship::pool::PoolConfig;
+/// let config = PoolConfig::new()
+///     .capacity(1 << 12)
+///     .seed(42)
+///     .insertion_order(true)
+///     .class("url", |bytes| bytes.starts_with(b"https://"))
+///     .class_budget(Some("url"), 32);
+///
+/// config.apply();
+/// let _name = IStr::new("a string long enough to be pooled");
+/// assert!(IStr::try_new("https://example.com/a/path/long/enough/to/exceed/the/budget").is_err());
+/// pool::check_invariants();
+///
+/// let mut interner = config.build_interner();
+/// let foo = interner.intern("foo");
+/// assert_eq!(interner.resolve(foo).unwrap(), "foo");
+/// ```
+#[derive(Clone, Default)]
+pub struct PoolConfig {
+    pub(crate) capacity: Option<usize>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) insertion_order: Option<bool>,
+    pub(crate) classes: Vec<(&'static str, Classify)>,
+    pub(crate) budgets: Vec<(Option<&'static str>, Option<usize>)>,
+    pub(crate) saturation: Option<(Thresholds, SharedCallback)>,
+}
+
+impl PoolConfig {
+    pub fn new() -> Self {
+        PoolConfig::default()
+    }
+
+    /// Number of the entries to reserve.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Hash the entries with the fixed seed instead of random keys, for reproducible runs.
+    /// It makes the pool vulnerable to hash flooding by hostile input.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Track the insertion order for `entries()`, or stop it. See `set_insertion_order`.
+    pub fn insertion_order(mut self, enabled: bool) -> Self {
+        self.insertion_order = Some(enabled);
+        self
+    }
+
+    /// Register the class of the entries, in order. See `register_class`.
+    pub fn class(mut self, name: &'static str, classify: fn(&[u8]) -> bool) -> Self {
+        self.classes.push((name, classify));
+        self
+    }
+
+    /// Limit the total length of the class's entries, after the classes are registered.
+    /// See `set_class_budget`. Budgets of the classes never registered are ignored.
+    pub fn class_budget(mut self, name: Option<&'static str>, budget: usize) -> Self {
+        self.budgets.push((name, Some(budget)));
+        self
+    }
+
+    /// Warn about the pool usage. See `set_saturation_warning`.
+    ///
+    /// The callback is shared by the pools of every thread if it's set as the default,
+    /// so it must be `Send` and `Sync`.
+    pub fn saturation_warning<F>(mut self, thresholds: Thresholds, callback: F) -> Self
+        where F: Fn(&Saturation) + Send + Sync + 'static
+    {
+        self.saturation = Some((thresholds, Arc::new(callback)));
+        self
+    }
+
+    pub(crate) fn hasher(&self) -> PoolHasher {
+        match self.seed {
+            Some(seed) => PoolHasher::Seeded(seed),
+            None => PoolHasher::default(),
+        }
+    }
+
+    /// Apply to the current thread's pool. Entries already in the pool are kept.
+    pub fn apply(&self) {
+        handle::configure(self)
+    }
+
+    /// Apply to the pool of every thread at its first use from now on.
+    /// Pools already in use are not affected.
+    ///
+    /// ```
+    /// # use internship::{IStr, pool};
+    /// # use internship::pool::PoolConfig;
+    /// # use std::thread;
+    /// PoolConfig::new()
+    ///     .insertion_order(true)
+    ///     .class("first", |bytes| bytes.starts_with(b"first"))
+    ///     .set_default();
+    ///
+    /// thread::spawn(|| {
+    ///     let names = [IStr::new("first string in the pool"), IStr::new("second string in the pool")];
+    ///     let entries: Vec<_> = pool::entries().iter().map(|v| v.to_istr().unwrap()).collect();
+    ///     assert_eq!(entries, names);
+    ///
+    ///     let first = pool::class_stats().into_iter().find(|class| class.name == Some("first")).unwrap();
+    ///     assert_eq!(first.entries, 1);
+    /// }).join().unwrap();
+    /// ```
+    pub fn set_default(self) {
+        handle::set_default_config(self)
+    }
+
+    /// Build a local interner with the capacity and the hasher.
+    pub fn build_interner(&self) -> Interner {
+        Interner::with_capacity_and_hasher(self.capacity.unwrap_or(0), self.hasher())
+    }
+}
+
+impl fmt::Debug for PoolConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let classes: Vec<_> = self.classes.iter().map(|&(name, _)| name).collect();
+
+        f.debug_struct("PoolConfig")
+            .field("capacity", &self.capacity)
+            .field("seed", &self.seed)
+            .field("insertion_order", &self.insertion_order)
+            .field("classes", &classes)
+            .field("budgets", &self.budgets)
+            .field("saturation", &self.saturation.as_ref().map(|&(thresholds, _)| thresholds))
+            .finish()
+    }
+}
+
+/// Intern the strings in ascending order at once.
+///
+/// The pool is reserved for the whole input and borrowed only once,
+/// and equal adjacent strings are interned only once. Loaders which can sort
+/// their vocabulary beforehand avoid the rehashes and repeated lookups of interning one by one.
+/// Unsorted input is still interned correctly, but duplicates are looked up again.
+///
+/// ```
+/// # use internship::pool;
+/// let words = ["apple", "banana", "banana", "cherry"];
+/// let interned = pool::extend_sorted(&words);
+///
+/// assert_eq!(interned, words);
+/// ```
+pub fn extend_sorted<I, T>(iter: I) -> Vec<IStr>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+{
+    let bytes = iter.into_iter().map(AsRefBytes);
+    handle::Handle::extend_sorted(bytes).into_iter().map(IStr).collect()
+}
+
+struct AsRefBytes<T>(T);
+
+impl<T: AsRef<str>> AsRef<[u8]> for AsRefBytes<T> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref().as_bytes()
+    }
+}
+
+/// Read-only view of a thread's pool, usable from other threads
+///
+/// The owner thread keeps interning as usual, while readers answer queries concurrently.
+/// While any reader is alive, the owner thread updates it on every insertion and removal
+/// of the pool entries. Contents short enough to be inlined are never in the pool.
+/// Once the owner thread exits, the reader is closed and finds nothing.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// # use std::thread;
+/// let name = IStr::new("a string long enough to be pooled");
+/// let reader = pool::reader();
+///
+/// let id = name.pool_id().unwrap();
+/// let found = thread::spawn(move || {
+///     (reader.get("a string long enough to be pooled"), reader.resolve(id))
+/// }).join().unwrap();
+///
+/// assert_eq!(found.0, Some(id));
+/// assert_eq!(found.1.as_deref(), Some(name.as_bytes()));
+/// ```
+#[derive(Clone)]
+pub struct PoolReader(Arc<RwLock<Mirror>>);
+
+/// Create a reader of the current thread's pool.
+pub fn reader() -> PoolReader {
+    PoolReader(handle::mirror())
+}
+
+impl PoolReader {
+    #[inline]
+    fn read(&self) -> RwLockReadGuard<'_, Mirror> {
+        self.0.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Id of the entry with given contents, which equals to its `pool_id()`.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<usize> {
+        self.read().entries.get(key.as_ref()).map(|entry| entry.addr())
+    }
+
+    pub fn contains<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.read().entries.contains(key.as_ref())
+    }
+
+    /// Copy the contents of the entry with given id.
+    pub fn resolve(&self, id: usize) -> Option<Box<[u8]>> {
+        self.read().by_addr.get(&id).map(|entry| entry.get().into())
+    }
+
+    /// Call `f` with the contents of the entry with given id.
+    ///
+    /// The contents are copied out before calling `f`, as the owner thread can't update
+    /// the reader while it's being read. So `f` may intern on the owner thread too.
+    pub fn resolve_with<R, F: FnOnce(&[u8]) -> R>(&self, id: usize, f: F) -> Option<R> {
+        self.resolve(id).map(|bytes| f(&bytes))
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().entries.len()
+    }
+
+    /// Whether the owner thread's pool is destroyed.
+    pub fn is_closed(&self) -> bool {
+        self.read().closed
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for PoolReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PoolReader")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// LZ4-compress large `IBlob`s which are not accessed since the last call of this function.
+///
+/// Compressed blobs are transparently decompressed by `IBlob::get()`,
+/// and kept uncompressed while the returned guard lives.
+/// Call it periodically, e.g. from the idle handler of an event loop.
+/// Returns the number of blobs compressed by this call.
+///
+/// `IStr` and other handle-based types are never compressed,
+/// as they give out plain references to their contents.
+#[cfg(feature = "blob")]
+pub fn compress_cold() -> usize {
+    blob::compress_cold()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use istr::IStr;
+
+    fn in_pool(src: &str) -> bool {
+        entries().iter().any(|entry| entry.as_bytes() == src.as_bytes())
+    }
+
+    #[test]
+    fn test_epoch_release() {
+        let outer = epoch();
+        let kept = IStr::new("a string kept out of the epoch");
+        IStr::new("a string pinned in the epoch").pin();
+        let soft = IStr::new_soft("a string softly interned in the epoch");
+        let leaked = IStr::new("a string leaked in the epoch").leak().unwrap();
+        assert_eq!(outer.len(), 4);
+
+        let survivors = outer.end();
+        assert_eq!(survivors.len(), 2);
+        assert!(survivors.contains(&kept.to_ibytes()));
+        assert!(!in_pool("a string pinned in the epoch"));
+        assert!(soft.is_released());
+        assert_eq!(leaked, "a string leaked in the epoch");
+        assert!(in_pool(leaked));
+    }
+
+    #[test]
+    fn test_epoch_ended_by_outer() {
+        let outer = epoch();
+        let inner = epoch();
+        drop(outer);
+
+        let next = epoch();
+        let pinned = IStr::new("a string pinned in the next epoch");
+        pinned.pin();
+        drop(pinned);
+
+        // Already ended with the outer one, so the next one is kept.
+        drop(inner);
+        assert_eq!(next.len(), 1);
+        assert!(next.end().is_empty());
+        assert!(!in_pool("a string pinned in the next epoch"));
+    }
+
+    #[test]
+    fn test_config_keeps_unset() {
+        set_insertion_order(true);
+        let names: Vec<_> = (0..3).map(|n| IStr::from(format!("config test string number {}", n))).collect();
+
+        PoolConfig::new().apply();
+        PoolConfig::new().capacity(1 << 10).apply();
+        let entries: Vec<_> = entries().iter().map(|v| v.to_istr().unwrap()).collect();
+        assert_eq!(entries, names);
+        assert!(capacity() >= 1 << 10);
+        check_invariants();
+
+        PoolConfig::new().insertion_order(false).apply();
+        check_invariants();
+    }
+
+    #[test]
+    fn test_reader_closed() {
+        let reader = thread::spawn(|| {
+            let name = IStr::new("a string long enough to be pooled");
+            let reader = reader();
+            let id = name.pool_id().unwrap();
+            // Freed with the thread's locals, not by dropping a handle.
+            name.pin();
+
+            assert_eq!(reader.get("a string long enough to be pooled"), Some(id));
+            (reader, id)
+        }).join().unwrap();
+
+        let (reader, id) = reader;
+        assert!(reader.is_closed());
+        assert!(reader.is_empty());
+        assert_eq!(reader.get("a string long enough to be pooled"), None);
+        assert_eq!(reader.resolve(id), None);
+    }
+
+    #[test]
+    fn test_reader_resolve_interning() {
+        let reader = reader();
+        let name = IStr::new("a string resolved on the owner thread");
+        let id = name.pool_id().unwrap();
+
+        let derived = reader.resolve_with(id, |bytes| {
+            IStr::from(format!("{} and interned again", ::std::str::from_utf8(bytes).unwrap()))
+        }).unwrap();
+
+        assert_eq!(derived, "a string resolved on the owner thread and interned again");
+        assert_eq!(reader.get(&derived), derived.pool_id());
+    }
+}
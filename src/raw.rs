@@ -0,0 +1,156 @@
+//! Low-level handles for embedders
+//!
+//! `RawHandle` is an interned value detached from its type and its destructor,
+//! for VMs and FFI layers which keep interned references in their own representation
+//! like NaN-boxed values, and reconstruct them later. Each `RawHandle` made by
+//! `into_raw` owns a refcount, which must be given back by `from_raw` or `decrement_count`.
+
+use std::ptr::NonNull;
+use std::mem;
+
+use handle::Handle;
+use istr::IStr;
+use ibytes::IBytes;
+
+/// Interned value without its type and destructor
+///
+/// It's not `Send` nor `Sync`, as the handles belong to the thread which interned them.
+///
+/// ```
+/// # use internship::{IStr, RawHandle};
+/// let raw = IStr::new("a string stored in a VM value").into_raw();
+///
+/// unsafe {
+///     raw.increment_count();
+///     let value = IStr::from_raw(raw);
+///     assert_eq!(value, "a string stored in a VM value");
+///
+///     raw.decrement_count();
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawHandle {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl RawHandle {
+    #[inline]
+    fn from_handle(handle: Handle) -> Self {
+        let (ptr, len) = handle.into_raw_parts();
+        RawHandle { ptr, len }
+    }
+
+    #[inline]
+    unsafe fn into_handle(self) -> Handle {
+        Handle::from_raw_parts(self.ptr, self.len)
+    }
+
+    /// Pointer and length words of the handle. The pointer is not dereferenceable
+    /// by itself, and its low bit is set if the contents are inlined into the words.
+    #[inline]
+    pub fn into_parts(self) -> (NonNull<u8>, usize) {
+        (self.ptr, self.len)
+    }
+
+    /// Raw handle from the words of `into_parts()`.
+    ///
+    /// # Safety
+    ///
+    /// The words must be from `into_parts()`, with the pointer keeping its provenance.
+    #[inline]
+    pub unsafe fn from_parts(ptr: NonNull<u8>, len: usize) -> Self {
+        RawHandle { ptr, len }
+    }
+
+    /// Whether the contents are inlined, so the refcount operations are no-ops.
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        self.ptr.as_ptr().addr() & 1 == 1
+    }
+
+    /// Add a refcount to the value, to be given back by `from_raw` or `decrement_count`.
+    ///
+    /// # Safety
+    ///
+    /// It must be from `into_raw` on this thread, and the value must still be alive.
+    #[inline]
+    pub unsafe fn increment_count(&self) {
+        let handle = mem::ManuallyDrop::new(self.into_handle());
+        mem::forget(Handle::clone(&handle));
+    }
+
+    /// Give back a refcount of the value, removing it from the pool if it was the last one.
+    ///
+    /// # Safety
+    ///
+    /// It must be from `into_raw` on this thread, and own a refcount not given back yet.
+    #[inline]
+    pub unsafe fn decrement_count(&self) {
+        drop(self.into_handle());
+    }
+}
+
+impl IStr {
+    /// Detach the string from its type and destructor. See `RawHandle`.
+    #[inline]
+    pub fn into_raw(self) -> RawHandle {
+        RawHandle::from_handle(self.0)
+    }
+
+    /// Take back the string from the raw handle.
+    ///
+    /// # Safety
+    ///
+    /// It must be from `IStr::into_raw` on this thread, and own a refcount not given back yet.
+    #[inline]
+    pub unsafe fn from_raw(raw: RawHandle) -> IStr {
+        IStr(raw.into_handle())
+    }
+}
+
+impl IBytes {
+    /// Detach the bytes from its type and destructor. See `RawHandle`.
+    #[inline]
+    pub fn into_raw(self) -> RawHandle {
+        RawHandle::from_handle(self.0)
+    }
+
+    /// Take back the bytes from the raw handle.
+    ///
+    /// # Safety
+    ///
+    /// It must be from `into_raw` of `IBytes` or `IStr` on this thread,
+    /// and own a refcount not given back yet.
+    #[inline]
+    pub unsafe fn from_raw(raw: RawHandle) -> IBytes {
+        IBytes(raw.into_handle())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pool;
+
+    #[test]
+    fn test_refcount() {
+        let src = "a string kept only by raw handles";
+        let raw = IStr::new(src).into_raw();
+        assert!(!raw.is_inline());
+
+        unsafe {
+            raw.increment_count();
+            raw.decrement_count();
+            assert_eq!(IStr::from_raw(raw), src);
+        }
+        assert!(!pool::entries().iter().any(|entry| entry == src.as_bytes()));
+
+        let short = IStr::new("short").into_raw();
+        assert!(short.is_inline());
+        unsafe {
+            let (ptr, len) = short.into_parts();
+            assert_eq!(IBytes::from_raw(RawHandle::from_parts(ptr, len)), b"short"[..]);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Rayon integration
+
+use std::sync::Arc;
+
+use rayon_crate::{self, Scope};
+
+use shared::{SharedPool, Localizer};
+
+/// Run `op` within a rayon scope with a `SharedPool` accessible from every worker.
+///
+/// Strings interned by workers are deduplicated across threads. Use the returned `Localizer`
+/// to resolve the `SharedStr`s from the result into `IStr`s, which interns each unique string
+/// into the caller's thread local pool exactly once, on its first lookup.
+///
+/// ```
+/// # extern crate internship;
+/// use std::sync::{Arc, Mutex};
+/// use internship::rayon::scope_interned;
+///
+/// # fn main() {
+/// let words = Mutex::new(Vec::new());
+///
+/// let ((), localizer) = scope_interned(|scope, pool| {
+///     for chunk in ["foo bar", "bar baz"].iter() {
+///         let words = &words;
+///         let pool = Arc::clone(pool);
+///         scope.spawn(move |_| {
+///             let interned = chunk.split(' ').map(|w| pool.intern(w));
+///             words.lock().unwrap().extend(interned);
+///         });
+///     }
+/// });
+///
+/// let words: Vec<_> = words.into_inner().unwrap().iter()
+///     .map(|w| localizer.get(w))
+///     .collect();
+/// assert_eq!(words.len(), 4);
+/// assert_eq!(localizer.len(), 3);
+/// # }
+/// ```
+pub fn scope_interned<'scope, OP, R>(op: OP) -> (R, Localizer)
+    where OP: FnOnce(&Scope<'scope>, &Arc<SharedPool>) -> R + Send, R: Send
+{
+    let pool = Arc::new(SharedPool::new());
+    let res = rayon_crate::scope(|scope| op(scope, &pool));
+
+    (res, Localizer::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::{Barrier, Mutex};
+    use rayon_crate::ThreadPoolBuilder;
+    use pool;
+
+    #[test]
+    fn test_scope_on_workers() {
+        const WORKERS: usize = 4;
+        let src = "a string interned by every worker of the scope";
+        let workers = ThreadPoolBuilder::new().num_threads(WORKERS).build().unwrap();
+
+        let (threads, before, after) = workers.install(|| {
+            let before = pool::len();
+            let barrier = Barrier::new(WORKERS);
+            let results = Mutex::new(Vec::new());
+
+            let ((), localizer) = scope_interned(|scope, shared| {
+                for _ in 0..WORKERS {
+                    let (barrier, results, shared) = (&barrier, &results, Arc::clone(shared));
+                    scope.spawn(move |_| {
+                        // Every task waits for the others, so they run on distinct workers.
+                        barrier.wait();
+                        let value = shared.intern(src);
+                        results.lock().unwrap().push((rayon_crate::current_thread_index(), value));
+                    });
+                }
+            });
+
+            let results = results.into_inner().unwrap();
+            for (_, value) in &results {
+                assert_eq!(&**value, src);
+                assert!(value.ptr_eq(&results[0].1));
+                assert_eq!(localizer.get(value), src);
+            }
+            assert_eq!(localizer.len(), 1);
+
+            let threads: HashSet<_> = results.iter().map(|&(thread, _)| thread).collect();
+            drop(localizer);
+            (threads.len(), before, pool::len())
+        });
+
+        assert_eq!(threads, WORKERS);
+        assert_eq!(after, before);
+    }
+}
@@ -0,0 +1,290 @@
+//! Log records with interned fields
+//!
+//! Structured logs repeat the same field names on every line, and often the same values
+//! like levels, targets and hostnames. A `Record` keeps both interned, so building one
+//! is mostly copying handles, and its serializers reuse the escaped form
+//! cached per pool entry instead of escaping the same strings again.
+//!
+//! ```
+//! # use internship::IStr;
+//! # use internship::record::Record;
+//! let target = IStr::new("server::http");
+//! let record = Record::new()
+//!     .with("level", "info")
+//!     .with("target", target)
+//!     .with("msg", "request \"done\"");
+//!
+//! assert_eq!(record.to_string(), r#"level=info target=server::http msg="request \"done\"""#);
+//! assert_eq!(record.get("level").unwrap().as_str(), Some("info"));
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+use istr::IStr;
+use ibytes::IBytes;
+
+/// Value of a field
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldValue {
+    Str(IStr),
+    /// Bytes are written as UTF-8 lossily by the text serializers.
+    Bytes(IBytes),
+}
+
+/// Field of a record
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Field {
+    pub(crate) key: IStr,
+    pub(crate) value: FieldValue,
+}
+
+/// Ordered list of fields
+///
+/// Fields are kept in their insertion order, and keys may be repeated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Record {
+    pub(crate) fields: Vec<Field>,
+}
+
+impl FieldValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            FieldValue::Str(ref v) => Some(v),
+            FieldValue::Bytes(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        match *self {
+            FieldValue::Str(ref v) => v.as_bytes(),
+            FieldValue::Bytes(ref v) => v,
+        }
+    }
+
+    // Escaped form for JSON strings, which is cached per pool entry of the strings.
+    fn escape_json(&self) -> IStr {
+        match *self {
+            FieldValue::Str(ref v) => v.escape_json(),
+            FieldValue::Bytes(ref v) => match v.to_istr() {
+                Ok(v) => v.escape_json(),
+                Err(_) => IStr::new(&String::from_utf8_lossy(v)).escape_json(),
+            },
+        }
+    }
+
+    fn to_str_lossy(&self) -> Cow<'_, str> {
+        match *self {
+            FieldValue::Str(ref v) => Cow::Borrowed(v),
+            FieldValue::Bytes(ref v) => String::from_utf8_lossy(v),
+        }
+    }
+}
+
+impl From<IStr> for FieldValue {
+    #[inline]
+    fn from(v: IStr) -> Self {
+        FieldValue::Str(v)
+    }
+}
+
+impl<'a> From<&'a str> for FieldValue {
+    #[inline]
+    fn from(v: &'a str) -> Self {
+        FieldValue::Str(IStr::new(v))
+    }
+}
+
+impl From<String> for FieldValue {
+    #[inline]
+    fn from(v: String) -> Self {
+        FieldValue::Str(IStr::from(v))
+    }
+}
+
+impl From<IBytes> for FieldValue {
+    #[inline]
+    fn from(v: IBytes) -> Self {
+        FieldValue::Bytes(v)
+    }
+}
+
+impl<'a> From<&'a [u8]> for FieldValue {
+    #[inline]
+    fn from(v: &'a [u8]) -> Self {
+        FieldValue::Bytes(IBytes::new(v))
+    }
+}
+
+impl Field {
+    pub fn new<K: Into<IStr>, V: Into<FieldValue>>(key: K, value: V) -> Self {
+        Field {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    #[inline]
+    pub fn key(&self) -> &IStr {
+        &self.key
+    }
+
+    #[inline]
+    pub fn value(&self) -> &FieldValue {
+        &self.value
+    }
+}
+
+impl Record {
+    pub fn new() -> Self {
+        Record::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Record {
+            fields: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Append the field.
+    pub fn push<K: Into<IStr>, V: Into<FieldValue>>(&mut self, key: K, value: V) {
+        self.fields.push(Field::new(key, value));
+    }
+
+    /// Append the field, returning the record.
+    pub fn with<K: Into<IStr>, V: Into<FieldValue>>(mut self, key: K, value: V) -> Self {
+        self.push(key, value);
+        self
+    }
+
+    /// Value of the first field of the key.
+    pub fn get(&self, key: &str) -> Option<&FieldValue> {
+        self.fields.iter().find(|field| field.key == key).map(Field::value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Field> {
+        self.fields.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.fields.clear()
+    }
+
+    /// Write the record as a logfmt line, without the trailing newline.
+    /// Values are quoted if they're empty or have spaces, `=`, quotes or control characters.
+    pub fn write_logfmt<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        for (idx, field) in self.fields.iter().enumerate() {
+            if idx > 0 {
+                w.write_char(' ')?;
+            }
+            w.write_str(&field.key)?;
+            w.write_char('=')?;
+
+            let value = field.value.to_str_lossy();
+            if value.is_empty() || value.chars().any(|ch| ch <= ' ' || ch == '=' || ch == '"') {
+                write!(w, "\"{}\"", field.value.escape_json())?;
+            } else {
+                w.write_str(&value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the record as a JSON object. Later fields of the repeated keys are written as is.
+    pub fn write_json<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        w.write_char('{')?;
+
+        for (idx, field) in self.fields.iter().enumerate() {
+            if idx > 0 {
+                w.write_char(',')?;
+            }
+            write!(w, "\"{}\":\"{}\"", field.key.escape_json(), field.value.escape_json())?;
+        }
+
+        w.write_char('}')
+    }
+}
+
+/// logfmt form of the record.
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_logfmt(f)
+    }
+}
+
+impl<K: Into<IStr>, V: Into<FieldValue>> Extend<(K, V)> for Record {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.fields.extend(iter.into_iter().map(|(key, value)| Field::new(key, value)))
+    }
+}
+
+impl<K: Into<IStr>, V: Into<FieldValue>> ::std::iter::FromIterator<(K, V)> for Record {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut record = Record::new();
+        record.extend(iter);
+        record
+    }
+}
+
+impl<'a> IntoIterator for &'a Record {
+    type Item = &'a Field;
+    type IntoIter = ::std::slice::Iter<'a, Field>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fields.iter()
+    }
+}
+
+#[cfg(feature = "serde-compat")]
+impl ::serde_crate::Serialize for FieldValue {
+    fn serialize<S: ::serde_crate::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            FieldValue::Str(ref v) => v.serialize(s),
+            FieldValue::Bytes(ref v) => v.serialize(s),
+        }
+    }
+}
+
+/// Serialized as a map, in the order of the fields.
+#[cfg(feature = "serde-compat")]
+impl ::serde_crate::Serialize for Record {
+    fn serialize<S: ::serde_crate::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_map(self.fields.iter().map(|field| (&field.key, &field.value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_formats() {
+        let record: Record = vec![("a", "x y"), ("b", ""), ("c", "tab\there")].into_iter().collect();
+        let record = record.with("raw", &b"\xffok"[..]);
+
+        assert_eq!(record.to_string(), "a=\"x y\" b=\"\" c=\"tab\\there\" raw=\u{fffd}ok");
+
+        let mut json = String::new();
+        record.write_json(&mut json).unwrap();
+        assert_eq!(json, "{\"a\":\"x y\",\"b\":\"\",\"c\":\"tab\\there\",\"raw\":\"\u{fffd}ok\"}");
+    }
+
+    #[cfg(feature = "serde-compat")]
+    #[test]
+    fn test_serialize() {
+        let record = Record::new().with("level", "warn").with("msg", "a \"quoted\" message");
+        assert_eq!(::serde_json::to_string(&record).unwrap(), r#"{"level":"warn","msg":"a \"quoted\" message"}"#);
+    }
+}
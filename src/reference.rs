@@ -0,0 +1,242 @@
+//! Safe reference implementation of the interned types
+//!
+//! `RefIStr` and `RefIBytes` behave like `IStr` and `IBytes`, but they're built only on
+//! `Rc<[u8]>` and a thread local `HashSet` without any `unsafe` code or inlining.
+//! They're much slower, and meant to be audited and to be an oracle for testing the real ones.
+//!
+//! ```
+//! # use internship::reference::{RefIStr, pool_len};
+//! let a = RefIStr::new("a string interned by the reference");
+//! let b = RefIStr::new("a string interned by the reference");
+//!
+//! assert_eq!(a, b);
+//! assert_eq!(pool_len(), 1);
+//! drop((a, b));
+//! assert_eq!(pool_len(), 0);
+//! ```
+#![forbid(unsafe_code)]
+
+use std::collections::HashSet;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::hash::{Hash, Hasher};
+use std::cmp::Ordering;
+use std::ops::Deref;
+use std::str;
+use std::fmt;
+
+thread_local! {
+    static POOL: RefCell<HashSet<Rc<[u8]>>> = Default::default();
+}
+
+/// Number of the entries in the reference pool of the current thread.
+pub fn pool_len() -> usize {
+    POOL.with(|pool| pool.borrow().len())
+}
+
+/// Whether the reference pool of the current thread has an entry of the contents.
+pub fn pool_contains(src: &[u8]) -> bool {
+    POOL.with(|pool| pool.borrow().contains(src))
+}
+
+/// Interned byte array of the reference implementation
+#[derive(Clone)]
+pub struct RefIBytes(Rc<[u8]>);
+
+/// Interned string of the reference implementation
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RefIStr(RefIBytes);
+
+impl RefIBytes {
+    pub fn new(src: &[u8]) -> Self {
+        POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+
+            if let Some(rc) = pool.get(src) {
+                return RefIBytes(Rc::clone(rc));
+            }
+
+            let rc: Rc<[u8]> = Rc::from(src);
+            pool.insert(Rc::clone(&rc));
+            RefIBytes(rc)
+        })
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Whether both are the same entry. It equals to `==`.
+    #[inline]
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Rc::ptr_eq(&a.0, &b.0)
+    }
+
+    /// Number of the handles to the entry, excluding the pool's.
+    #[inline]
+    pub fn ref_count(&self) -> usize {
+        Rc::strong_count(&self.0) - 1
+    }
+}
+
+impl Drop for RefIBytes {
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.0) == 2 {
+            let _ = POOL.try_with(|pool| pool.borrow_mut().remove(&*self.0));
+        }
+    }
+}
+
+impl RefIStr {
+    pub fn new(src: &str) -> Self {
+        RefIStr(RefIBytes::new(src.as_bytes()))
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        str::from_utf8(self.0.as_bytes()).expect("reference string is not valid UTF-8")
+    }
+
+    #[inline]
+    pub fn as_ref_ibytes(&self) -> &RefIBytes {
+        &self.0
+    }
+}
+
+impl PartialEq for RefIBytes {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        RefIBytes::ptr_eq(self, other)
+    }
+}
+
+impl Eq for RefIBytes {}
+
+impl PartialOrd for RefIBytes {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RefIBytes {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl Hash for RefIBytes {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.as_bytes().hash(hasher)
+    }
+}
+
+impl Deref for RefIBytes {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Deref for RefIStr {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for RefIBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_bytes(), f)
+    }
+}
+
+impl fmt::Debug for RefIStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for RefIStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_same_entry() {
+        let a = RefIBytes::new(b"shared by both handles");
+        let b = RefIBytes::new(b"shared by both handles");
+        let c = RefIBytes::new(b"another entry");
+
+        assert!(RefIBytes::ptr_eq(&a, &b));
+        assert!(!RefIBytes::ptr_eq(&a, &c));
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.ref_count(), 2);
+        assert_eq!(a.clone().ref_count(), 3);
+    }
+
+    #[test]
+    fn test_removed_on_drop() {
+        let data = &b"removed with its last handle"[..];
+        let a = RefIBytes::new(data);
+        let b = a.clone();
+        assert!(pool_contains(data));
+
+        drop(a);
+        assert!(pool_contains(data));
+        drop(b);
+        assert!(!pool_contains(data));
+        assert_eq!(RefIBytes::new(data).ref_count(), 1);
+    }
+
+    #[test]
+    fn test_empty() {
+        let empty = RefIStr::new("");
+
+        assert_eq!(empty.as_str(), "");
+        assert_eq!(empty, RefIStr::new(""));
+        assert!(pool_contains(b""));
+    }
+
+    #[test]
+    fn test_order_and_hash() {
+        let mut strs: Vec<_> = ["b", "ab", "a", ""].iter().map(|&s| RefIStr::new(s)).collect();
+        strs.sort();
+
+        let sorted: Vec<&str> = strs.iter().map(|s| s.as_str()).collect();
+        assert_eq!(sorted, ["", "a", "ab", "b"]);
+        assert_eq!(hash_of(&RefIBytes::new(b"hashed")), hash_of(&&b"hashed"[..]));
+    }
+
+    #[test]
+    fn test_thread_local() {
+        let data = "interned on the other thread only";
+        let other = ::std::thread::spawn(move || {
+            let s = RefIStr::new(data);
+            (pool_contains(data.as_bytes()), s.to_string())
+        }).join().unwrap();
+
+        assert_eq!(other, (true, data.to_string()));
+        assert!(!pool_contains(data.as_bytes()));
+    }
+}
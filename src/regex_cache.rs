@@ -0,0 +1,57 @@
+use std::rc::Rc;
+
+use regex::{Regex, Error};
+
+use istr::IStr;
+use side;
+
+// Wrapped so that no one else can remove it from the side table.
+struct Compiled(Result<Rc<Regex>, Error>);
+
+impl IStr {
+    /// Compile the string as a regex pattern.
+    ///
+    /// The result is cached in a side table, so each unique pattern is compiled once per thread.
+    /// Results of inlined patterns have no pool entry to be dropped with, and are kept until the thread exits.
+    ///
+    /// ```
+    /// # use internship::IStr;
+    /// # use std::rc::Rc;
+    /// let pattern = IStr::new(r"^\d{4}-\d{2}-\d{2}$");
+    /// assert!(pattern.regex().unwrap().is_match("2018-05-01"));
+    ///
+    /// let again = IStr::new(r"^\d{4}-\d{2}-\d{2}$");
+    /// assert!(Rc::ptr_eq(&pattern.regex().unwrap(), &again.regex().unwrap()));
+    /// assert!(IStr::new("(unclosed").regex().is_err());
+    /// ```
+    pub fn regex(&self) -> Result<Rc<Regex>, Error> {
+        let compiled = side::entry(self).or_insert_with(|| Compiled(Regex::new(self).map(Rc::new)));
+        compiled.0.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_cached() {
+        let pattern = IStr::new("(an unclosed group in the pattern");
+        let err = pattern.regex().unwrap_err();
+        let compiled = side::entry::<Compiled>(&pattern).get().unwrap();
+
+        assert_eq!(IStr::new("(an unclosed group in the pattern").regex().unwrap_err(), err);
+        let again = side::entry::<Compiled>(&pattern).get().unwrap();
+        assert!(Rc::ptr_eq(&compiled, &again));
+    }
+
+    #[test]
+    fn test_shared_regex() {
+        for src in &[r"^[a-z]+$", r"^(?:GET|POST) /api/v\d+/[a-z]+$"] {
+            let first = IStr::new(src);
+            let second = IStr::new(src);
+            assert!(Rc::ptr_eq(&first.regex().unwrap(), &second.regex().unwrap()));
+            assert!(Rc::ptr_eq(&first.regex().unwrap(), &first.clone().regex().unwrap()));
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::ops::Deref;
+use std::hash::{Hash, Hasher};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::sync::{Arc, Weak};
+use std::fmt;
+
+#[cfg(not(feature = "forbid-unsafe"))]
+use handle::INLINE_MAX_LEN;
+use istr::IStr;
+
+/// `Send`able form of `IStr`
+///
+/// `IStr` can't be sent to other threads, which makes it impossible to hold it
+/// across `.await` points of multi-threaded executors.
+/// `SendIStr` carries its contents instead, and interns them again
+/// on the first `to_istr()` at the other side. The interned string is cached
+/// by each thread which called it, so later calls on the same thread only clone it.
+/// Caches are dropped with the `SendIStr` on its thread, or with the thread's locals,
+/// and those of the `SendIStr`s dropped on other threads are swept as the cache grows.
+///
+/// Inlined strings are kept as is since they don't refer the thread local pool.
+/// With the `forbid-unsafe` feature, every string is carried as `Box<str>`
+/// as the handles can't be marked as `Send`.
+pub struct SendIStr(Repr);
+
+enum Repr {
+    // Only holds inlined handle.
+    #[cfg(not(feature = "forbid-unsafe"))]
+    Inline(IStr),
+    Heap(Box<str>, Cache),
+}
+
+// Inlined handle does not own any pointer, and the heap representation is
+// plain `Box<str>` with the token of its caches.
+#[cfg(not(feature = "forbid-unsafe"))]
+unsafe impl Send for SendIStr {}
+#[cfg(not(feature = "forbid-unsafe"))]
+unsafe impl Sync for SendIStr {}
+
+thread_local! {
+    static CACHE: RefCell<LocalCache> = RefCell::new(LocalCache::default());
+}
+
+// Strings interned on this thread, by the address of the token of their `SendIStr`.
+// The `Weak` keeps the address from being reused until the entry is removed.
+#[derive(Default)]
+struct LocalCache {
+    entries: HashMap<usize, (Weak<()>, IStr)>,
+    // Number of the entries after the last sweep.
+    swept: usize,
+}
+
+impl LocalCache {
+    fn insert(&mut self, token: &Arc<()>, value: IStr) -> Vec<IStr> {
+        let stale = if self.entries.len() >= 2 * self.swept.max(8) {
+            self.sweep()
+        } else {
+            Vec::new()
+        };
+
+        self.entries.insert(addr(token), (Arc::downgrade(token), value));
+        stale
+    }
+
+    // Remove the entries of the `SendIStr`s dropped on other threads.
+    fn sweep(&mut self) -> Vec<IStr> {
+        let dead: Vec<usize> = self.entries.iter()
+            .filter(|&(_, (token, _))| token.strong_count() == 0)
+            .map(|(&key, _)| key)
+            .collect();
+        let stale = dead.iter().filter_map(|key| self.entries.remove(key)).map(|entry| entry.1).collect();
+
+        self.swept = self.entries.len();
+        stale
+    }
+}
+
+#[inline]
+fn addr(token: &Arc<()>) -> usize {
+    Arc::as_ptr(token).addr()
+}
+
+// Token of the caches of a `SendIStr`, not shared with its clones.
+struct Cache(Arc<()>);
+
+impl Cache {
+    fn new() -> Self {
+        Cache(Arc::new(()))
+    }
+
+    fn get_or_intern(&self, src: &str) -> IStr {
+        let cached = CACHE.try_with(|cache| {
+            cache.borrow().entries.get(&addr(&self.0)).map(|entry| entry.1.clone())
+        });
+
+        match cached {
+            Ok(Some(value)) => value,
+            Ok(None) => {
+                let value = IStr::new(src);
+                let stale = CACHE.with(|cache| cache.borrow_mut().insert(&self.0, value.clone()));
+                // Dropped after the borrow is released, as they may free the pool entries.
+                drop(stale);
+                value
+            }
+            // This thread's cache is already destroyed.
+            Err(_) => IStr::new(src),
+        }
+    }
+
+    fn take_local(&mut self) -> Option<IStr> {
+        let key = addr(&self.0);
+        let taken = CACHE.try_with(|cache| cache.borrow_mut().entries.remove(&key)).ok()??;
+        Some(taken.1)
+    }
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        self.take_local();
+    }
+}
+
+impl SendIStr {
+    pub fn new(src: &str) -> Self {
+        #[cfg(not(feature = "forbid-unsafe"))]
+        {
+            if src.len() <= INLINE_MAX_LEN {
+                return SendIStr(Repr::Inline(IStr::new(src)));
+            }
+        }
+
+        SendIStr(Repr::Heap(src.into(), Cache::new()))
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self.0 {
+            #[cfg(not(feature = "forbid-unsafe"))]
+            Repr::Inline(ref v) => v.as_str(),
+            Repr::Heap(ref v, _) => v,
+        }
+    }
+
+    /// Intern the contents on current thread, or clone the one cached by the last call
+    /// if it was on the same thread.
+    #[inline]
+    pub fn to_istr(&self) -> IStr {
+        match self.0 {
+            #[cfg(not(feature = "forbid-unsafe"))]
+            Repr::Inline(ref v) => v.clone(),
+            Repr::Heap(ref v, ref cache) => cache.get_or_intern(v),
+        }
+    }
+
+    #[inline]
+    pub fn into_istr(self) -> IStr {
+        match self.0 {
+            #[cfg(not(feature = "forbid-unsafe"))]
+            Repr::Inline(v) => v,
+            Repr::Heap(v, mut cache) => cache.take_local().unwrap_or_else(|| IStr::from(v)),
+        }
+    }
+}
+
+impl Clone for SendIStr {
+    fn clone(&self) -> Self {
+        match self.0 {
+            #[cfg(not(feature = "forbid-unsafe"))]
+            Repr::Inline(ref v) => SendIStr(Repr::Inline(v.clone())),
+            Repr::Heap(ref v, _) => SendIStr(Repr::Heap(v.clone(), Cache::new())),
+        }
+    }
+}
+
+impl From<IStr> for SendIStr {
+    fn from(v: IStr) -> Self {
+        #[cfg(not(feature = "forbid-unsafe"))]
+        {
+            if v.0.is_inline() {
+                return SendIStr(Repr::Inline(v));
+            }
+        }
+
+        SendIStr(Repr::Heap(v.as_str().into(), Cache::new()))
+    }
+}
+
+impl From<&IStr> for SendIStr {
+    fn from(v: &IStr) -> Self {
+        SendIStr::from(v.clone())
+    }
+}
+
+impl From<&str> for SendIStr {
+    fn from(v: &str) -> Self {
+        SendIStr::new(v)
+    }
+}
+
+impl From<SendIStr> for IStr {
+    #[inline]
+    fn from(v: SendIStr) -> Self {
+        v.into_istr()
+    }
+}
+
+impl Deref for SendIStr {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for SendIStr {
+    fn eq(&self, other: &SendIStr) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SendIStr {}
+
+impl Hash for SendIStr {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        Hash::hash(self.as_str(), hasher)
+    }
+}
+
+impl Borrow<str> for SendIStr {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for SendIStr {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for SendIStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for SendIStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+/// `clap` stores parsed values as `Send + Sync`, so CLI arguments are declared as `SendIStr`
+/// and interned with `into_istr()` after parsing.
+#[cfg(feature = "clap")]
+mod clap_compat {
+    use super::*;
+    use clap::builder::{MapValueParser, StringValueParser, TypedValueParser, ValueParserFactory};
+
+    impl ValueParserFactory for SendIStr {
+        type Parser = MapValueParser<StringValueParser, fn(String) -> SendIStr>;
+
+        fn value_parser() -> Self::Parser {
+            StringValueParser::new().map(|s| SendIStr::new(&s))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_send() {
+        let short = SendIStr::from(IStr::new("foo"));
+        let long = SendIStr::from(IStr::new("Lorem ipsum dolor sit amet, consectetur adipiscing elit"));
+
+        let (short, long) = thread::spawn(move || {
+            assert_eq!(short.to_istr(), "foo");
+            (short, long.into_istr().into())
+        }).join().unwrap();
+
+        let long: SendIStr = long;
+        assert_eq!(short.into_istr(), IStr::new("foo"));
+        assert_eq!(long.into_istr(), "Lorem ipsum dolor sit amet, consectetur adipiscing elit");
+    }
+
+    #[test]
+    fn test_cache() {
+        let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit";
+        let value = SendIStr::new(text);
+        let in_pool = || ::pool::entries().iter().any(|entry| entry == text.as_bytes());
+
+        drop(value.to_istr());
+        assert!(in_pool());
+        assert!(value.to_istr().is(&value.to_istr()));
+
+        // Interned again on the other thread, and dropped with its locals.
+        let value = thread::spawn(move || {
+            assert_eq!(value.to_istr(), text);
+            value
+        }).join().unwrap();
+        assert!(in_pool());
+
+        drop(value.to_istr());
+        drop(value);
+        assert!(!in_pool());
+    }
+
+    #[test]
+    fn test_cache_left_behind() {
+        let text = "a string cached by every thread it's sent to";
+        let mut value = SendIStr::new(text);
+
+        for _ in 0..4 {
+            value = thread::spawn(move || {
+                assert_eq!(value.to_istr(), text);
+                value
+            }).join().unwrap();
+        }
+
+        let token = match value.0 {
+            Repr::Heap(_, ref cache) => Arc::clone(&cache.0),
+            #[cfg(not(feature = "forbid-unsafe"))]
+            Repr::Inline(_) => unreachable!(),
+        };
+        // Caches of the exited threads are gone with them.
+        assert_eq!(Arc::weak_count(&token), 0);
+
+        // Those of the values dropped on other threads are swept by the later misses.
+        for n in 0..64 {
+            let value = SendIStr::from(format!("a string cached here and dropped elsewhere {}", n).as_str());
+            drop(value.to_istr());
+            thread::spawn(move || drop(value)).join().unwrap();
+        }
+        let left = ::pool::entries().iter().filter(|entry| entry.starts_with(b"a string cached here")).count();
+        assert!(left <= 16);
+        assert_eq!(CACHE.with(|cache| cache.borrow().entries.len()), left);
+    }
+
+    #[cfg(feature = "clap")]
+    #[test]
+    fn test_clap_value_parser() {
+        use clap::{value_parser, Arg, Command};
+
+        let matches = Command::new("app")
+            .arg(Arg::new("name").long("name").value_parser(value_parser!(SendIStr)))
+            .get_matches_from(["app", "--name", "subcommand-name"]);
+
+        let name = matches.get_one::<SendIStr>("name").unwrap().to_istr();
+        assert_eq!(name, IStr::new("subcommand-name"));
+    }
+}
@@ -0,0 +1,192 @@
+//! Serde helpers for the interned types
+//!
+//! `IBytes` is serialized as a sequence of integers like `[u8]`, regardless of the features.
+//! Modules here serialize it in other forms, to be used with `#[serde(with = "...")]`
+//! on the fields which need them.
+
+use std::borrow::Cow;
+use std::ops::Deref;
+use std::fmt;
+
+use serde_crate::{Serialize, Serializer, Deserialize, Deserializer, de};
+
+use istr::IStr;
+
+pub use dedup::Dedup;
+
+/// String borrowed from the input if possible, interned only when it's kept
+///
+/// Deserializing records into `MaybeInterned` fields defers the interning
+/// until after they're filtered, so discarded records never touch the pool.
+/// Strings which can't be borrowed, e.g. with escape sequences in JSON, are copied.
+///
+/// ```
+/// # extern crate serde_json;
+/// # extern crate internship;
+/// # use internship::IStr;
+/// # use internship::serde::MaybeInterned;
+/// # fn main() {
+/// let input = r#"["keep", "drop", "keep"]"#;
+/// let words: Vec<MaybeInterned> = serde_json::from_str(input).unwrap();
+/// assert!(words.iter().all(MaybeInterned::is_borrowed));
+///
+/// let kept: Vec<IStr> = words.into_iter()
+///     .filter(|word| word == "keep")
+///     .map(MaybeInterned::into_owned)
+///     .collect();
+/// assert_eq!(kept, ["keep", "keep"]);
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaybeInterned<'a>(Cow<'a, str>);
+
+impl<'a> MaybeInterned<'a> {
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether it's borrowed from the input.
+    #[inline]
+    pub fn is_borrowed(&self) -> bool {
+        match self.0 {
+            Cow::Borrowed(_) => true,
+            Cow::Owned(_) => false,
+        }
+    }
+
+    /// Intern the string.
+    pub fn into_owned(self) -> IStr {
+        IStr::from(self.0)
+    }
+
+    #[inline]
+    pub fn into_cow(self) -> Cow<'a, str> {
+        self.0
+    }
+}
+
+impl Deref for MaybeInterned<'_> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for MaybeInterned<'_> {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<'a> From<&'a str> for MaybeInterned<'a> {
+    #[inline]
+    fn from(v: &'a str) -> Self {
+        MaybeInterned(Cow::Borrowed(v))
+    }
+}
+
+impl From<MaybeInterned<'_>> for IStr {
+    #[inline]
+    fn from(v: MaybeInterned) -> Self {
+        v.into_owned()
+    }
+}
+
+impl PartialEq<str> for MaybeInterned<'_> {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for MaybeInterned<'_> {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for MaybeInterned<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for MaybeInterned<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for MaybeInterned<'_> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
+impl<'d: 'a, 'a> Deserialize<'d> for MaybeInterned<'a> {
+    fn deserialize<D: Deserializer<'d>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_str(Visitor)
+    }
+}
+
+struct Visitor;
+
+impl<'d> de::Visitor<'d> for Visitor {
+    type Value = MaybeInterned<'d>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("string slice")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, value: &'d str) -> Result<Self::Value, E> {
+        Ok(MaybeInterned(Cow::Borrowed(value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(MaybeInterned(Cow::Owned(value.into())))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(MaybeInterned(Cow::Owned(value)))
+    }
+}
+
+/// Serialize `IBytes` as a native byte string, e.g. `bin` of MessagePack,
+/// with `#[serde(with = "internship::serde::bytes")]`.
+///
+/// ```
+/// # extern crate rmp_serde;
+/// # extern crate internship;
+/// # use internship::IBytes;
+/// # use internship::serde::bytes;
+/// # fn main() {
+/// let value = IBytes::new(&[0xde, 0xad]);
+///
+/// let mut encoded = Vec::new();
+/// bytes::serialize(&value, &mut rmp_serde::Serializer::new(&mut encoded)).unwrap();
+/// assert_eq!(encoded, [0xc4, 2, 0xde, 0xad]);
+///
+/// let decoded = bytes::deserialize(&mut rmp_serde::Deserializer::new(&encoded[..])).unwrap();
+/// assert_eq!(decoded, value);
+/// # }
+/// ```
+pub mod bytes {
+    use serde_crate::{Serializer, Deserializer};
+
+    use ibytes::IBytes;
+    use ibytes::serde_compat::Visitor;
+
+    pub fn serialize<S: Serializer>(value: &IBytes, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(value.as_bytes())
+    }
+
+    /// Formats without byte strings may give sequences of integers, which are accepted too.
+    pub fn deserialize<'d, D: Deserializer<'d>>(d: D) -> Result<IBytes, D::Error> {
+        d.deserialize_bytes(Visitor)
+    }
+}
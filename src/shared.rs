@@ -0,0 +1,514 @@
+//! Shared pool
+//!
+//! Interning pool which can be accessed from multiple threads.
+//! Unlike the thread local pool, entries of the shared pool live until the pool is dropped,
+//! which makes it suitable for scoped parallel work.
+//! Its results can be moved into the thread local pool with a `Localizer`.
+//!
+//! On multi-socket machines, the pool can keep a cache per NUMA node with `SharedPool::with_topology`.
+//! Interning threads look up the cache of their own node first, so hot strings don't bounce
+//! between sockets. Each cache keeps its own replica of the string, so strings interned
+//! on different nodes are equal but don't share their allocation. It's a trade of memory
+//! for locality: a string interned on every node is allocated once per node plus once for the pool.
+
+use std::collections::{HashSet, HashMap};
+use std::collections::hash_map::RandomState;
+use std::hash::{Hash, Hasher, BuildHasher};
+use std::sync::{Arc, Mutex};
+use std::ops::Deref;
+use std::borrow::Borrow;
+use std::cell::{Cell, RefCell};
+use std::thread;
+use std::fs;
+use std::path::Path;
+use std::fmt;
+
+use istr::IStr;
+
+#[cfg(feature = "epoch")]
+mod epoch;
+
+#[cfg(feature = "epoch")]
+pub use self::epoch::EpochPool;
+
+#[cfg(feature = "arc")]
+mod arc;
+
+#[cfg(feature = "arc")]
+pub use self::arc::{ArcIStr, ArcIBytes};
+
+/// String interned in the `SharedPool`
+///
+/// `SharedStr` is `Send` and `Sync`. Strings interned in the same pool
+/// share their allocation and can be compared by pointer,
+/// unless they're interned on different nodes of a pool with topology.
+#[derive(Clone, Eq, PartialOrd, Ord)]
+pub struct SharedStr(pub(crate) Arc<str>);
+
+impl SharedStr {
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Check whether both strings are same entry of the same pool.
+    #[inline]
+    pub fn ptr_eq(&self, other: &SharedStr) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    #[inline]
+    fn addr(&self) -> usize {
+        self.0.as_ptr().addr()
+    }
+}
+
+impl Deref for SharedStr {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for SharedStr {
+    #[inline]
+    fn eq(&self, other: &SharedStr) -> bool {
+        self.ptr_eq(other) || self.as_str() == other.as_str()
+    }
+}
+
+impl Hash for SharedStr {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        Hash::hash(self.as_str(), hasher)
+    }
+}
+
+impl Borrow<str> for SharedStr {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for SharedStr {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for SharedStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+type Shards = Box<[Mutex<HashSet<Arc<str>>>]>;
+
+fn new_shards(count: usize) -> Shards {
+    (0..count.max(1)).map(|_| Default::default()).collect()
+}
+
+/// Interning pool shared between threads
+///
+/// The pool is split into shards each guarded by its own lock,
+/// to reduce contention between threads interning at the same time.
+pub struct SharedPool {
+    shards: Shards,
+    hasher: RandomState,
+    topology: Option<Topology>,
+    node_caches: Box<[Shards]>,
+}
+
+/// Mapping from CPUs to NUMA nodes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topology {
+    node_of_cpu: Box<[usize]>,
+    nodes: usize,
+}
+
+thread_local! {
+    static CURRENT_NODE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+impl Topology {
+    /// Create a topology from the NUMA node of each CPU, indexed by CPU number.
+    pub fn new(node_of_cpu: Vec<usize>) -> Self {
+        let nodes = node_of_cpu.iter().max().map_or(1, |max| max + 1);
+
+        Topology {
+            node_of_cpu: node_of_cpu.into(),
+            nodes,
+        }
+    }
+
+    /// Read the topology from sysfs. Returns `None` if it's not available, e.g. not on Linux.
+    pub fn detect() -> Option<Self> {
+        Topology::detect_in(Path::new("/sys/devices/system/node"))
+    }
+
+    fn detect_in(root: &Path) -> Option<Self> {
+        let mut node_of_cpu = Vec::new();
+
+        for entry in fs::read_dir(root).ok()? {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let node: usize = match name.to_str().and_then(|name| name.strip_prefix("node")) {
+                Some(num) => num.parse().ok()?,
+                None => continue,
+            };
+            let cpulist = fs::read_to_string(entry.path().join("cpulist")).ok()?;
+
+            for range in cpulist.trim().split(',').filter(|range| !range.is_empty()) {
+                let (start, end) = match range.find('-') {
+                    Some(idx) => (range[..idx].parse().ok()?, range[idx + 1..].parse().ok()?),
+                    None => {
+                        let cpu: usize = range.parse().ok()?;
+                        (cpu, cpu)
+                    }
+                };
+
+                if node_of_cpu.len() <= end {
+                    node_of_cpu.resize(end + 1, 0);
+                }
+                for slot in &mut node_of_cpu[start..=end] {
+                    *slot = node;
+                }
+            }
+        }
+
+        if node_of_cpu.is_empty() {
+            None
+        } else {
+            Some(Topology::new(node_of_cpu))
+        }
+    }
+
+    #[inline]
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    #[inline]
+    pub fn node_of_cpu(&self, cpu: usize) -> Option<usize> {
+        self.node_of_cpu.get(cpu).cloned()
+    }
+}
+
+/// Pin the current thread to the NUMA node, so pools with topology use the node's cache
+/// without asking the OS for the current CPU. `None` removes the pin.
+pub fn pin_thread_to_node(node: Option<usize>) {
+    CURRENT_NODE.with(|current| current.set(node));
+}
+
+#[cfg(all(feature = "numa", target_os = "linux"))]
+fn current_cpu() -> Option<usize> {
+    let cpu = unsafe { libc::sched_getcpu() };
+
+    if cpu < 0 {
+        None
+    } else {
+        Some(cpu as usize)
+    }
+}
+
+#[cfg(not(all(feature = "numa", target_os = "linux")))]
+fn current_cpu() -> Option<usize> {
+    None
+}
+
+impl SharedPool {
+    /// Create a pool with the number of shards based on available parallelism.
+    pub fn new() -> Self {
+        let shards = thread::available_parallelism()
+            .map(|n| n.get() * 4)
+            .unwrap_or(16);
+
+        SharedPool::with_shards(shards)
+    }
+
+    pub fn with_shards(shards: usize) -> Self {
+        SharedPool {
+            shards: new_shards(shards),
+            hasher: RandomState::new(),
+            topology: None,
+            node_caches: Box::new([]),
+        }
+    }
+
+    /// Create a pool with a cache per NUMA node, each having `shards` shards.
+    ///
+    /// Threads are routed to their node's cache by `pin_thread_to_node`,
+    /// or with the `numa` feature on Linux, by the CPU they're currently running on.
+    /// Threads of unknown node skip the caches.
+    ///
+    /// The caches don't share the allocations of the pool. A string interned on `n` nodes
+    /// takes `n + 1` allocations, so it's worth it only for small sets of hot strings.
+    pub fn with_topology(topology: Topology, shards: usize) -> Self {
+        SharedPool {
+            shards: new_shards(shards),
+            hasher: RandomState::new(),
+            node_caches: (0..topology.nodes).map(|_| new_shards(shards)).collect(),
+            topology: Some(topology),
+        }
+    }
+
+    #[inline]
+    pub fn topology(&self) -> Option<&Topology> {
+        self.topology.as_ref()
+    }
+
+    #[inline]
+    fn shard_index(&self, src: &str) -> usize {
+        self.hasher.hash_one(src) as usize % self.shards.len()
+    }
+
+    fn node_cache(&self, index: usize) -> Option<&Mutex<HashSet<Arc<str>>>> {
+        let topology = self.topology.as_ref()?;
+        let node = CURRENT_NODE.with(Cell::get)
+            .or_else(|| current_cpu().and_then(|cpu| topology.node_of_cpu(cpu)))?;
+
+        self.node_caches.get(node).map(|cache| &cache[index])
+    }
+
+    pub fn intern(&self, src: &str) -> SharedStr {
+        let index = self.shard_index(src);
+        let cache = self.node_cache(index);
+
+        if let Some(cache) = cache {
+            let cache = cache.lock().unwrap_or_else(|err| err.into_inner());
+
+            if let Some(rc) = cache.get(src) {
+                return SharedStr(Arc::clone(rc));
+            }
+        }
+
+        let rc = {
+            let mut shard = self.shards[index].lock().unwrap_or_else(|err| err.into_inner());
+
+            match shard.get(src) {
+                Some(rc) => Arc::clone(rc),
+                None => {
+                    let rc: Arc<str> = Arc::from(src);
+                    shard.insert(Arc::clone(&rc));
+                    rc
+                }
+            }
+        };
+
+        match cache {
+            // The node gets its own replica, so its threads don't share
+            // the refcount of the home allocation with other nodes.
+            Some(cache) => {
+                let mut cache = cache.lock().unwrap_or_else(|err| err.into_inner());
+
+                if let Some(replica) = cache.get(src) {
+                    return SharedStr(Arc::clone(replica));
+                }
+
+                let replica: Arc<str> = Arc::from(src);
+                cache.insert(Arc::clone(&replica));
+                SharedStr(replica)
+            }
+            None => SharedStr(rc),
+        }
+    }
+
+    pub fn contains(&self, src: &str) -> bool {
+        self.shards[self.shard_index(src)].lock().unwrap_or_else(|err| err.into_inner()).contains(src)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter()
+            .map(|shard| shard.lock().unwrap_or_else(|err| err.into_inner()).len())
+            .sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every entry from the pool. Existing `SharedStr`s remain valid.
+    pub fn clear(&self) {
+        for shard in self.shards.iter().chain(self.node_caches.iter().flat_map(|cache| cache.iter())) {
+            shard.lock().unwrap_or_else(|err| err.into_inner()).clear();
+        }
+    }
+}
+
+impl Default for SharedPool {
+    fn default() -> Self {
+        SharedPool::new()
+    }
+}
+
+impl fmt::Debug for SharedPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedPool")
+            .field("shards", &self.shards.len())
+            .field("nodes", &self.node_caches.len())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Mapping from `SharedStr`s to `IStr`s of the current thread
+///
+/// Each unique entry is interned only once, when it's resolved for the first time,
+/// and the later lookups of it, including the replicas of the node caches, are by address.
+/// So the entries the caller never resolves don't cost anything.
+/// It takes the strings of any pool, as they carry their contents.
+///
+/// ```
+/// # use internship::shared::{SharedPool, Localizer};
+/// let pool = SharedPool::new();
+/// let localizer = Localizer::new();
+///
+/// let first = localizer.get(&pool.intern("a string interned by the workers"));
+/// let second = localizer.get(&pool.intern("a string interned by the workers"));
+/// assert!(first.is(&second));
+/// assert_eq!(localizer.len(), 1);
+/// ```
+#[derive(Default)]
+pub struct Localizer {
+    // Keeps the `Arc` so its address is not reused while the map is alive.
+    pub(crate) map: RefCell<HashMap<usize, (IStr, Arc<str>)>>,
+}
+
+impl Localizer {
+    pub fn new() -> Self {
+        Localizer { map: RefCell::new(HashMap::new()) }
+    }
+
+    /// Resolve the shared string into `IStr`. Strings from other pools are interned as usual.
+    #[inline]
+    pub fn get(&self, src: &SharedStr) -> IStr {
+        if let Some((istr, _)) = self.map.borrow().get(&src.addr()) {
+            return istr.clone();
+        }
+
+        let istr = IStr::new(src);
+        self.map.borrow_mut().insert(src.addr(), (istr.clone(), Arc::clone(&src.0)));
+        istr
+    }
+
+    /// Number of the unique strings resolved so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.borrow().len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.borrow().is_empty()
+    }
+}
+
+impl fmt::Debug for Localizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.map.borrow().values().map(|entry| &entry.0)).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_cache() {
+        let pool = Arc::new(SharedPool::with_topology(Topology::new(vec![0, 0, 1, 1]), 4));
+
+        let handles: Vec<_> = (0..2).map(|node| {
+            let pool = Arc::clone(&pool);
+
+            thread::spawn(move || {
+                pin_thread_to_node(Some(node));
+                (pool.intern("foo"), pool.intern("foo"))
+            })
+        }).collect();
+
+        let results: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+        assert!(results[0].0.ptr_eq(&results[0].1));
+        assert!(results[1].0.ptr_eq(&results[1].1));
+        assert!(!results[0].0.ptr_eq(&results[1].0));
+        assert_eq!(results[0].0, results[1].0);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.topology().unwrap().nodes(), 2);
+
+        let localizer = Localizer::new();
+        assert_eq!(localizer.get(&results[0].0), "foo");
+        assert_eq!(localizer.get(&results[0].0).pool_id(), localizer.get(&results[1].0).pool_id());
+        assert!(localizer.map.borrow().contains_key(&results[1].0.addr()));
+    }
+
+    #[test]
+    fn test_node_cache_allocations() {
+        let pool = Arc::new(SharedPool::with_topology(Topology::new(vec![0, 1]), 2));
+
+        for node in 0..2 {
+            let pool = Arc::clone(&pool);
+
+            thread::spawn(move || {
+                pin_thread_to_node(Some(node));
+                pool.intern("replicated");
+                pool.intern("replicated");
+            }).join().unwrap();
+        }
+
+        let allocations: HashSet<_> = pool.shards.iter()
+            .chain(pool.node_caches.iter().flat_map(|cache| cache.iter()))
+            .flat_map(|shard| shard.lock().unwrap().iter().map(|rc| rc.as_ptr().addr()).collect::<Vec<_>>())
+            .collect();
+
+        // The entry of the pool and a replica per node.
+        assert_eq!(allocations.len(), 3);
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_localize_lazily() {
+        let pool = SharedPool::with_shards(4);
+        let used = pool.intern("a string resolved after the parallel work");
+        pool.intern("a string only used by the workers");
+
+        let localizer = Localizer::new();
+        assert!(localizer.is_empty());
+        assert!(!::pool::entries().iter().any(|entry| entry == &b"a string only used by the workers"[..]));
+
+        let istr = localizer.get(&used);
+        assert!(istr.is(&localizer.get(&pool.intern("a string resolved after the parallel work"))));
+        assert_eq!(localizer.len(), 1);
+    }
+
+    #[test]
+    fn test_detect() {
+        let root = std::env::temp_dir().join(format!("internship-topology-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        for &(node, cpulist) in &[("node0", "0-1,4\n"), ("node1", "2-3\n")] {
+            fs::create_dir_all(root.join(node)).unwrap();
+            fs::write(root.join(node).join("cpulist"), cpulist).unwrap();
+        }
+        fs::write(root.join("possible"), "0-1\n").unwrap();
+
+        let topology = Topology::detect_in(&root);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(topology, Some(Topology::new(vec![0, 0, 1, 1, 0])));
+        assert_eq!(Topology::detect_in(&root), None);
+
+        if let Some(topology) = Topology::detect() {
+            assert!(topology.nodes() >= 1);
+        }
+    }
+}
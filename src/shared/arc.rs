@@ -0,0 +1,375 @@
+//! Process-wide pool of atomically refcounted handles
+
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
+use std::hash::{Hash, Hasher, BuildHasher};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::Deref;
+use std::fmt;
+
+use handle;
+use istr::IStr;
+
+const SHARDS: usize = 64;
+
+type Shard = HashSet<Arc<[u8]>>;
+
+struct Pool {
+    shards: Box<[Mutex<Shard>]>,
+    hasher: RandomState,
+}
+
+static POOL: OnceLock<Pool> = OnceLock::new();
+
+impl Pool {
+    fn get() -> &'static Pool {
+        POOL.get_or_init(|| Pool {
+            shards: (0..SHARDS).map(|_| Default::default()).collect(),
+            hasher: RandomState::new(),
+        })
+    }
+
+    fn shard(&self, src: &[u8]) -> MutexGuard<'_, Shard> {
+        let index = self.hasher.hash_one(src) as usize % self.shards.len();
+        self.shards[index].lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Byte array interned in the process-wide pool
+///
+/// Unlike `IBytes`, it's `Send` and `Sync`, and equal contents share one allocation
+/// across every thread. It's removed from the pool when its last handle is dropped.
+/// Cloning is an atomic increment, while interning and the drops lock a shard of the pool.
+pub struct ArcIBytes(Option<Arc<[u8]>>);
+
+/// String interned in the process-wide pool
+///
+/// `Send` and `Sync` counterpart of `IStr`, for sharing interned strings between
+/// the threads of rayon, tokio or crossbeam.
+///
+/// ```
+/// # use internship::shared::ArcIStr;
+/// # use std::thread;
+/// let name = ArcIStr::new("a name shared between the threads");
+///
+/// let other = thread::spawn(|| ArcIStr::new("a name shared between the threads")).join().unwrap();
+/// assert!(ArcIStr::ptr_eq(&name, &other));
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ArcIStr(ArcIBytes);
+
+impl ArcIBytes {
+    pub fn new(src: &[u8]) -> Self {
+        let mut shard = Pool::get().shard(src);
+
+        if let Some(arc) = shard.get(src) {
+            return ArcIBytes(Some(Arc::clone(arc)));
+        }
+
+        let arc: Arc<[u8]> = Arc::from(src);
+        shard.insert(Arc::clone(&arc));
+        ArcIBytes(Some(arc))
+    }
+
+    #[inline]
+    fn arc(&self) -> &Arc<[u8]> {
+        self.0.as_ref().expect("dropped shared handle")
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.arc()
+    }
+
+    /// Whether both are the same entry. It equals to `==`.
+    #[inline]
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(a.arc(), b.arc())
+    }
+
+    /// Number of the entries in the process-wide pool.
+    pub fn pool_len() -> usize {
+        Pool::get().shards.iter()
+            .map(|shard| shard.lock().unwrap_or_else(|err| err.into_inner()).len())
+            .sum()
+    }
+}
+
+impl Drop for ArcIBytes {
+    fn drop(&mut self) {
+        let arc = match self.0.take() {
+            Some(arc) => arc,
+            None => return,
+        };
+
+        // Other handles are only created under the shard's lock or cloned by their holders,
+        // so the count of 2 under the lock means this is the last one.
+        let mut shard = Pool::get().shard(&arc);
+        if Arc::strong_count(&arc) == 2 {
+            shard.remove(&*arc);
+        }
+        drop(arc);
+    }
+}
+
+impl Clone for ArcIBytes {
+    #[inline]
+    fn clone(&self) -> Self {
+        ArcIBytes(Some(Arc::clone(self.arc())))
+    }
+}
+
+impl ArcIStr {
+    pub fn new(src: &str) -> Self {
+        ArcIStr(ArcIBytes::new(src.as_bytes()))
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        // Contents always came from `str`.
+        handle::to_str(self.0.as_bytes())
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    #[inline]
+    pub fn to_arc_ibytes(&self) -> ArcIBytes {
+        self.0.clone()
+    }
+
+    /// Intern the string in the current thread's pool.
+    #[inline]
+    pub fn to_istr(&self) -> IStr {
+        IStr::new(self.as_str())
+    }
+
+    /// Whether both are the same entry. It equals to `==`.
+    #[inline]
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        ArcIBytes::ptr_eq(&a.0, &b.0)
+    }
+
+    /// Number of the entries in the process-wide pool.
+    #[inline]
+    pub fn pool_len() -> usize {
+        ArcIBytes::pool_len()
+    }
+}
+
+impl PartialEq for ArcIBytes {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        ArcIBytes::ptr_eq(self, other)
+    }
+}
+
+impl Eq for ArcIBytes {}
+
+impl PartialOrd for ArcIBytes {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ArcIBytes {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl Hash for ArcIBytes {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.as_bytes().hash(hasher)
+    }
+}
+
+impl Deref for ArcIBytes {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Deref for ArcIStr {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<[u8]> for ArcIBytes {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Borrow<str> for ArcIStr {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<[u8]> for ArcIBytes {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<str> for ArcIStr {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<'a> From<&'a [u8]> for ArcIBytes {
+    fn from(v: &'a [u8]) -> Self {
+        ArcIBytes::new(v)
+    }
+}
+
+impl<'a> From<&'a str> for ArcIStr {
+    fn from(v: &'a str) -> Self {
+        ArcIStr::new(v)
+    }
+}
+
+impl From<String> for ArcIStr {
+    fn from(v: String) -> Self {
+        ArcIStr::new(&v)
+    }
+}
+
+impl<'a> From<&'a IStr> for ArcIStr {
+    fn from(v: &'a IStr) -> Self {
+        ArcIStr::new(v)
+    }
+}
+
+impl PartialEq<str> for ArcIStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ArcIStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Default for ArcIStr {
+    #[inline]
+    fn default() -> Self {
+        ArcIStr::new("")
+    }
+}
+
+impl fmt::Debug for ArcIBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_bytes(), f)
+    }
+}
+
+impl fmt::Debug for ArcIStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ArcIStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "serde-compat")]
+mod serde_compat {
+    use super::*;
+    use serde_crate::{Serialize, Serializer, Deserialize, Deserializer};
+
+    impl Serialize for ArcIStr {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_str(self.as_str())
+        }
+    }
+
+    impl<'d> Deserialize<'d> for ArcIStr {
+        fn deserialize<D: Deserializer<'d>>(d: D) -> Result<ArcIStr, D::Error> {
+            String::deserialize(d).map(ArcIStr::from)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+    use std::thread;
+
+    #[test]
+    fn test_removed_after_threads() {
+        let src = "an entry dropped concurrently by many threads";
+        let handles: Vec<_> = (0..8).map(|_| thread::spawn(move || {
+            for _ in 0..1000 {
+                let value = ArcIStr::new(src);
+                assert_eq!(value.clone(), src);
+            }
+        })).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(!Pool::get().shard(src.as_bytes()).contains(src.as_bytes()));
+    }
+
+    #[test]
+    fn test_last_drop_races_intern() {
+        let src = "an entry whose last clone drops while another thread interns it";
+        let barrier = Arc::new(Barrier::new(2));
+
+        let dropper = {
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    let last = ArcIStr::new(src);
+                    barrier.wait();
+                    drop(last);
+                    barrier.wait();
+                }
+            })
+        };
+
+        for _ in 0..1000 {
+            barrier.wait();
+            let value = ArcIStr::new(src);
+
+            // Whichever takes the lock first, the interned handle is the pool's entry.
+            let shard = Pool::get().shard(src.as_bytes());
+            assert!(shard.get(src.as_bytes()).is_some_and(|arc| Arc::ptr_eq(arc, (value.0).arc())));
+            drop(shard);
+
+            barrier.wait();
+            drop(value);
+        }
+
+        dropper.join().unwrap();
+        assert!(!Pool::get().shard(src.as_bytes()).contains(src.as_bytes()));
+    }
+}
@@ -0,0 +1,363 @@
+//! Shared pool with epoch-based reclamation
+
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
+use std::hash::{Hash, Hasher, BuildHasher};
+use std::borrow::Borrow;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::fmt;
+
+use crossbeam_epoch::{self as epoch, Atomic, Owned, Guard};
+
+use super::SharedStr;
+
+// Entry of a snapshot, borrowed from the `Arc<str>` owned by the shard.
+#[derive(Clone, Copy)]
+struct Entry(*const str);
+
+// The pointee is an immutable `str` kept alive while any snapshot containing it is reachable.
+unsafe impl Send for Entry {}
+unsafe impl Sync for Entry {}
+
+impl Entry {
+    #[inline]
+    fn get(&self) -> &str {
+        unsafe { &*self.0 }
+    }
+
+    /// Clone the `Arc` this entry is borrowed from.
+    #[inline]
+    fn to_arc(self) -> Arc<str> {
+        unsafe {
+            Arc::increment_strong_count(self.0);
+            Arc::from_raw(self.0)
+        }
+    }
+}
+
+impl PartialEq for Entry {
+    #[inline]
+    fn eq(&self, other: &Entry) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl Eq for Entry {}
+
+impl Hash for Entry {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.get().hash(hasher)
+    }
+}
+
+impl Borrow<str> for Entry {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.get()
+    }
+}
+
+type Snapshot = HashSet<Entry>;
+
+#[derive(Default)]
+struct State {
+    // Every entry of the shard, owning them.
+    live: HashSet<Arc<str>>,
+    // Removed entries which the current snapshot still refers to.
+    removed: Vec<Arc<str>>,
+    // Number of changes of `live` not yet published.
+    stale: usize,
+}
+
+struct Shard {
+    // Immutable snapshot, read without locking.
+    snapshot: Atomic<Snapshot>,
+    state: Mutex<State>,
+    // Whether the snapshot contains removed entries, so a hit must be checked with `state`.
+    has_removed: AtomicBool,
+}
+
+impl Shard {
+    fn new() -> Self {
+        Shard {
+            snapshot: Atomic::new(Snapshot::new()),
+            state: Mutex::new(State::default()),
+            has_removed: AtomicBool::new(false),
+        }
+    }
+
+    #[inline]
+    fn snapshot<'g>(&self, guard: &'g Guard) -> &'g Snapshot {
+        // Snapshots are only replaced by `publish`, which defers their destruction.
+        unsafe { self.snapshot.load(Ordering::Acquire, guard).deref() }
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Publish the changes if enough of them are pending.
+    fn maybe_publish(&self, state: &mut State, guard: &Guard) {
+        if state.stale > self.snapshot(guard).len() / 4 + 64 {
+            self.publish(state, guard);
+        }
+    }
+
+    /// Replace the snapshot with the live entries, releasing the removed ones
+    /// after every reader leaves the old snapshot. Must be called with the `state` locked.
+    fn publish(&self, state: &mut State, guard: &Guard) {
+        let snapshot: Snapshot = state.live.iter().map(|rc| Entry(Arc::as_ptr(rc))).collect();
+        let old = self.snapshot.swap(Owned::new(snapshot), Ordering::AcqRel, guard);
+        self.has_removed.store(false, Ordering::Release);
+
+        let removed = std::mem::take(&mut state.removed);
+        state.stale = 0;
+
+        unsafe {
+            guard.defer_destroy(old);
+        }
+        if !removed.is_empty() {
+            guard.defer(move || drop(removed));
+        }
+    }
+
+    fn remove(&self, state: &mut State, src: &str) -> bool {
+        match state.live.take(src) {
+            Some(rc) => {
+                state.removed.push(rc);
+                state.stale += 1;
+                self.has_removed.store(true, Ordering::Release);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Drop for Shard {
+    fn drop(&mut self) {
+        unsafe {
+            drop(self.snapshot.load(Ordering::Relaxed, epoch::unprotected()).into_owned());
+        }
+    }
+}
+
+/// Interning pool shared between threads, with lock-free lookup
+///
+/// Each shard keeps an immutable snapshot of its entries which is read without any lock,
+/// and the locked set of the entries owning them. Once enough insertions and removals
+/// are pending, they're published together as a new snapshot, and the old one is reclaimed
+/// by `crossbeam-epoch` after every reader has left it. So removals and merges never block readers,
+/// and interning an existing string never takes a lock once it's published,
+/// unless removals of the shard are pending.
+///
+/// Compared to `SharedPool`, lookups of existing strings don't contend on the shard locks,
+/// but interning new strings costs the merges. Which one is faster depends on the workload
+/// and the number of cores, so measure both with the `compare_shared` example.
+pub struct EpochPool {
+    shards: Box<[Shard]>,
+    hasher: RandomState,
+}
+
+impl EpochPool {
+    /// Create a pool with the number of shards based on available parallelism.
+    pub fn new() -> Self {
+        let shards = thread::available_parallelism()
+            .map(|n| n.get() * 4)
+            .unwrap_or(16);
+
+        EpochPool::with_shards(shards)
+    }
+
+    pub fn with_shards(shards: usize) -> Self {
+        EpochPool {
+            shards: (0..shards.max(1)).map(|_| Shard::new()).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    #[inline]
+    fn shard(&self, src: &str) -> &Shard {
+        &self.shards[self.hasher.hash_one(src) as usize % self.shards.len()]
+    }
+
+    pub fn intern(&self, src: &str) -> SharedStr {
+        let shard = self.shard(src);
+        let guard = epoch::pin();
+
+        if !shard.has_removed.load(Ordering::Acquire) {
+            if let Some(&entry) = shard.snapshot(&guard).get(src) {
+                return SharedStr(entry.to_arc());
+            }
+        }
+
+        let mut state = shard.lock();
+
+        if let Some(rc) = state.live.get(src) {
+            return SharedStr(Arc::clone(rc));
+        }
+
+        let rc: Arc<str> = Arc::from(src);
+        state.live.insert(Arc::clone(&rc));
+        state.stale += 1;
+        shard.maybe_publish(&mut state, &guard);
+
+        SharedStr(rc)
+    }
+
+    pub fn contains(&self, src: &str) -> bool {
+        let shard = self.shard(src);
+
+        if !shard.has_removed.load(Ordering::Acquire) && shard.snapshot(&epoch::pin()).contains(src) {
+            return true;
+        }
+
+        shard.lock().live.contains(src)
+    }
+
+    /// Remove the entry from the pool. Existing `SharedStr`s of it remain valid.
+    ///
+    /// Removals are published with later changes of the shard in a single snapshot.
+    pub fn remove(&self, src: &str) -> bool {
+        let shard = self.shard(src);
+        let guard = epoch::pin();
+        let mut state = shard.lock();
+
+        let removed = shard.remove(&mut state, src);
+        shard.maybe_publish(&mut state, &guard);
+        removed
+    }
+
+    /// Remove every entry not referenced by any `SharedStr`. Returns the number of removed entries.
+    ///
+    /// The removals of each shard are published as a single snapshot. An entry being interned
+    /// concurrently may be removed, then interning it again creates a new entry
+    /// which is equal but not `ptr_eq` to it.
+    pub fn purge_unused(&self) -> usize {
+        let guard = epoch::pin();
+        let mut count = 0;
+
+        for shard in self.shards.iter() {
+            let mut state = shard.lock();
+            // The shard holds one count, and the `SharedStr`s hold the rest.
+            let unused: Vec<Arc<str>> = state.live.iter()
+                .filter(|rc| Arc::strong_count(rc) == 1)
+                .cloned()
+                .collect();
+
+            if unused.is_empty() {
+                continue;
+            }
+
+            for rc in &unused {
+                shard.remove(&mut state, rc);
+            }
+            count += unused.len();
+            shard.publish(&mut state, &guard);
+        }
+
+        count
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter()
+            .map(|shard| shard.lock().live.len())
+            .sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every entry from the pool. Existing `SharedStr`s remain valid.
+    pub fn clear(&self) {
+        let guard = epoch::pin();
+
+        for shard in self.shards.iter() {
+            let mut state = shard.lock();
+            let live = std::mem::take(&mut state.live);
+            state.removed.extend(live);
+            shard.publish(&mut state, &guard);
+        }
+    }
+}
+
+impl Default for EpochPool {
+    fn default() -> Self {
+        EpochPool::new()
+    }
+}
+
+impl fmt::Debug for EpochPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EpochPool")
+            .field("shards", &self.shards.len())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_intern() {
+        let pool = Arc::new(EpochPool::with_shards(2));
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let pool = Arc::clone(&pool);
+
+            thread::spawn(move || {
+                (0..1000).map(|n| pool.intern(&n.to_string())).collect::<Vec<_>>()
+            })
+        }).collect();
+
+        let results: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+
+        for (a, b) in results[0].iter().zip(results[3].iter()) {
+            assert!(a.ptr_eq(b));
+        }
+        assert_eq!(pool.len(), 1000);
+
+        drop(results);
+        assert!(pool.remove("42"));
+        assert!(!pool.contains("42"));
+        assert!(!pool.remove("42"));
+
+        let kept = pool.intern("7");
+        assert_eq!(pool.purge_unused(), 998);
+        assert_eq!(pool.len(), 1);
+        assert!(pool.intern("7").ptr_eq(&kept));
+
+        drop(kept);
+        assert_eq!(pool.purge_unused(), 1);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_removed_readable_from_snapshot() {
+        let src = "a string removed while a reader holds the old snapshot";
+        let pool = EpochPool::with_shards(1);
+        let weak = Arc::downgrade(&pool.intern(src).0);
+
+        let shard = pool.shard(src);
+        shard.publish(&mut shard.lock(), &epoch::pin());
+
+        let guard = epoch::pin();
+        let entry = *shard.snapshot(&guard).get(src).unwrap();
+        pool.clear();
+
+        assert!(!pool.contains(src));
+        assert!(!shard.snapshot(&epoch::pin()).contains(src));
+        // Released only after the reader leaves the old snapshot.
+        assert_eq!(entry.get(), src);
+        assert!(weak.upgrade().is_some());
+        drop(guard);
+    }
+}
@@ -0,0 +1,193 @@
+//! Side tables
+//!
+//! Typed user data attached to the pool entries. Data of an entry is dropped
+//! when the entry is removed from the pool, i.e. when its last handle is dropped.
+//! Inlined strings have no pool entry, so their data stays until removed explicitly.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::fmt;
+
+use handle::Handle;
+use istr::IStr;
+
+type Id = (usize, usize);
+
+trait Table {
+    fn remove(&mut self, id: Id) -> Option<Rc<dyn Any>>;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<V: 'static> Table for HashMap<Id, Rc<V>> {
+    fn remove(&mut self, id: Id) -> Option<Rc<dyn Any>> {
+        HashMap::remove(self, &id).map(|v| v as Rc<dyn Any>)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+thread_local! {
+    static TABLES: RefCell<HashMap<TypeId, Box<dyn Table>>> = Default::default();
+    static ACTIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+fn with_table<V: 'static, R, F: FnOnce(&mut HashMap<Id, Rc<V>>) -> R>(f: F) -> R {
+    ACTIVE.with(|active| active.set(true));
+
+    TABLES.with(|tables| {
+        let mut tables = tables.borrow_mut();
+        let table = tables.entry(TypeId::of::<V>())
+            .or_insert_with(|| Box::new(HashMap::<Id, Rc<V>>::new()));
+
+        f(table.as_any_mut().downcast_mut().expect("side table of wrong type"))
+    })
+}
+
+/// Called when the pool entry is removed.
+#[inline]
+pub(crate) fn on_remove(id: Id) {
+    if !ACTIVE.try_with(Cell::get).unwrap_or(false) {
+        return;
+    }
+
+    let removed: Vec<_> = TABLES.try_with(|tables| {
+        tables.borrow_mut().values_mut().filter_map(|table| table.remove(id)).collect()
+    }).unwrap_or_default();
+
+    // Dropped after the borrow is released, as it may drop other handles.
+    drop(removed);
+}
+
+/// Side data of type `V` of the handle, inserted by `f` if absent.
+pub(crate) fn get_or_insert_with<V: 'static, F: FnOnce() -> V>(handle: &Handle, f: F) -> Rc<V> {
+    let id = handle.id();
+
+    if let Some(value) = with_table(|table: &mut HashMap<Id, Rc<V>>| table.get(&id).cloned()) {
+        return value;
+    }
+
+    // `f` may access the side tables too.
+    let value = Rc::new(f());
+    with_table(|table| Rc::clone(table.entry(id).or_insert(value)))
+}
+
+/// Slot of the side data of type `V` for an interned string
+pub struct SideEntry<V> {
+    key: IStr,
+    _marker: PhantomData<Rc<V>>,
+}
+
+pub(crate) fn entry<V: 'static>(key: &IStr) -> SideEntry<V> {
+    SideEntry {
+        key: key.clone(),
+        _marker: PhantomData,
+    }
+}
+
+impl<V: 'static> SideEntry<V> {
+    #[inline]
+    fn id(&self) -> Id {
+        self.key.0.id()
+    }
+
+    pub fn get(&self) -> Option<Rc<V>> {
+        let id = self.id();
+        with_table(|table: &mut HashMap<Id, Rc<V>>| table.get(&id).cloned())
+    }
+
+    /// Set the data, returning the previous one.
+    pub fn insert(self, value: V) -> Option<Rc<V>> {
+        let id = self.id();
+        with_table(|table| table.insert(id, Rc::new(value)))
+    }
+
+    pub fn remove(self) -> Option<Rc<V>> {
+        let id = self.id();
+        with_table(|table: &mut HashMap<Id, Rc<V>>| table.remove(&id))
+    }
+
+    #[inline]
+    pub fn or_insert(self, value: V) -> Rc<V> {
+        self.or_insert_with(|| value)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> Rc<V> {
+        get_or_insert_with(&self.key.0, f)
+    }
+
+    #[inline]
+    pub fn key(&self) -> &IStr {
+        &self.key
+    }
+}
+
+impl<V: 'static + Default> SideEntry<V> {
+    #[inline]
+    pub fn or_default(self) -> Rc<V> {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<V> fmt::Debug for SideEntry<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("SideEntry").field(&self.key).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_side_data() {
+        let key = IStr::new("a string long enough to be pooled");
+
+        let count = entry::<Cell<u32>>(&key).or_default();
+        count.set(count.get() + 1);
+        entry::<Cell<u32>>(&key).or_default().set(count.get() + 1);
+        assert_eq!(count.get(), 2);
+
+        entry(&key).or_insert("other type");
+        assert_eq!(*entry::<&str>(&key).get().unwrap(), "other type");
+
+        let weak = Rc::downgrade(&count);
+        drop(count);
+        drop(key);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_removal_drops_data() {
+        // Sets the flag when dropped.
+        struct Flag(Rc<Cell<bool>>);
+
+        impl Drop for Flag {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let src = "a string whose side data is dropped with its entry";
+        let dropped = Rc::new(Cell::new(false));
+        let key = IStr::new(src);
+        entry(&key).insert(Flag(Rc::clone(&dropped)));
+
+        // Kept while any handle or the pin keeps the entry.
+        let other = IStr::new(src);
+        drop(key);
+        assert!(!dropped.get());
+        other.pin();
+        drop(other);
+        assert!(!dropped.get());
+
+        assert_eq!(::pool::purge_unused(), 1);
+        assert!(dropped.get());
+        assert!(entry::<Flag>(&IStr::new(src)).get().is_none());
+    }
+}
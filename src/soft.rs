@@ -0,0 +1,194 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::fmt;
+
+use istr::IStr;
+
+thread_local! {
+    // Soft entries with the serial of their registration.
+    static SOFT: RefCell<HashMap<usize, (IStr, u64)>> = Default::default();
+    static SERIAL: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Soft reference to an interned string
+///
+/// The pool keeps the entry alive for its soft references until `pool::release_soft()`
+/// is called, e.g. under memory pressure, even while soft references exist.
+/// After that it's freed once no `IStr` refers it, and soft references re-intern it
+/// on the next `get_or_intern()`. It's for speculative caching of strings which may never be used again.
+///
+/// ```
+/// # use internship::{IStr, pool};
+/// let src = "a speculatively cached string";
+/// let soft = IStr::new_soft(src);
+/// assert_eq!(soft.get().unwrap(), src);
+///
+/// pool::release_soft();
+/// assert!(soft.get().is_none());
+/// assert_eq!(soft.get_or_intern(src), src);
+/// assert!(soft.get().is_some());
+/// ```
+#[derive(Clone)]
+pub struct SoftIStr {
+    inline: Option<IStr>,
+    addr: Cell<usize>,
+    serial: Cell<u64>,
+    // Hash of the contents, to catch re-interning from another string.
+    hash: u64,
+}
+
+fn hash_of(src: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    src.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn register(value: &IStr) -> (usize, u64) {
+    let addr = value.pool_id().expect("inlined string is never soft");
+
+    let serial = SOFT.with(|soft| {
+        soft.borrow_mut().entry(addr).or_insert_with(|| {
+            let serial = SERIAL.with(|serial| {
+                serial.set(serial.get() + 1);
+                serial.get()
+            });
+            (value.clone(), serial)
+        }).1
+    });
+
+    (addr, serial)
+}
+
+/// Release the pool's references of every soft entry, returning the number of them.
+pub(crate) fn release() -> usize {
+    let released = SOFT.with(|soft| soft.replace(HashMap::new()));
+    let len = released.len();
+
+    // Dropped after the borrow is released, as it removes the pool entries.
+    drop(released);
+    len
+}
+
+/// Whether the pool entry of the address has soft references kept by the pool.
+pub(crate) fn contains(addr: usize) -> bool {
+    SOFT.with(|soft| soft.borrow().contains_key(&addr))
+}
+
+/// Release the pool's reference of the soft entry. Returns whether it was soft.
+pub(crate) fn release_entry(addr: usize) -> bool {
+    let released = SOFT.with(|soft| soft.borrow_mut().remove(&addr));
+
+    // Dropped after the borrow is released, as it removes the pool entry.
+    released.is_some()
+}
+
+impl IStr {
+    /// Intern the string softly. See `SoftIStr`.
+    pub fn new_soft(src: &str) -> SoftIStr {
+        let value = IStr::new(src);
+
+        if value.pool_id().is_none() {
+            return SoftIStr {
+                inline: Some(value),
+                addr: Cell::new(0),
+                serial: Cell::new(0),
+                hash: hash_of(src),
+            };
+        }
+
+        let (addr, serial) = register(&value);
+        SoftIStr {
+            inline: None,
+            addr: Cell::new(addr),
+            serial: Cell::new(serial),
+            hash: hash_of(src),
+        }
+    }
+}
+
+impl SoftIStr {
+    /// The string, or `None` if the pool released it.
+    pub fn get(&self) -> Option<IStr> {
+        if let Some(ref inline) = self.inline {
+            return Some(inline.clone());
+        }
+
+        SOFT.with(|soft| match soft.borrow().get(&self.addr.get()) {
+            Some(&(ref value, serial)) if serial == self.serial.get() => Some(value.clone()),
+            _ => None,
+        })
+    }
+
+    /// The string, re-interning it from `src` if the pool released it.
+    ///
+    /// `src` must be the string this soft reference was created from.
+    /// Otherwise it panics in debug builds, and in release builds it returns
+    /// the interned `src` without registering it to this soft reference.
+    pub fn get_or_intern(&self, src: &str) -> IStr {
+        if hash_of(src) != self.hash {
+            debug_assert!(false, "SoftIStr re-interned from a different string {:?}", src);
+            return IStr::new(src);
+        }
+
+        if let Some(value) = self.get().filter(|value| value == src) {
+            return value;
+        }
+
+        let value = IStr::new(src);
+        if value.pool_id().is_some() {
+            let (addr, serial) = register(&value);
+            self.addr.set(addr);
+            self.serial.set(serial);
+        }
+        value
+    }
+
+    #[inline]
+    pub fn is_released(&self) -> bool {
+        self.get().is_none()
+    }
+}
+
+impl fmt::Debug for SoftIStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(value) => f.debug_tuple("SoftIStr").field(&value).finish(),
+            None => f.write_str("SoftIStr(<released>)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_frees_entry() {
+        let src = "a soft string long enough to be pooled";
+        let soft = IStr::new_soft(src);
+        let strong = soft.get().unwrap();
+
+        assert_eq!(release(), 1);
+        assert!(soft.is_released());
+        assert_eq!(IStr::new(src).pool_id(), strong.pool_id());
+
+        drop(strong);
+        assert_eq!(soft.get_or_intern(src), src);
+        assert!(!soft.is_released());
+        assert!(IStr::new_soft("inline").get().is_some());
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "different string"))]
+    fn test_mismatched_source() {
+        let src = "a soft string to be re-interned from";
+        let soft = IStr::new_soft(src);
+        release();
+
+        let other = soft.get_or_intern("another string than the soft one");
+        assert_eq!(other, "another string than the soft one");
+        assert!(soft.is_released());
+    }
+}
@@ -0,0 +1,243 @@
+//! Template
+//!
+//! Minimal string interpolation over interned strings.
+//! Templates are compiled once into interned literal segments and placeholder names,
+//! and rendered outputs are interned so identical messages share one entry.
+//!
+//! ```
+//! # use std::collections::HashMap;
+//! # use internship::IStr;
+//! # use internship::template::Template;
+//! let tmpl = Template::compile("Hello, {name}! You have {count} new {{messages}}.").unwrap();
+//!
+//! let mut values = HashMap::new();
+//! values.insert(IStr::new("name"), IStr::new("Alice"));
+//! values.insert(IStr::new("count"), IStr::new("3"));
+//!
+//! let msg = tmpl.render_cached(&values).unwrap();
+//! assert_eq!(msg, "Hello, Alice! You have 3 new {messages}.");
+//! ```
+
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::hash::{Hash, Hasher};
+use std::error::Error;
+use std::fmt;
+
+use istr::IStr;
+use map::InternMapExt;
+use decode::intern_decoded;
+
+/// Number of rendered outputs `Template::render_cached` keeps before clearing its cache.
+pub const CACHE_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(IStr),
+    Placeholder(IStr),
+}
+
+/// Compiled template with `{name}` placeholders. `{{` and `}}` are literal braces.
+pub struct Template {
+    segments: Box<[Segment]>,
+    cache: RefCell<HashMap<IdKey, IStr>>,
+}
+
+/// Values compared by their handles, which is valid as the key keeps them alive.
+#[derive(PartialEq, Eq)]
+struct IdKey(Box<[IStr]>);
+
+impl Hash for IdKey {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        for value in self.0.iter() {
+            value.0.id().hash(hasher);
+        }
+    }
+}
+
+/// Error returned when the template has unbalanced braces or an empty placeholder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTemplate;
+
+/// Error returned when the value of the placeholder is not given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingValue(pub IStr);
+
+impl Template {
+    pub fn compile(src: &str) -> Result<Self, InvalidTemplate> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut rest = src;
+
+        while let Some(idx) = rest.find(['{', '}']) {
+            literal.push_str(&rest[..idx]);
+            let brace = rest.as_bytes()[idx];
+            rest = &rest[idx + 1..];
+
+            if rest.as_bytes().first() == Some(&brace) {
+                literal.push(brace as char);
+                rest = &rest[1..];
+                continue;
+            }
+
+            if brace == b'}' {
+                return Err(InvalidTemplate);
+            }
+
+            let end = rest.find('}').ok_or(InvalidTemplate)?;
+            let name = &rest[..end];
+
+            if name.is_empty() || name.contains('{') {
+                return Err(InvalidTemplate);
+            }
+
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(IStr::new(&literal)));
+                literal.clear();
+            }
+            segments.push(Segment::Placeholder(IStr::new(name)));
+            rest = &rest[end + 1..];
+        }
+
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(IStr::new(&literal)));
+        }
+
+        Ok(Template {
+            segments: segments.into(),
+            cache: Default::default(),
+        })
+    }
+
+    /// Names of the placeholders in order of appearance.
+    pub fn placeholders(&self) -> impl Iterator<Item = &IStr> {
+        self.segments.iter().filter_map(|seg| match *seg {
+            Segment::Placeholder(ref name) => Some(name),
+            Segment::Literal(_) => None,
+        })
+    }
+
+    /// Render the template with values looked up by placeholder names.
+    pub fn render<M, V>(&self, values: &M) -> Result<IStr, MissingValue>
+    where
+        M: InternMapExt<V>,
+        V: AsRef<str>,
+    {
+        intern_decoded(&self.segments[..], |segments, buf| {
+            for seg in segments.iter() {
+                match *seg {
+                    Segment::Literal(ref text) => buf.push_str(text),
+                    Segment::Placeholder(ref name) => match values.get_str(name) {
+                        Some(value) => buf.push_str(value.as_ref()),
+                        None => return Err(MissingValue(name.clone())),
+                    },
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Same as `render`, but remembers the output for each combination of values,
+    /// so rendering with the same values again skips formatting and pool lookup.
+    pub fn render_cached<M>(&self, values: &M) -> Result<IStr, MissingValue>
+    where
+        M: InternMapExt<IStr>,
+    {
+        let key = self.placeholders()
+            .map(|name| match values.get_str(name) {
+                // Keyed by the pooled handle, so it meets the pooled values of the same contents.
+                Some(value) => Ok(value.0.to_pooled().map_or_else(|| value.clone(), IStr)),
+                None => Err(MissingValue(name.clone())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let key = IdKey(key.into());
+
+        if let Some(output) = self.cache.borrow().get(&key) {
+            return Ok(output.clone());
+        }
+
+        let output = self.render(values)?;
+        let mut cache = self.cache.borrow_mut();
+
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(key, output.clone());
+
+        Ok(output)
+    }
+
+    pub fn clear_cache(&self) {
+        self.cache.borrow_mut().clear();
+    }
+}
+
+impl Clone for Template {
+    fn clone(&self) -> Self {
+        Template {
+            segments: self.segments.clone(),
+            cache: Default::default(),
+        }
+    }
+}
+
+impl fmt::Debug for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.segments.iter()).finish()
+    }
+}
+
+impl fmt::Display for InvalidTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid template")
+    }
+}
+
+impl Error for InvalidTemplate {}
+
+impl fmt::Display for MissingValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "missing value for placeholder {:?}", self.0)
+    }
+}
+
+impl Error for MissingValue {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_compile() {
+        for src in &["{", "}", "{}", "a{b", "a}b", "{a{b}"] {
+            assert_eq!(Template::compile(src).err(), Some(InvalidTemplate), "{}", src);
+        }
+
+        let tmpl = Template::compile("{{{a}}}-{b}").unwrap();
+        let names: Vec<_> = tmpl.placeholders().cloned().collect();
+        assert_eq!(names, ["a", "b"]);
+
+        let mut values = BTreeMap::new();
+        values.insert(IStr::new("a"), "1");
+        assert_eq!(tmpl.render(&values), Err(MissingValue(IStr::new("b"))));
+        values.insert(IStr::new("b"), "2");
+        assert_eq!(tmpl.render(&values).unwrap(), "{1}-2");
+    }
+
+    #[test]
+    fn test_cache_mixed_keys() {
+        let tmpl = Template::compile("{a}").unwrap();
+        let src = "a value long enough to be pooled";
+
+        let mut values = BTreeMap::new();
+        values.insert(IStr::new("a"), IStr::new_unpooled(src));
+        assert_eq!(tmpl.render_cached(&values).unwrap(), src);
+
+        values.insert(IStr::new("a"), IStr::new(src));
+        assert_eq!(tmpl.render_cached(&values).unwrap(), src);
+        assert_eq!(tmpl.cache.borrow().len(), 1);
+    }
+}
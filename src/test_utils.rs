@@ -0,0 +1,75 @@
+//! Assertions for testing code built on the interned types
+//!
+//! They check the invariants the crate relies on, so downstream crates
+//! embedding interned values can test them without knowing the internals.
+//! They panic with a descriptive message on failure, like `assert!`.
+
+use std::fmt::Debug;
+
+use istr::IStr;
+use ibytes::IBytes;
+
+/// Assert both are the same interned string, sharing the pool entry.
+///
+/// Unlike `assert_eq!`, it reports the contents and the pool ids on failure,
+/// and catches the values interned on different threads.
+///
+/// ```
+/// # use internship::IStr;
+/// # use internship::test_utils::assert_interned_eq;
+/// let text = String::from("a string long enough to be pooled");
+/// assert_interned_eq(&IStr::new(&text), &IStr::from(text.clone()));
+/// ```
+#[track_caller]
+pub fn assert_interned_eq(left: &IStr, right: &IStr) {
+    assert!(left.0 == right.0,
+        "interned strings are not the same\n  left: {:?} ({:?})\n right: {:?} ({:?})",
+        left.as_str(), left.pool_id(), right.as_str(), right.pool_id());
+    assert_eq!(left.as_str(), right.as_str(), "same handle with different contents");
+}
+
+/// Assert the string is correctly represented and present in this thread's pool.
+#[track_caller]
+pub fn assert_istr_invariants(value: &IStr) {
+    if let Err(msg) = value.0.check() {
+        panic!("invalid IStr {:?}: {}", value.as_str(), msg);
+    }
+    assert!(::std::str::from_utf8(value.as_bytes()).is_ok(), "IStr contains invalid UTF-8");
+    assert_interned_eq(value, &IStr::new(value.as_str()));
+}
+
+/// Assert the bytes are correctly represented and present in this thread's pool.
+#[track_caller]
+pub fn assert_ibytes_invariants(value: &IBytes) {
+    if let Err(msg) = value.0.check() {
+        panic!("invalid IBytes {:?}: {}", value.as_bytes(), msg);
+    }
+    assert!(value.0 == IBytes::new(value.as_bytes()).0, "IBytes is not interned");
+}
+
+/// Assert the value survives the round trip through the encoder and decoder,
+/// e.g. `serde_json::to_string` and `serde_json::from_str`.
+///
+/// ```
+/// # extern crate serde_json;
+/// # extern crate internship;
+/// # use internship::IStr;
+/// # use internship::test_utils::assert_round_trip;
+/// # fn main() {
+/// let value = vec![IStr::new("a"), IStr::new("a string long enough to be pooled")];
+/// assert_round_trip(&value, |v| serde_json::to_string(v), |s| serde_json::from_str(s));
+/// # }
+/// ```
+#[track_caller]
+pub fn assert_round_trip<T, R, E1, E2, F, G>(value: &T, encode: F, decode: G)
+where
+    T: PartialEq + Debug,
+    E1: Debug,
+    E2: Debug,
+    F: FnOnce(&T) -> Result<R, E1>,
+    G: FnOnce(&R) -> Result<T, E2>,
+{
+    let encoded = encode(value).unwrap_or_else(|err| panic!("failed to encode {:?}: {:?}", value, err));
+    let decoded = decode(&encoded).unwrap_or_else(|err| panic!("failed to decode {:?}: {:?}", value, err));
+    assert_eq!(&decoded, value, "value changed by the round trip");
+}
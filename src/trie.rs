@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use istr::IStr;
+
+/// Trie keyed by sequences of interned segments
+///
+/// Paths, module paths and topic names split into segments share their common prefixes.
+/// `longest_prefix` finds the value of the deepest stored prefix, like a router does.
+///
+/// ```
+/// # use internship::InternTrie;
+/// let mut routes = InternTrie::new();
+/// routes.insert(["api"], "api root");
+/// routes.insert(["api", "users"], "users");
+///
+/// assert_eq!(routes.get(["api", "users"]), Some(&"users"));
+/// assert_eq!(routes.longest_prefix(["api", "users", "42"]), Some((2, &"users")));
+/// assert_eq!(routes.longest_prefix(["api", "posts"]), Some((1, &"api root")));
+/// assert_eq!(routes.longest_prefix(["static"]), None);
+/// ```
+#[derive(Clone)]
+pub struct InternTrie<V> {
+    root: Node<V>,
+    len: usize,
+}
+
+#[derive(Clone)]
+struct Node<V> {
+    value: Option<V>,
+    children: HashMap<IStr, Node<V>>,
+}
+
+impl<V> Default for Node<V> {
+    fn default() -> Self {
+        Node {
+            value: None,
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl<V> InternTrie<V> {
+    pub fn new() -> Self {
+        InternTrie {
+            root: Node::default(),
+            len: 0,
+        }
+    }
+
+    /// Insert the value at the path, returning the previous one if any.
+    pub fn insert<I>(&mut self, path: I, value: V) -> Option<V>
+    where
+        I: IntoIterator,
+        I::Item: Into<IStr>,
+    {
+        let mut node = &mut self.root;
+
+        for segment in path {
+            node = node.children.entry(segment.into()).or_default();
+        }
+
+        let prev = node.value.replace(value);
+        if prev.is_none() {
+            self.len += 1;
+        }
+        prev
+    }
+
+    fn node<I>(&self, path: I) -> Option<&Node<V>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        path.into_iter().try_fold(&self.root, |node, segment| node.children.get(segment.as_ref()))
+    }
+
+    pub fn get<I>(&self, path: I) -> Option<&V>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.node(path)?.value.as_ref()
+    }
+
+    pub fn get_mut<I>(&mut self, path: I) -> Option<&mut V>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        path.into_iter()
+            .try_fold(&mut self.root, |node, segment| node.children.get_mut(segment.as_ref()))?
+            .value.as_mut()
+    }
+
+    #[inline]
+    pub fn contains<I>(&self, path: I) -> bool
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.get(path).is_some()
+    }
+
+    /// Remove the value at the path. Nodes left without values are pruned.
+    pub fn remove<I>(&mut self, path: I) -> Option<V>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        fn remove<V, S: AsRef<str>>(node: &mut Node<V>, mut path: impl Iterator<Item = S>) -> Option<V> {
+            let segment = match path.next() {
+                Some(segment) => segment,
+                None => return node.value.take(),
+            };
+
+            let child = node.children.get_mut(segment.as_ref())?;
+            let removed = remove(child, path);
+
+            if child.value.is_none() && child.children.is_empty() {
+                node.children.remove(segment.as_ref());
+            }
+
+            removed
+        }
+
+        let removed = remove(&mut self.root, path.into_iter());
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// Value of the longest prefix of the path which has one,
+    /// with the number of segments of the prefix.
+    pub fn longest_prefix<I>(&self, path: I) -> Option<(usize, &V)>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut node = &self.root;
+        let mut found = node.value.as_ref().map(|value| (0, value));
+
+        for (depth, segment) in path.into_iter().enumerate() {
+            node = match node.children.get(segment.as_ref()) {
+                Some(child) => child,
+                None => break,
+            };
+
+            if let Some(ref value) = node.value {
+                found = Some((depth + 1, value));
+            }
+        }
+
+        found
+    }
+
+    /// Number of the values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.root = Node::default();
+        self.len = 0;
+    }
+}
+
+impl<V> Default for InternTrie<V> {
+    fn default() -> Self {
+        InternTrie::new()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for InternTrie<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn collect<'a, V>(node: &'a Node<V>, path: &mut Vec<&'a IStr>, out: &mut Vec<(Vec<&'a IStr>, &'a V)>) {
+            if let Some(ref value) = node.value {
+                out.push((path.clone(), value));
+            }
+
+            for (segment, child) in &node.children {
+                path.push(segment);
+                collect(child, path, out);
+                path.pop();
+            }
+        }
+
+        let mut entries = Vec::with_capacity(self.len);
+        collect(&self.root, &mut Vec::new(), &mut entries);
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_prunes() {
+        let mut trie = InternTrie::new();
+        trie.insert(vec!["a", "b", "c"], 1);
+        trie.insert(vec!["a"], 2);
+
+        assert_eq!(trie.remove(["a", "b"]), None);
+        assert_eq!(trie.remove(["a", "b", "c"]), Some(1));
+        assert!(trie.root.children[&IStr::new("a")].children.is_empty());
+        assert_eq!(trie.len(), 1);
+
+        assert_eq!(trie.insert(Vec::<IStr>::new(), 0), None);
+        assert_eq!(trie.longest_prefix(["x"]), Some((0, &0)));
+    }
+}
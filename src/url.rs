@@ -0,0 +1,277 @@
+use std::ops::{Deref, Range};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::fmt;
+
+use url_crate::{Url, ParseError};
+
+use istr::IStr;
+
+/// Interned URL
+///
+/// URLs are parsed and normalized by the `url` crate, then their serialization is interned.
+/// Offsets of the components are stored alongside, so the accessors don't reparse the URL.
+///
+/// ```
+/// # use internship::IUrl;
+/// let url = IUrl::parse("HTTPS://Example.com:8080/a/b?q=1#top").unwrap();
+///
+/// assert_eq!(url.as_str(), "https://example.com:8080/a/b?q=1#top");
+/// assert_eq!(url.scheme(), "https");
+/// assert_eq!(url.host_istr().unwrap(), "example.com");
+/// assert_eq!(url.port(), Some(8080));
+/// assert_eq!(url.path_istr(), "/a/b");
+/// assert_eq!(url.query(), Some("q=1"));
+/// ```
+#[derive(Clone)]
+pub struct IUrl {
+    text: IStr,
+    scheme_end: u32,
+    host: Option<(u32, u32)>,
+    port: Option<u16>,
+    path: (u32, u32),
+    query: Option<(u32, u32)>,
+    fragment: Option<u32>,
+}
+
+fn offset(base: &str, part: &str) -> u32 {
+    (part.as_ptr().addr() - base.as_ptr().addr()) as u32
+}
+
+fn span(base: &str, part: &str) -> (u32, u32) {
+    let start = offset(base, part);
+    (start, start + part.len() as u32)
+}
+
+impl IUrl {
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        Url::parse(src).map(|url| IUrl::from(&url))
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.text.as_str()
+    }
+
+    #[inline]
+    pub fn as_istr(&self) -> &IStr {
+        &self.text
+    }
+
+    #[inline]
+    pub fn into_istr(self) -> IStr {
+        self.text
+    }
+
+    #[inline]
+    fn slice(&self, (start, end): (u32, u32)) -> &str {
+        &self.as_str()[start as usize..end as usize]
+    }
+
+    #[inline]
+    fn range(&self, (start, end): (u32, u32)) -> Range<usize> {
+        start as usize..end as usize
+    }
+
+    #[inline]
+    pub fn scheme(&self) -> &str {
+        &self.as_str()[..self.scheme_end as usize]
+    }
+
+    #[inline]
+    pub fn host_str(&self) -> Option<&str> {
+        self.host.map(|host| self.slice(host))
+    }
+
+    pub fn host_istr(&self) -> Option<IStr> {
+        self.host_str().map(IStr::new)
+    }
+
+    #[inline]
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    #[inline]
+    pub fn path(&self) -> &str {
+        self.slice(self.path)
+    }
+
+    pub fn path_istr(&self) -> IStr {
+        IStr::new(self.path())
+    }
+
+    #[inline]
+    pub fn path_range(&self) -> Range<usize> {
+        self.range(self.path)
+    }
+
+    #[inline]
+    pub fn query(&self) -> Option<&str> {
+        self.query.map(|query| self.slice(query))
+    }
+
+    #[inline]
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.map(|start| &self.as_str()[start as usize..])
+    }
+
+    /// Parse again into `url::Url`.
+    pub fn to_url(&self) -> Url {
+        Url::parse(self.as_str()).expect("IUrl always holds valid URL")
+    }
+}
+
+impl From<&Url> for IUrl {
+    fn from(url: &Url) -> Self {
+        let text = url.as_str();
+
+        IUrl {
+            text: IStr::new(text),
+            scheme_end: url.scheme().len() as u32,
+            host: url.host_str().map(|host| span(text, host)),
+            port: url.port(),
+            path: span(text, url.path()),
+            query: url.query().map(|query| span(text, query)),
+            fragment: url.fragment().map(|fragment| offset(text, fragment)),
+        }
+    }
+}
+
+impl From<Url> for IUrl {
+    fn from(url: Url) -> Self {
+        IUrl::from(&url)
+    }
+}
+
+impl From<&IUrl> for Url {
+    fn from(url: &IUrl) -> Self {
+        url.to_url()
+    }
+}
+
+impl FromStr for IUrl {
+    type Err = ParseError;
+
+    fn from_str(src: &str) -> Result<Self, ParseError> {
+        IUrl::parse(src)
+    }
+}
+
+impl PartialEq for IUrl {
+    #[inline]
+    fn eq(&self, other: &IUrl) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for IUrl {}
+
+impl PartialOrd for IUrl {
+    #[inline]
+    fn partial_cmp(&self, other: &IUrl) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IUrl {
+    #[inline]
+    fn cmp(&self, other: &IUrl) -> Ordering {
+        self.text.cmp(&other.text)
+    }
+}
+
+impl Hash for IUrl {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.text.hash(hasher)
+    }
+}
+
+impl Deref for IUrl {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for IUrl {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<IUrl> for IStr {
+    #[inline]
+    fn from(v: IUrl) -> IStr {
+        v.text
+    }
+}
+
+impl fmt::Debug for IUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for IUrl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every span must match the accessors of `url::Url`.
+    fn check(src: &str) -> IUrl {
+        let url = Url::parse(src).unwrap();
+        let iurl = IUrl::from(&url);
+
+        assert_eq!(iurl.as_str(), url.as_str(), "{}", src);
+        assert_eq!(iurl.scheme(), url.scheme(), "{}", src);
+        assert_eq!(iurl.host_str(), url.host_str(), "{}", src);
+        assert_eq!(iurl.port(), url.port(), "{}", src);
+        assert_eq!(iurl.path(), url.path(), "{}", src);
+        assert_eq!(iurl.query(), url.query(), "{}", src);
+        assert_eq!(iurl.fragment(), url.fragment(), "{}", src);
+        assert_eq!(iurl.to_url(), url, "{}", src);
+        iurl
+    }
+
+    #[test]
+    fn test_without_host() {
+        let mail = check("mailto:someone@example.com?subject=hi");
+        assert_eq!(mail.host_str(), None);
+        assert_eq!(mail.path(), "someone@example.com");
+
+        let data = check("data:text/plain,hello#frag");
+        assert_eq!(data.host_str(), None);
+        assert_eq!(data.fragment(), Some("frag"));
+
+        check("file:///tmp/a%20b");
+    }
+
+    #[test]
+    fn test_without_query() {
+        let url = check("https://example.com/a/b#top");
+        assert_eq!(url.query(), None);
+        assert_eq!(url.path_range(), 19..23);
+
+        assert_eq!(check("https://example.com/?").query(), Some(""));
+    }
+
+    #[test]
+    fn test_without_fragment() {
+        let url = check("https://example.com:8443?q=1");
+        assert_eq!(url.path(), "/");
+        assert_eq!(url.fragment(), None);
+
+        assert_eq!(check("https://example.com/#").fragment(), Some(""));
+        check("https://user:pass@[::1]:8080/");
+    }
+}
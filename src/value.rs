@@ -0,0 +1,272 @@
+//! Dynamically typed value with interned strings
+//!
+//! Config trees repeat their section and key names across many files,
+//! so `IValue` interns every map key and string value while deserializing.
+//! It deserializes from any self-describing format, and the `toml` and `yaml` features
+//! add conversions from the already parsed values of those crates.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_crate::{Serialize, Serializer, Deserialize, Deserializer, de};
+use serde_crate::ser::{SerializeMap, SerializeSeq};
+
+use istr::IStr;
+
+/// Map of `IValue`s with interned keys
+pub type IValueMap = BTreeMap<IStr, IValue>;
+
+/// Dynamically typed value with interned strings
+///
+/// ```
+/// # extern crate serde_json;
+/// # extern crate internship;
+/// # use internship::IValue;
+/// # fn main() {
+/// let value: IValue = serde_json::from_str(r#"{"server": {"port": 80, "hosts": ["a", "b"]}}"#).unwrap();
+///
+/// let server = value.get("server").unwrap();
+/// assert_eq!(server.get("port").and_then(IValue::as_i64), Some(80));
+/// assert_eq!(server.get("hosts").unwrap().as_seq().unwrap().len(), 2);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum IValue {
+    Null,
+    Bool(bool),
+    /// Integers out of the `i64` range are stored as `Float`.
+    Int(i64),
+    Float(f64),
+    String(IStr),
+    Seq(Vec<IValue>),
+    Map(IValueMap),
+}
+
+impl IValue {
+    /// Value of the key if it's a map.
+    pub fn get(&self, key: &str) -> Option<&IValue> {
+        self.as_map()?.get(key)
+    }
+
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        matches!(*self, IValue::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            IValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            IValue::Int(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Floats and integers as `f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            IValue::Int(v) => Some(v as f64),
+            IValue::Float(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_istr(&self) -> Option<&IStr> {
+        match *self {
+            IValue::String(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_istr().map(IStr::as_str)
+    }
+
+    pub fn as_seq(&self) -> Option<&[IValue]> {
+        match *self {
+            IValue::Seq(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&IValueMap> {
+        match *self {
+            IValue::Map(ref v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl Default for IValue {
+    #[inline]
+    fn default() -> Self {
+        IValue::Null
+    }
+}
+
+impl Serialize for IValue {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            IValue::Null => s.serialize_unit(),
+            IValue::Bool(v) => s.serialize_bool(v),
+            IValue::Int(v) => s.serialize_i64(v),
+            IValue::Float(v) => s.serialize_f64(v),
+            IValue::String(ref v) => v.serialize(s),
+            IValue::Seq(ref v) => {
+                let mut seq = s.serialize_seq(Some(v.len()))?;
+                for elem in v {
+                    seq.serialize_element(elem)?;
+                }
+                seq.end()
+            }
+            IValue::Map(ref v) => {
+                let mut map = s.serialize_map(Some(v.len()))?;
+                for (key, value) in v {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'d> Deserialize<'d> for IValue {
+    fn deserialize<D: Deserializer<'d>>(d: D) -> Result<IValue, D::Error> {
+        d.deserialize_any(Visitor)
+    }
+}
+
+struct Visitor;
+
+impl<'d> de::Visitor<'d> for Visitor {
+    type Value = IValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<IValue, E> {
+        Ok(IValue::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<IValue, E> {
+        Ok(IValue::Null)
+    }
+
+    fn visit_some<D: Deserializer<'d>>(self, d: D) -> Result<IValue, D::Error> {
+        IValue::deserialize(d)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<IValue, E> {
+        Ok(IValue::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<IValue, E> {
+        Ok(IValue::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<IValue, E> {
+        if v <= i64::MAX as u64 {
+            Ok(IValue::Int(v as i64))
+        } else {
+            Ok(IValue::Float(v as f64))
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<IValue, E> {
+        Ok(IValue::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<IValue, E> {
+        Ok(IValue::String(IStr::new(v)))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'d>>(self, mut seq: A) -> Result<IValue, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+
+        Ok(IValue::Seq(values))
+    }
+
+    fn visit_map<A: de::MapAccess<'d>>(self, mut map: A) -> Result<IValue, A::Error> {
+        let mut values = IValueMap::new();
+
+        while let Some((key, value)) = map.next_entry()? {
+            values.insert(key, value);
+        }
+
+        Ok(IValue::Map(values))
+    }
+}
+
+/// Conversion from the parsed TOML value. Datetimes become strings.
+#[cfg(feature = "toml")]
+impl From<::toml::Value> for IValue {
+    fn from(value: ::toml::Value) -> IValue {
+        fn convert(value: ::toml::Value) -> IValue {
+            use toml::Value;
+
+            match value {
+                Value::Boolean(v) => IValue::Bool(v),
+                Value::Integer(v) => IValue::Int(v),
+                Value::Float(v) => IValue::Float(v),
+                Value::String(v) => IValue::String(v.into()),
+                Value::Datetime(v) => IValue::String(v.to_string().into()),
+                Value::Array(v) => IValue::Seq(v.into_iter().map(convert).collect()),
+                Value::Table(v) => IValue::Map(v.into_iter()
+                    .map(|(key, value)| (key.into(), convert(value)))
+                    .collect()),
+            }
+        }
+
+        convert(value)
+    }
+}
+
+/// Conversion from the parsed YAML value. Fails on the keys which are not strings.
+#[cfg(feature = "yaml")]
+impl ::std::convert::TryFrom<::serde_yaml::Value> for IValue {
+    type Error = ::serde_yaml::Error;
+
+    fn try_from(value: ::serde_yaml::Value) -> Result<IValue, Self::Error> {
+        IValue::deserialize(value)
+    }
+}
+
+#[cfg(all(test, any(feature = "toml", feature = "yaml")))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml() {
+        let src = "[server]\nport = 80\nstarted = 1979-05-27T07:32:00Z\n";
+        let value = IValue::from(src.parse::<::toml::Value>().unwrap());
+
+        assert_eq!(value.get("server").unwrap().get("port"), Some(&IValue::Int(80)));
+        assert_eq!(value.get("server").unwrap().get("started").unwrap().as_str(), Some("1979-05-27T07:32:00Z"));
+        assert_eq!(::toml::from_str::<IValue>("a = [1, 2]").unwrap().get("a").unwrap().as_seq().unwrap().len(), 2);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml() {
+        use std::convert::TryFrom;
+
+        let src = "server:\n  port: 80\n  hosts: [a, b]\n";
+        let value = IValue::try_from(::serde_yaml::from_str::<::serde_yaml::Value>(src).unwrap()).unwrap();
+
+        assert_eq!(value, ::serde_yaml::from_str::<IValue>(src).unwrap());
+        assert_eq!(value.get("server").unwrap().get("port"), Some(&IValue::Int(80)));
+        assert!(IValue::try_from(::serde_yaml::from_str::<::serde_yaml::Value>("[1]: x").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::str::FromStr;
+use std::error::Error;
+use std::fmt;
+
+use istr::IStr;
+
+/// Interned semantic version
+///
+/// The version string is validated by the rules of Semantic Versioning 2.0.0 and interned,
+/// while the numeric components are parsed once and stored alongside.
+/// Versions are ordered by semver precedence, and versions with same precedence
+/// are ordered by their build metadata to keep it consistent with `Eq`.
+///
+/// ```
+/// # use internship::IVersion;
+/// let a: IVersion = "1.0.0-alpha.2".parse().unwrap();
+/// let b: IVersion = "1.0.0-alpha.10".parse().unwrap();
+/// let c: IVersion = "1.0.0".parse().unwrap();
+///
+/// assert!(a < b && b < c);
+/// assert_eq!(b.pre(), "alpha.10");
+/// ```
+#[derive(Clone)]
+pub struct IVersion {
+    text: IStr,
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+/// Error returned when the version is not a valid semver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidVersion;
+
+impl IVersion {
+    pub fn new(src: &str) -> Result<Self, InvalidVersion> {
+        let (rest, build) = match src.find('+') {
+            Some(idx) => (&src[..idx], Some(&src[idx + 1..])),
+            None => (src, None),
+        };
+        let (core, pre) = match rest.find('-') {
+            Some(idx) => (&rest[..idx], Some(&rest[idx + 1..])),
+            None => (rest, None),
+        };
+
+        let mut nums = core.split('.');
+        let major = parse_num(nums.next())?;
+        let minor = parse_num(nums.next())?;
+        let patch = parse_num(nums.next())?;
+
+        if nums.next().is_some() {
+            return Err(InvalidVersion);
+        }
+
+        if let Some(pre) = pre {
+            for ident in pre.split('.') {
+                validate_ident(ident)?;
+
+                if ident.len() > 1 && ident.starts_with('0') && is_numeric(ident) {
+                    return Err(InvalidVersion);
+                }
+            }
+        }
+
+        if let Some(build) = build {
+            for ident in build.split('.') {
+                validate_ident(ident)?;
+            }
+        }
+
+        Ok(IVersion {
+            text: IStr::new(src),
+            major,
+            minor,
+            patch,
+        })
+    }
+
+    #[inline]
+    pub fn major(&self) -> u64 {
+        self.major
+    }
+
+    #[inline]
+    pub fn minor(&self) -> u64 {
+        self.minor
+    }
+
+    #[inline]
+    pub fn patch(&self) -> u64 {
+        self.patch
+    }
+
+    fn split(&self) -> (&str, &str) {
+        let text = self.text.as_str();
+        let (rest, build) = match text.find('+') {
+            Some(idx) => (&text[..idx], &text[idx + 1..]),
+            None => (text, ""),
+        };
+        let pre = match rest.find('-') {
+            Some(idx) => &rest[idx + 1..],
+            None => "",
+        };
+
+        (pre, build)
+    }
+
+    /// Pre-release part, or empty string if this is not a pre-release.
+    #[inline]
+    pub fn pre(&self) -> &str {
+        self.split().0
+    }
+
+    /// Build metadata, or empty string if not exist.
+    #[inline]
+    pub fn build(&self) -> &str {
+        self.split().1
+    }
+
+    #[inline]
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre().is_empty()
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.text.as_str()
+    }
+
+    #[inline]
+    pub fn as_istr(&self) -> &IStr {
+        &self.text
+    }
+
+    /// Compare by semver precedence, which ignores build metadata.
+    pub fn cmp_precedence(&self, other: &IVersion) -> Ordering {
+        (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| cmp_pre(self.pre(), other.pre()))
+    }
+}
+
+fn parse_num(src: Option<&str>) -> Result<u64, InvalidVersion> {
+    let src = src.ok_or(InvalidVersion)?;
+
+    if src.is_empty() || !is_numeric(src) || (src.len() > 1 && src.starts_with('0')) {
+        return Err(InvalidVersion);
+    }
+
+    src.parse().map_err(|_| InvalidVersion)
+}
+
+fn is_numeric(ident: &str) -> bool {
+    ident.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn validate_ident(ident: &str) -> Result<(), InvalidVersion> {
+    if ident.is_empty() || !ident.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+        Err(InvalidVersion)
+    } else {
+        Ok(())
+    }
+}
+
+fn cmp_pre(a: &str, b: &str) -> Ordering {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Greater,
+        (false, true) => return Ordering::Less,
+        (false, false) => {}
+    }
+
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+
+    loop {
+        let ord = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => match (is_numeric(a), is_numeric(b)) {
+                (true, true) => a.len().cmp(&b.len()).then_with(|| a.cmp(b)),
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (false, false) => a.cmp(b),
+            },
+        };
+
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+impl PartialEq for IVersion {
+    #[inline]
+    fn eq(&self, other: &IVersion) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for IVersion {}
+
+impl PartialOrd for IVersion {
+    #[inline]
+    fn partial_cmp(&self, other: &IVersion) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IVersion {
+    fn cmp(&self, other: &IVersion) -> Ordering {
+        if self.text == other.text {
+            return Ordering::Equal;
+        }
+
+        self.cmp_precedence(other).then_with(|| self.build().cmp(other.build()))
+    }
+}
+
+impl Hash for IVersion {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.text.hash(hasher)
+    }
+}
+
+impl FromStr for IVersion {
+    type Err = InvalidVersion;
+
+    fn from_str(src: &str) -> Result<Self, InvalidVersion> {
+        IVersion::new(src)
+    }
+}
+
+impl Deref for IVersion {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for IVersion {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<IVersion> for IStr {
+    #[inline]
+    fn from(v: IVersion) -> IStr {
+        v.text
+    }
+}
+
+impl fmt::Debug for IVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for IVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for InvalidVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid semantic version")
+    }
+}
+
+impl Error for InvalidVersion {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence() {
+        let versions = [
+            "1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta",
+            "1.0.0-beta.2", "1.0.0-beta.11", "1.0.0-rc.1", "1.0.0", "1.0.0+build", "2.0.0",
+        ];
+        let versions: Vec<IVersion> = versions.iter().map(|v| v.parse().unwrap()).collect();
+
+        for pair in versions.windows(2) {
+            assert!(pair[0] < pair[1], "{} < {}", pair[0], pair[1]);
+        }
+
+        assert_eq!(versions[7].cmp_precedence(&versions[8]), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for v in &["1.0", "01.0.0", "1.0.0-", "1.0.0-01", "1.0.0+", "1.0.0.0", "1.a.0"] {
+            assert_eq!(IVersion::new(v), Err(InvalidVersion), "{}", v);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::cmp::Reverse;
+
+use istr::IStr;
+
+/// Vocabulary mapping interned terms to dense `u32` ids
+///
+/// If out-of-vocabulary handling is enabled, id `0` is reserved for unknown terms
+/// and known terms start from `1`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vocab {
+    terms: Vec<IStr>,
+    ids: HashMap<IStr, u32>,
+    oov: bool,
+}
+
+/// Builder of `Vocab` which counts term frequencies
+#[derive(Debug, Clone, Default)]
+pub struct VocabBuilder {
+    counts: HashMap<IStr, u64>,
+    min_count: u64,
+    max_size: Option<usize>,
+    oov: bool,
+}
+
+impl VocabBuilder {
+    pub fn new() -> Self {
+        VocabBuilder::default()
+    }
+
+    /// Exclude terms which occurs less than `count` times.
+    pub fn min_count(mut self, count: u64) -> Self {
+        self.min_count = count;
+        self
+    }
+
+    /// Keep at most `size` most frequent terms.
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Reserve id `0` for out-of-vocabulary terms.
+    pub fn oov(mut self, oov: bool) -> Self {
+        self.oov = oov;
+        self
+    }
+
+    #[inline]
+    pub fn add(&mut self, term: &str) {
+        match self.counts.get_mut(term) {
+            Some(count) => *count += 1,
+            None => {
+                self.counts.insert(IStr::new(term), 1);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn add_istr(&mut self, term: IStr) {
+        *self.counts.entry(term).or_insert(0) += 1;
+    }
+
+    /// Build the vocabulary. Terms are ordered by descending frequency,
+    /// and ties are broken by the term itself so the result is deterministic.
+    pub fn build(self) -> Vocab {
+        let min_count = self.min_count;
+        let mut terms: Vec<_> = self.counts.into_iter()
+            .filter(|&(_, count)| count >= min_count)
+            .collect();
+
+        terms.sort_by(|a, b| (Reverse(a.1), &a.0).cmp(&(Reverse(b.1), &b.0)));
+
+        if let Some(size) = self.max_size {
+            terms.truncate(size);
+        }
+
+        Vocab::from_terms(terms.into_iter().map(|(term, _)| term), self.oov)
+    }
+}
+
+impl Extend<IStr> for VocabBuilder {
+    fn extend<I: IntoIterator<Item = IStr>>(&mut self, iter: I) {
+        for term in iter {
+            self.add_istr(term);
+        }
+    }
+}
+
+impl<'a> Extend<&'a str> for VocabBuilder {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        for term in iter {
+            self.add(term);
+        }
+    }
+}
+
+impl Vocab {
+    /// Create vocabulary from the terms in id order. Duplicated terms are ignored.
+    pub fn from_terms<I: IntoIterator<Item = IStr>>(terms: I, oov: bool) -> Self {
+        let mut vocab = Vocab {
+            terms: Vec::new(),
+            ids: HashMap::new(),
+            oov,
+        };
+
+        for term in terms {
+            if !vocab.ids.contains_key(&term) {
+                let id = vocab.terms.len() as u32 + vocab.offset();
+                vocab.ids.insert(term.clone(), id);
+                vocab.terms.push(term);
+            }
+        }
+
+        vocab
+    }
+
+    #[inline]
+    fn offset(&self) -> u32 {
+        self.oov as u32
+    }
+
+    /// Id of the term, or `None` if it's not in vocabulary.
+    #[inline]
+    pub fn get(&self, term: &str) -> Option<u32> {
+        self.ids.get(term).cloned()
+    }
+
+    /// Id of the term. Unknown terms are mapped to `oov_id()` if enabled.
+    #[inline]
+    pub fn encode(&self, term: &str) -> Option<u32> {
+        self.get(term).or_else(|| self.oov_id())
+    }
+
+    #[inline]
+    pub fn term(&self, id: u32) -> Option<&IStr> {
+        id.checked_sub(self.offset()).and_then(|idx| self.terms.get(idx as usize))
+    }
+
+    #[inline]
+    pub fn oov_id(&self) -> Option<u32> {
+        if self.oov { Some(0) } else { None }
+    }
+
+    /// Number of ids, including the one reserved for out-of-vocabulary terms.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.terms.len() + self.offset() as usize
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Terms in id order, excluding out-of-vocabulary id.
+    #[inline]
+    pub fn terms(&self) -> &[IStr] {
+        &self.terms
+    }
+}
+
+#[cfg(feature = "serde-compat")]
+mod serde_compat {
+    use super::*;
+    use serde_crate::{Serialize, Serializer, Deserialize, Deserializer};
+
+    impl Serialize for Vocab {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            Serialize::serialize(&(self.oov, &self.terms), s)
+        }
+    }
+
+    impl<'d> Deserialize<'d> for Vocab {
+        fn deserialize<D: Deserializer<'d>>(d: D) -> Result<Vocab, D::Error> {
+            let (oov, terms): (bool, Vec<IStr>) = Deserialize::deserialize(d)?;
+            Ok(Vocab::from_terms(terms, oov))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let mut builder = VocabBuilder::new().min_count(2).oov(true);
+        builder.extend("b a c a b a d".split(' '));
+        let vocab = builder.build();
+
+        assert_eq!(vocab.len(), 3);
+        assert_eq!(vocab.get("a"), Some(1));
+        assert_eq!(vocab.get("b"), Some(2));
+        assert_eq!(vocab.get("c"), None);
+        assert_eq!(vocab.encode("c"), Some(0));
+        assert_eq!(vocab.term(2), Some(&IStr::new("b")));
+        assert_eq!(vocab.term(0), None);
+    }
+}